@@ -1,14 +1,30 @@
-use crate::config::Config;
+use crate::config::{
+    ArchiveMode, BrokenSymlinkPolicy, CompressionMode, Config, DepthLimitMode, ReflinkMode,
+    MAX_IO_RETRY_DELAY,
+};
 use crate::error::QbakError;
-use crate::naming::{generate_backup_name, resolve_collision};
-use crate::progress::{create_progress_bar, BackupProgress};
+use crate::naming::{
+    find_existing_backups, generate_backup_name, generate_backup_name_in,
+    generate_combined_backup_name, parse_backup_name, resolve_collision_with,
+    resolve_combined_collision, COMBINED_BACKUP_STEM,
+};
+use crate::progress::{
+    create_deferred_progress_bar, create_progress_bar, BackupProgress, ProgressEvent,
+    ProgressFormat,
+};
 
 use crate::utils::{
-    calculate_size, copy_permissions, copy_timestamps, format_size, is_hidden, validate_source,
+    calculate_size, check_available_space_for, check_backup_dir_writable, copy_acls,
+    copy_permissions, copy_timestamps, create_private_dir_all, format_size_for, inode_key,
+    is_hidden, is_preserved_symlink, is_transient_io_error, mtime_nanos, set_mode, sha256_file,
+    sha256_reader, special_file_kind, try_reflink, try_sparse_copy, validate_backup_filename,
+    validate_backup_location, validate_source, Filters, InodeSet, ACL_SUPPORT,
 };
 use crate::Result;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 #[derive(Debug)]
@@ -17,7 +33,79 @@ pub struct BackupResult {
     pub backup_path: PathBuf,
     pub files_processed: usize,
     pub total_size: u64,
+    pub files_verified: usize,
+    pub files_copied: usize,
+    pub files_linked: usize,
+    /// Bytes not written because `dedup` hardlinked identical files instead
+    pub bytes_deduplicated: u64,
+    /// Bytes not written because `preserve_hardlinks` linked files hardlinked in the source
+    pub bytes_hardlinked: u64,
+    /// On-disk size of a compressed backup (gzip or zip); `None` when stored uncompressed
+    pub stored_size: Option<u64>,
     pub duration: Duration,
+    /// Per-entry outcomes of a directory backup, filled only with `record_entries`
+    pub entries: Vec<BackupEntry>,
+    /// Entries a directory backup left out, with the reason
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    /// Source files that changed while being copied, so their backup may be inconsistent
+    pub changed: Vec<PathBuf>,
+}
+
+/// A single file, directory or symlink considered during a directory backup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupEntry {
+    pub source: PathBuf,
+    /// Where the entry was written; the path inside the archive for tar and zip backups
+    pub backup: PathBuf,
+    pub size: u64,
+    pub outcome: EntryOutcome,
+}
+
+/// What happened to an entry of a directory backup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryOutcome {
+    /// Copied into the backup
+    Copied,
+    /// Hardlinked from the previous backup in incremental mode
+    Linked,
+    /// Recreated as a symlink
+    Symlink,
+    /// Left out of the backup, with the reason
+    Skipped(String),
+}
+
+/// Why a directory backup left an entry out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// Hidden while `include_hidden` is off
+    Hidden,
+    /// Excluded by include/exclude patterns
+    Excluded,
+    /// Larger than `max_file_size`
+    Oversize,
+    /// Not modified since `newer_than`
+    Unchanged,
+    /// A followed symlink whose target is missing
+    BrokenSymlink,
+    /// A FIFO, socket or device
+    SpecialFile,
+    /// A symlink `backup_into` left out because `follow_symlinks` is off
+    Symlink,
+}
+
+impl SkipReason {
+    /// Short name used in summaries
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::Hidden => "hidden",
+            SkipReason::Excluded => "excluded",
+            SkipReason::Oversize => "oversize",
+            SkipReason::Unchanged => "unchanged",
+            SkipReason::BrokenSymlink => "broken symlink",
+            SkipReason::SpecialFile => "special file",
+            SkipReason::Symlink => "symlink",
+        }
+    }
 }
 
 impl BackupResult {
@@ -27,38 +115,162 @@ impl BackupResult {
             backup_path,
             files_processed: 0,
             total_size: 0,
+            files_verified: 0,
+            files_copied: 0,
+            files_linked: 0,
+            bytes_deduplicated: 0,
+            bytes_hardlinked: 0,
+            stored_size: None,
             duration: Duration::from_secs(0),
+            entries: Vec::new(),
+            skipped: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    /// Record the outcome for an entry if `record_entries` is enabled
+    fn record(
+        &mut self,
+        config: &Config,
+        source: &Path,
+        backup: &Path,
+        size: u64,
+        outcome: EntryOutcome,
+    ) {
+        if config.record_entries {
+            self.entries.push(BackupEntry {
+                source: source.to_path_buf(),
+                backup: backup.to_path_buf(),
+                size,
+                outcome,
+            });
+        }
+    }
+
+    /// Note an entry the backup left out
+    fn skip(&mut self, path: &Path, reason: SkipReason) {
+        self.skipped.push((path.to_path_buf(), reason));
+    }
+
+    /// Note a source file that changed while it was copied; callers decide how to warn
+    fn note_changed(&mut self, path: &Path) {
+        self.changed.push(path.to_path_buf());
+    }
+
+    /// Tally of skipped entries, e.g. `3 entries skipped (2 excluded, 1 oversize)`
+    ///
+    /// Returns `None` if nothing was skipped.
+    pub fn skipped_summary(&self) -> Option<String> {
+        if self.skipped.is_empty() {
+            return None;
+        }
+        let mut counts: std::collections::BTreeMap<SkipReason, usize> = Default::default();
+        for (_, reason) in &self.skipped {
+            *counts.entry(*reason).or_default() += 1;
+        }
+        let details: Vec<String> = counts
+            .iter()
+            .map(|(reason, count)| format!("{count} {}", reason.as_str()))
+            .collect();
+        let total = self.skipped.len();
+        let noun = if total == 1 { "entry" } else { "entries" };
+        Some(format!("{total} {noun} skipped ({})", details.join(", ")))
+    }
+
+    /// Point recorded entries at the final backup location once the staging directory is renamed
+    fn rebase_entries(&mut self, staging: &Path, final_path: &Path) {
+        for entry in &mut self.entries {
+            if let Ok(relative) = entry.backup.strip_prefix(staging) {
+                entry.backup = final_path.join(relative);
+            }
         }
     }
 
     pub fn summary(&self) -> String {
+        self.summary_with(&Config::default())
+    }
+
+    /// One-line summary with sizes formatted according to `config`
+    pub fn summary_with(&self, config: &Config) -> String {
+        // Styling is a no-op unless colors were enabled (see `--color`)
+        let label = console::style("Created backup:").green();
+        let size = console::style(self.size_with(config)).dim();
         if self.files_processed == 1 {
-            format!(
-                "Created backup: {} ({})",
-                self.backup_path.display(),
-                format_size(self.total_size)
-            )
+            format!("{label} {} ({size})", self.backup_path.display())
         } else {
             format!(
-                "Created backup: {} ({} files, {})",
+                "{label} {} ({} files, {size})",
                 self.backup_path.display(),
                 self.files_processed,
-                format_size(self.total_size)
             )
         }
     }
+
+    /// Stored size as a percentage of the original size, for compressed backups
+    pub fn stored_percent(&self) -> Option<u64> {
+        let stored = self.stored_size?;
+        if self.total_size == 0 {
+            return None;
+        }
+        Some((u128::from(stored) * 100 / u128::from(self.total_size)) as u64)
+    }
+
+    /// Total size formatted according to `config`, with the stored size and ratio
+    /// (e.g. `10.0 MB → 2.1 MB (21%)`) for compressed backups
+    pub fn size_with(&self, config: &Config) -> String {
+        let size = format_size_for(self.total_size, config);
+        let Some(stored) = self.stored_size else {
+            return size;
+        };
+        let stored = format_size_for(stored, config);
+        match self.stored_percent() {
+            Some(percent) => format!("{size} → {stored} ({percent}%)"),
+            None => format!("{size} → {stored}"),
+        }
+    }
 }
 
 /// Backup a single file
 pub fn backup_file(source: &Path, config: &Config) -> Result<BackupResult> {
+    backup_file_with_progress(source, config, false, true)
+}
+
+/// Backup a single file with a byte-based progress bar for large files
+pub fn backup_file_with_progress(
+    source: &Path,
+    config: &Config,
+    force_progress: bool,
+    quiet: bool,
+) -> Result<BackupResult> {
+    backup_single_file(source, None, config, force_progress, quiet, None)
+}
+
+/// Back up a single file, reporting copied bytes to `shared` when one bar spans several
+/// targets, or to a bar of its own otherwise
+///
+/// The backup goes into `into` if given, e.g. a file's directory under a `backup_into`
+/// destination, instead of the configured output directory.
+fn backup_single_file<'a>(
+    source: &Path,
+    into: Option<&Path>,
+    config: &Config,
+    force_progress: bool,
+    quiet: bool,
+    shared: Option<&mut BackupProgress<'a>>,
+) -> Result<BackupResult> {
+    if is_preserved_symlink(source, config) {
+        return backup_symlink(source, into, config);
+    }
+
     let start_time = Instant::now();
 
     // Validate source
-    validate_source(source)?;
+    validate_source(source, config)?;
 
     // Generate backup name
-    let backup_path = generate_backup_name(source, config)?;
-    let final_backup_path = resolve_collision(&backup_path)?;
+    let backup_path = generate_backup_name_in(source, into, config)?;
+    let final_backup_path = resolve_collision_with(source, &backup_path, config)?;
+    prepare_output_dir(&final_backup_path, into, config)?;
 
     // Register operation for cleanup tracking
     let _operation_guard = crate::signal::create_backup_guard(final_backup_path.clone());
@@ -66,30 +278,95 @@ pub fn backup_file(source: &Path, config: &Config) -> Result<BackupResult> {
     // Calculate size for reporting
     let file_size = calculate_size(source)?;
 
-    // Perform atomic copy
+    // Fail up front if the backup can't fit
+    if config.check_space {
+        check_available_space_for(file_size, backup_parent(&final_backup_path), config)?;
+    }
+
+    // Check if we should show progress
+    let mut own_progress: Option<BackupProgress<'a>> = if !quiet && shared.is_none() {
+        create_progress_bar(&config.progress, 1, file_size, force_progress)
+    } else {
+        None
+    };
+    if let Some(ref mut prog) = own_progress {
+        prog.start_file_copy(file_size, source);
+    }
+    let mut progress = shared.or(own_progress.as_mut());
+
+    // Perform atomic copy; the temp copy is tracked too, so a kept partial gets listed
     let temp_path = create_temp_backup_path(&final_backup_path)?;
+    let temp_guard = crate::signal::create_backup_guard(temp_path.clone());
+
+    // Copy the file (reflink when possible, otherwise with interrupt checking)
+    let copy_result = copy_watching_source(source, config, || match config.compression {
+        CompressionMode::Gzip => {
+            compress_file_with_interrupt_check(source, &temp_path, config, progress.as_deref_mut())
+        }
+        CompressionMode::None => {
+            copy_file_data(source, &temp_path, config, progress.as_deref_mut())?;
+            Ok(fs::metadata(&temp_path)?.len())
+        }
+    });
 
-    // Copy the file with interrupt checking
-    copy_file_with_interrupt_check(source, &temp_path)?;
+    if let Some(ref mut prog) = own_progress {
+        prog.finish();
+    }
+    let (bytes_written, changed) = copy_result?;
 
     // Copy metadata if configured
     if config.preserve_permissions {
         copy_permissions(source, &temp_path)?;
         copy_timestamps(source, &temp_path)?;
+    } else if let Some(mode) = config.file_mode {
+        set_mode(&temp_path, mode)?;
+    }
+    if config.preserve_acls {
+        copy_acls_or_warn(source, &temp_path);
     }
 
+    let stored_size = match config.compression {
+        CompressionMode::Gzip => Some(fs::metadata(&temp_path)?.len()),
+        CompressionMode::None => None,
+    };
+
     // Atomic rename
-    fs::rename(&temp_path, &final_backup_path)?;
+    finalize_backup(&temp_path, &final_backup_path, config)?;
+    temp_guard.complete();
+
+    // Verify the backup against the source if configured
+    let files_verified = if config.verify {
+        match config.compression {
+            CompressionMode::Gzip => verify_compressed_backup(source, &final_backup_path)?,
+            CompressionMode::None => verify_backup(source, &final_backup_path)?,
+        }
+        1
+    } else {
+        0
+    };
 
     let duration = start_time.elapsed();
 
-    let result = BackupResult {
+    let mut result = BackupResult {
         source_path: source.to_path_buf(),
         backup_path: final_backup_path,
         files_processed: 1,
-        total_size: file_size,
+        total_size: bytes_written,
+        files_verified,
+        files_copied: 1,
+        files_linked: 0,
+        bytes_deduplicated: 0,
+        bytes_hardlinked: 0,
+        stored_size,
         duration,
+        entries: Vec::new(),
+        skipped: Vec::new(),
+        changed: Vec::new(),
     };
+    if changed {
+        result.note_changed(source);
+    }
+    write_manifest_if_enabled(&result, config)?;
 
     // Mark operation as completed (prevents cleanup)
     _operation_guard.complete();
@@ -97,53 +374,256 @@ pub fn backup_file(source: &Path, config: &Config) -> Result<BackupResult> {
     Ok(result)
 }
 
-/// Backup a directory recursively
-pub fn backup_directory(source: &Path, config: &Config, verbose: bool) -> Result<BackupResult> {
+/// Stream a backup of a single file into `writer` instead of creating a backup file
+///
+/// The data is gzip-compressed when configured, and is copied through the same
+/// interrupt-checked, rate-limited loop as file backups. Progress, if shown, is drawn on
+/// stderr. The returned result's `backup_path` is `-`.
+pub fn backup_file_to_writer(
+    source: &Path,
+    config: &Config,
+    writer: &mut impl std::io::Write,
+    force_progress: bool,
+    quiet: bool,
+) -> Result<BackupResult> {
     let start_time = Instant::now();
 
-    // Validate source
-    validate_source(source)?;
+    validate_source(source, config)?;
+    if source.is_dir() {
+        return Err(QbakError::validation(format!(
+            "Only single files can be streamed, but {} is a directory",
+            source.display()
+        )));
+    }
 
-    if !source.is_dir() {
-        return Err(QbakError::validation("Source is not a directory"));
+    let file_size = calculate_size(source)?;
+
+    let mut progress = if !quiet {
+        create_progress_bar(&config.progress, 1, file_size, force_progress)
+    } else {
+        None
+    };
+    if let Some(ref mut prog) = progress {
+        prog.start_file_copy(file_size, source);
     }
 
-    // Generate backup name
-    let backup_path = generate_backup_name(source, config)?;
-    let final_backup_path = resolve_collision(&backup_path)?;
+    let mut source_file = fs::File::open(source)?;
+    let copy_result = match config.compression {
+        CompressionMode::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut *writer, flate2::Compression::default());
+            copy_chunks_with_interrupt_check(
+                &mut source_file,
+                &mut encoder,
+                None,
+                config,
+                progress.as_mut(),
+                &mut 0,
+            )
+            .and_then(|()| encoder.finish().map(drop).map_err(QbakError::from))
+        }
+        CompressionMode::None => copy_chunks_with_interrupt_check(
+            &mut source_file,
+            writer,
+            None,
+            config,
+            progress.as_mut(),
+            &mut 0,
+        ),
+    };
 
-    // Register operation for cleanup tracking
-    let _operation_guard = crate::signal::create_backup_guard(final_backup_path.clone());
+    if let Some(ref mut prog) = progress {
+        prog.finish();
+    }
+    copy_result?;
+    writer.flush()?;
 
-    // Create backup directory
-    fs::create_dir_all(&final_backup_path)?;
+    Ok(BackupResult {
+        source_path: source.to_path_buf(),
+        backup_path: PathBuf::from("-"),
+        files_processed: 1,
+        total_size: file_size,
+        files_verified: 0,
+        files_copied: 1,
+        files_linked: 0,
+        bytes_deduplicated: 0,
+        bytes_hardlinked: 0,
+        stored_size: None,
+        duration: start_time.elapsed(),
+        entries: Vec::new(),
+        skipped: Vec::new(),
+        changed: Vec::new(),
+    })
+}
 
-    // Copy directory contents
-    let mut result = BackupResult::new(source.to_path_buf(), final_backup_path.clone());
+/// Back up a directory's contents into an existing folder, one timestamped backup per file
+///
+/// Instead of a single timestamped copy of the tree, each file gets its own timestamped
+/// backup at the matching place under `dest`, e.g. `src/lib/util.rs` becomes
+/// `dest/lib/util-20250603T145231-qbak.rs`. Subdirectories are created or merged as
+/// needed, and each file's name is resolved with `collision_strategy` within its own
+/// directory. A single file source is backed up into `dest` directly. Symlinks are
+/// followed with `follow_symlinks`; preserved links have no per-file backup and are
+/// skipped. `dest` may not be the source or lie inside it.
+pub fn backup_into(source: &Path, dest: &Path, config: &Config) -> Result<BackupResult> {
+    let start_time = Instant::now();
 
-    // Check if we should show progress
-    let total_files = count_files_recursive(source, config)?;
-    let show_progress = verbose;
+    let preserved_link = is_preserved_symlink(source, config);
+    if !preserved_link {
+        validate_source(source, config)?;
+        validate_backup_location(source, dest)?;
+    }
+    fs::create_dir_all(dest)?;
 
-    if show_progress {
-        println!("Backing up directory with {total_files} files...");
+    let mut result = BackupResult::new(source.to_path_buf(), dest.to_path_buf());
+    if source.is_dir() && !preserved_link {
+        let mut walk = Traversal::new(config)?;
+        backup_contents_into(source, dest, config, &mut result, &mut walk)?;
+    } else {
+        add_file_backup_into(source, dest, config, &mut result)?;
     }
 
-    copy_directory_contents(
-        source,
-        &final_backup_path,
+    result.duration = start_time.elapsed();
+    Ok(result)
+}
+
+/// Recursive helper for `backup_into`
+fn backup_contents_into(
+    source_dir: &Path,
+    dest_dir: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+    walk: &mut Traversal,
+) -> Result<()> {
+    let canonical = walk.enter(source_dir)?;
+
+    for entry in fs::read_dir(source_dir)? {
+        if crate::signal::is_interrupted() {
+            return Err(QbakError::Interrupted);
+        }
+
+        let entry = entry?;
+        let source_path = entry.path();
+        let dest_path = dest_dir.join(entry.file_name());
+
+        if !config.include_hidden && is_hidden(&source_path) {
+            result.skip(&source_path, SkipReason::Hidden);
+            continue;
+        }
+        if !walk.filters.matches(&source_path, source_path.is_dir()) {
+            result.skip(&source_path, SkipReason::Excluded);
+            continue;
+        }
+
+        let mut metadata = entry.metadata()?;
+        let mut real_path = source_path.clone();
+        if metadata.file_type().is_symlink() {
+            if !config.follow_symlinks {
+                // A link has no per-file backup to give it
+                result.skip(&source_path, SkipReason::Symlink);
+                let outcome = EntryOutcome::Skipped("symlink".to_string());
+                result.record(config, &source_path, &dest_path, 0, outcome);
+                continue;
+            }
+            if !source_path.exists() {
+                broken_symlink_to_preserve(&source_path, config)?;
+                result.skip(&source_path, SkipReason::BrokenSymlink);
+                continue;
+            }
+            real_path = resolve_symlink_target(&source_path)?;
+            metadata = fs::metadata(&real_path)?;
+        }
+
+        if metadata.is_file() {
+            if skip_filtered_file(&source_path, &dest_path, &metadata, config, result) {
+                continue;
+            }
+            // Named after the link, with the content of its target
+            add_file_backup_into(&source_path, dest_dir, config, result)?;
+        } else if metadata.is_dir() {
+            if !walk.can_descend(config) {
+                continue;
+            }
+            fs::create_dir_all(&dest_path)?;
+            backup_contents_into(&real_path, &dest_path, config, result, walk)?;
+        } else {
+            warn_special_file(&source_path, &dest_path, &metadata, config, result);
+        }
+    }
+
+    walk.leave(&canonical);
+    Ok(())
+}
+
+/// Back up a single file into `dest_dir` and add it to the totals of `backup_into`
+fn add_file_backup_into(
+    source: &Path,
+    dest_dir: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+) -> Result<()> {
+    let file_result = backup_single_file(source, Some(dest_dir), config, false, true, None)?;
+
+    result.files_processed += 1;
+    result.files_copied += 1;
+    result.total_size += file_result.total_size;
+    result.files_verified += file_result.files_verified;
+    if let Some(stored) = file_result.stored_size {
+        result.stored_size = Some(result.stored_size.unwrap_or(0) + stored);
+    }
+    result.record(
         config,
-        &mut result,
-        show_progress,
-    )?;
+        source,
+        &file_result.backup_path,
+        file_result.total_size,
+        EntryOutcome::Copied,
+    );
+    Ok(())
+}
 
-    // Set directory permissions if configured
-    if config.preserve_permissions {
-        copy_permissions(source, &final_backup_path)?;
-        copy_timestamps(source, &final_backup_path)?;
+/// Backup a directory recursively
+pub fn backup_directory(source: &Path, config: &Config, verbose: bool) -> Result<BackupResult> {
+    if is_preserved_symlink(source, config) {
+        return backup_symlink(source, None, config);
+    }
+
+    // Archives are written by the streaming path, which handles progress itself
+    if config.archive != ArchiveMode::None {
+        return backup_directory_with_progress(source, config, false, true);
     }
 
-    if show_progress {
+    let start_time = Instant::now();
+
+    // Validate source
+    validate_source(source, config)?;
+
+    if !source.is_dir() {
+        return Err(QbakError::validation("Source is not a directory"));
+    }
+
+    // Walk the tree once to count it; the totals also serve the space check
+    let total_size = if uses_single_pass(source, config) {
+        if verbose {
+            println!("Backing up {} without a prescan...", source.display());
+        }
+        None
+    } else {
+        if verbose {
+            println!("Scanning {}...", source.display());
+        }
+        let (total_files, total_size) = count_files_and_size(source, config)?;
+        if verbose {
+            println!(
+                "Backing up directory with {total_files} files ({})...",
+                format_size_for(total_size, config)
+            );
+        }
+        Some(total_size)
+    };
+
+    let mut result = backup_scanned_directory(source, config, total_size, None)?;
+
+    if verbose {
         println!(
             "Directory backup completed: {} files processed",
             result.files_processed
@@ -152,9 +632,6 @@ pub fn backup_directory(source: &Path, config: &Config, verbose: bool) -> Result
 
     result.duration = start_time.elapsed();
 
-    // Mark operation as completed (prevents cleanup)
-    _operation_guard.complete();
-
     Ok(result)
 }
 
@@ -164,8 +641,10 @@ fn copy_directory_contents(
     backup_dir: &Path,
     config: &Config,
     result: &mut BackupResult,
-    show_progress: bool,
+    walk: &mut Traversal,
 ) -> Result<()> {
+    let canonical = walk.enter(source_dir)?;
+
     for entry in fs::read_dir(source_dir)? {
         // Check for interrupt signal
         if crate::signal::is_interrupted() {
@@ -178,40 +657,141 @@ fn copy_directory_contents(
 
         // Skip hidden files if not configured to include them
         if !config.include_hidden && is_hidden(&source_path) {
+            result.skip(&source_path, SkipReason::Hidden);
             continue;
         }
 
         let backup_path = backup_dir.join(&file_name);
         let metadata = entry.metadata()?;
 
-        if metadata.is_file() {
-            // Copy file
-            copy_file_to_backup(&source_path, &backup_path, config, result)?;
+        // Skip entries filtered out by include/exclude patterns
+        if !walk.filters.matches(&source_path, source_path.is_dir()) {
+            result.skip(&source_path, SkipReason::Excluded);
+            continue;
+        }
 
-            // Show progress if enabled
-            if show_progress && result.files_processed % 10 == 0 {
-                eprint!(".");
-                use std::io::{self, Write};
-                io::stderr().flush().unwrap_or(());
+        if metadata.is_file() {
+            if skip_filtered_file(&source_path, &backup_path, &metadata, config, result) {
+                continue;
             }
+
+            // Copy file
+            copy_or_dedup_file(&source_path, &backup_path, config, result, walk)?;
         } else if metadata.is_dir() {
+            if !walk.can_descend(config) {
+                create_depth_limited_dir(&backup_path, config)?;
+                continue;
+            }
+
             // Create directory and recurse
-            fs::create_dir_all(&backup_path)?;
-            copy_directory_contents(&source_path, &backup_path, config, result, show_progress)?;
+            create_backup_dir(&backup_path, config)?;
+            copy_directory_contents(&source_path, &backup_path, config, result, walk)?;
 
-            // Set directory permissions
-            if config.preserve_permissions {
-                copy_permissions(&source_path, &backup_path)?;
-                copy_timestamps(&source_path, &backup_path)?;
+            // Drop directories left empty by include patterns
+            if prune_empty_directory(&backup_path, config) {
+                continue;
             }
+            walk.directories.push((source_path, backup_path));
         } else if metadata.file_type().is_symlink() {
             // Handle symlinks
-            handle_symlink(&source_path, &backup_path, config, result, show_progress)?;
+            handle_symlink(&source_path, &backup_path, config, result, walk)?;
+        } else {
+            warn_special_file(&source_path, &backup_path, &metadata, config, result);
+        }
+    }
+
+    walk.leave(&canonical);
+    Ok(())
+}
+
+/// Copy a file within a directory backup, or hardlink a file already written to it
+///
+/// With `preserve_hardlinks`, a further link to a source file already backed up is linked
+/// to its copy; with `dedup`, so is any file identical to one already written.
+fn copy_or_dedup_file(
+    source: &Path,
+    backup: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+    walk: &mut Traversal,
+) -> Result<()> {
+    if !config.dedup && !config.preserve_hardlinks {
+        return copy_file_to_backup(source, backup, config, result);
+    }
+
+    let metadata = fs::metadata(source)?;
+    let inode = inode_key(&metadata).filter(|_| config.preserve_hardlinks);
+    if let Some(existing) = inode.and_then(|inode| walk.hardlinks.get(&inode)) {
+        if link_duplicate_file(source, &metadata, existing, backup, config, result)? {
+            result.bytes_hardlinked += metadata.len();
+            return Ok(());
+        }
+    }
+
+    if config.dedup {
+        let key = (metadata.len(), sha256_file(source)?);
+        let linked = match walk.written.get(&key) {
+            Some(existing) => {
+                link_duplicate_file(source, &metadata, existing, backup, config, result)?
+            }
+            None => false,
+        };
+        if linked {
+            result.bytes_deduplicated += metadata.len();
+        } else {
+            copy_file_to_backup(source, backup, config, result)?;
+            walk.written
+                .entry(key)
+                .or_insert_with(|| backup.to_path_buf());
         }
+    } else {
+        copy_file_to_backup(source, backup, config, result)?;
+    }
+
+    if let Some(inode) = inode {
+        walk.hardlinks
+            .entry(inode)
+            .or_insert_with(|| backup.to_path_buf());
     }
     Ok(())
 }
 
+/// Hardlink `backup` to an identical file already written to this backup
+///
+/// Returns false if the permissions to preserve differ or linking fails (e.g. across
+/// filesystems), in which case the file must be copied.
+fn link_duplicate_file(
+    source: &Path,
+    metadata: &fs::Metadata,
+    existing: &Path,
+    backup: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+) -> Result<bool> {
+    // Linked files share metadata, so only link files that would end up with the same mode
+    if config.preserve_permissions
+        && fs::metadata(existing)?.permissions() != metadata.permissions()
+    {
+        return Ok(false);
+    }
+
+    if fs::hard_link(existing, backup).is_err() {
+        return Ok(false);
+    }
+
+    if config.verify {
+        verify_backup(source, backup)?;
+        result.files_verified += 1;
+    }
+
+    result.files_processed += 1;
+    result.files_linked += 1;
+    result.total_size += metadata.len();
+    result.record(config, source, backup, metadata.len(), EntryOutcome::Linked);
+
+    Ok(true)
+}
+
 /// Copy a single file within a directory backup
 fn copy_file_to_backup(
     source: &Path,
@@ -222,393 +802,860 @@ fn copy_file_to_backup(
     // Create temp file for atomic operation
     let temp_path = create_temp_backup_path(backup)?;
 
-    // Copy file with interrupt checking
-    copy_file_with_interrupt_check(source, &temp_path)?;
+    // Copy file (reflink when possible, otherwise with interrupt checking)
+    let (file_size, changed) = copy_watching_source(source, config, || {
+        copy_file_data(source, &temp_path, config, None)?;
+        Ok(fs::metadata(&temp_path)?.len())
+    })?;
+    if changed {
+        result.note_changed(source);
+    }
 
     // Copy metadata if configured
     if config.preserve_permissions {
         copy_permissions(source, &temp_path)?;
         copy_timestamps(source, &temp_path)?;
+    } else if let Some(mode) = config.file_mode {
+        set_mode(&temp_path, mode)?;
+    }
+    if config.preserve_acls {
+        copy_acls_or_warn(source, &temp_path);
     }
 
     // Atomic rename
-    fs::rename(&temp_path, backup)?;
+    finalize_backup(&temp_path, backup, config)?;
 
-    // Update statistics
-    let file_size = fs::metadata(source)?.len();
+    // Verify the backup against the source if configured
+    if config.verify {
+        verify_backup(source, backup)?;
+        result.files_verified += 1;
+    }
+
+    // Update statistics with what was actually written
     result.files_processed += 1;
+    result.files_copied += 1;
     result.total_size += file_size;
+    result.record(config, source, backup, file_size, EntryOutcome::Copied);
 
     Ok(())
 }
 
-/// Copy a file while checking for interrupt signals
-fn copy_file_with_interrupt_check(source: &Path, dest: &Path) -> Result<()> {
-    use std::io::{Read, Write};
-
-    let mut source_file = fs::File::open(source)?;
-    let mut dest_file = fs::File::create(dest)?;
+/// Move a completed temp file or staging directory to its final backup name
+///
+/// Temp files and staging directories are always created next to the final name, so the
+/// rename stays on one filesystem; should it fail with `EXDEV` anyway, that is reported as
+/// `CrossDevice`. With `fsync` on, the containing directory is synced afterwards so the
+/// rename survives a crash.
+fn finalize_backup(temp: &Path, final_path: &Path, config: &Config) -> Result<()> {
+    if config.overwrite && fs::symlink_metadata(final_path).is_ok() {
+        replace_backup(temp, final_path)?;
+    } else {
+        finalize_backup_with(temp, final_path, |from, to| fs::rename(from, to))?;
+    }
+    if config.fsync {
+        sync_parent_dir(final_path)?;
+    }
+    Ok(())
+}
 
-    let mut buffer = vec![0u8; 64 * 1024]; // 64KB buffer
+/// Put the fully staged backup `temp` in place of the existing backup at `final_path`
+///
+/// A file simply replaces a file. Otherwise the existing backup is moved aside first and
+/// only deleted once the new one is in place, so a crash in between leaves it under a
+/// `.qbak_old_` name rather than losing both.
+fn replace_backup(temp: &Path, final_path: &Path) -> Result<()> {
+    let rename = |from: &Path, to: &Path| fs::rename(from, to);
+    let existing_is_dir = fs::symlink_metadata(final_path)?.is_dir();
+    if !existing_is_dir && !fs::symlink_metadata(temp)?.is_dir() {
+        return finalize_backup_with(temp, final_path, rename);
+    }
 
-    loop {
-        // Check for interrupt before reading each chunk
-        if crate::signal::is_interrupted() {
-            // Clean up partial file
-            let _ = fs::remove_file(dest);
-            return Err(QbakError::Interrupted);
-        }
+    let filename = final_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| QbakError::validation("Invalid backup filename"))?;
+    let random_suffix = crate::utils::generate_secure_random_string(16);
+    let aside = final_path.with_file_name(format!(".qbak_old_{random_suffix}_{filename}"));
+    fs::rename(final_path, &aside)?;
 
-        let bytes_read = source_file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break; // EOF
-        }
+    if let Err(e) = finalize_backup_with(temp, final_path, rename) {
+        let _ = fs::rename(&aside, final_path);
+        return Err(e);
+    }
 
-        dest_file.write_all(&buffer[..bytes_read])?;
+    if existing_is_dir {
+        fs::remove_dir_all(&aside)?;
+    } else {
+        fs::remove_file(&aside)?;
     }
+    Ok(())
+}
 
-    dest_file.flush()?;
+/// Flush a file's data and metadata to disk
+fn sync_file(path: &Path) -> Result<()> {
+    // Windows needs write access to flush a file's buffers
+    fs::OpenOptions::new().write(true).open(path)?.sync_all()?;
     Ok(())
 }
 
-/// Handle symlink based on configuration
-fn handle_symlink(
-    source: &Path,
-    backup: &Path,
-    config: &Config,
-    result: &mut BackupResult,
-    show_progress: bool,
-) -> Result<()> {
-    if config.follow_symlinks {
-        // Follow the symlink and copy the target
-        let target = fs::read_link(source)?;
-        let resolved_target = if target.is_absolute() {
-            target
-        } else {
-            source.parent().unwrap_or(Path::new(".")).join(target)
-        };
+/// Flush the directory entry for `path`, making a rename into it durable
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent)?.sync_all()?;
+    Ok(())
+}
 
-        if resolved_target.exists() {
-            let metadata = fs::metadata(&resolved_target)?;
-            if metadata.is_file() {
-                copy_file_to_backup(&resolved_target, backup, config, result)?;
-            } else if metadata.is_dir() {
-                fs::create_dir_all(backup)?;
-                copy_directory_contents(&resolved_target, backup, config, result, show_progress)?;
-            }
-        }
-    } else {
-        // Preserve the symlink as-is
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::symlink;
-            let target = fs::read_link(source)?;
-            symlink(target, backup)?;
-        }
+/// Directories can't be opened for syncing on this platform
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
 
-        #[cfg(not(unix))]
-        {
-            // On non-Unix systems, copy the target file instead
-            let target = fs::read_link(source)?;
-            let resolved_target = if target.is_absolute() {
-                target
-            } else {
-                source.parent().unwrap_or(Path::new(".")).join(target)
-            };
+/// `finalize_backup` with an injectable rename, so the cross-device error can be tested
+fn finalize_backup_with<F>(temp: &Path, final_path: &Path, rename: F) -> Result<()>
+where
+    F: Fn(&Path, &Path) -> std::io::Result<()>,
+{
+    // Renaming over an existing empty directory would silently succeed on Unix
+    if fs::symlink_metadata(temp)?.is_dir() && final_path.exists() {
+        return Err(QbakError::BackupExists {
+            path: final_path.to_path_buf(),
+        });
+    }
 
-            if resolved_target.exists() && resolved_target.is_file() {
-                copy_file_to_backup(&resolved_target, backup, config, result)?;
+    // The temp copy is left for the caller's cleanup
+    rename(temp, final_path).map_err(|e| {
+        if is_cross_device_error(&e) {
+            QbakError::CrossDevice {
+                from: temp.to_path_buf(),
+                to: final_path.to_path_buf(),
             }
+        } else {
+            QbakError::Io(e)
         }
+    })
+}
+
+/// Check whether an IO error is the "cross-device link" error returned by rename
+fn is_cross_device_error(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    const EXDEV: i32 = 18;
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(windows)]
+    const EXDEV: i32 = 17;
+
+    #[cfg(any(unix, windows))]
+    {
+        error.raw_os_error() == Some(EXDEV)
     }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = error;
+        false
+    }
+}
 
+/// Copy permissions, timestamps and ACLs (or apply `dir_mode`) onto backed up directories
+///
+/// Runs once every file is written, since adding entries to a directory would bump its
+/// mtime again. `directories` lists children before parents, so setting a directory's
+/// metadata never disturbs one already handled.
+fn apply_directory_metadata(directories: &[(PathBuf, PathBuf)], config: &Config) -> Result<()> {
+    for (source, backup) in directories {
+        if config.preserve_permissions {
+            copy_permissions(source, backup)?;
+            copy_timestamps(source, backup)?;
+        } else if let Some(mode) = config.dir_mode {
+            set_mode(backup, mode)?;
+        }
+        if config.preserve_acls {
+            copy_acls_or_warn(source, backup);
+        }
+    }
     Ok(())
 }
 
-/// Create a temporary backup path for atomic operations
-fn create_temp_backup_path(backup_path: &Path) -> Result<PathBuf> {
-    let parent = backup_path.parent().unwrap_or(Path::new("."));
-    let filename = backup_path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .ok_or_else(|| QbakError::validation("Invalid backup filename"))?;
-
-    // Use cryptographically secure random string instead of predictable process ID
-    let random_suffix = crate::utils::generate_secure_random_string(16);
-    let temp_name = format!(".qbak_temp_{random_suffix}_{filename}");
-    Ok(parent.join(temp_name))
+/// Create a directory of a backup in progress
+///
+/// When its final mode comes from the source or `dir_mode`, it starts out accessible to
+/// the owner only and gets that mode from `apply_directory_metadata` once its contents
+/// are written, so no file is ever exposed more widely than it will end up. Otherwise the
+/// umask decides.
+fn create_backup_dir(path: &Path, config: &Config) -> Result<()> {
+    if config.preserve_permissions || config.dir_mode.is_some() {
+        create_private_dir_all(path)
+    } else {
+        Ok(fs::create_dir_all(path)?)
+    }
 }
 
-/// Count the total number of files in a directory recursively
-fn count_files_recursive(dir: &Path, config: &Config) -> Result<usize> {
-    let mut count = 0;
-
-    if !dir.is_dir() {
-        return Ok(1); // Single file
+/// Create the configured output or dated directory, or `into`, if missing and check it is
+/// writable
+fn prepare_output_dir(backup: &Path, into: Option<&Path>, config: &Config) -> Result<()> {
+    if into.is_none() && config.output_dir.is_none() && config.dated_dir.is_none() {
+        return Ok(());
     }
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+    fs::create_dir_all(backup_parent(backup))?;
+    // With `overwrite`, an existing backup is replaced once the new one is staged
+    if config.overwrite {
+        check_backup_dir_writable(backup)
+    } else {
+        validate_backup_filename(backup)
+    }
+}
 
-        // Skip hidden files if not configured to include them
-        if !config.include_hidden && is_hidden(&path) {
-            continue;
-        }
+/// Directory a backup is created in, used for the disk space preflight
+fn backup_parent(backup: &Path) -> &Path {
+    match backup.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
 
-        let metadata = entry.metadata()?;
+/// Set once the warning about a build without ACL support has been printed
+static ACL_SUPPORT_WARNED: AtomicBool = AtomicBool::new(false);
 
-        if metadata.is_file() {
-            count += 1;
-        } else if metadata.is_dir() {
-            count += count_files_recursive(&path, config)?;
-        } else if metadata.file_type().is_symlink() && config.follow_symlinks {
-            // Count symlink targets if we're following them
-            let target = fs::read_link(&path)?;
-            let resolved_target = if target.is_absolute() {
-                target
-            } else {
-                path.parent().unwrap_or(Path::new(".")).join(target)
-            };
+/// Whether the missing ACL support still needs to be reported; true only on the first call
+fn take_acl_support_warning() -> bool {
+    !ACL_SUPPORT_WARNED.swap(true, Ordering::Relaxed)
+}
 
-            if resolved_target.exists() {
-                let target_metadata = fs::metadata(&resolved_target)?;
-                if target_metadata.is_file() {
-                    count += 1;
-                } else if target_metadata.is_dir() {
-                    count += count_files_recursive(&resolved_target, config)?;
-                }
-            }
+/// Copy POSIX ACLs, warning instead of failing when the filesystem doesn't support them
+///
+/// A build without ACL support can't copy them for any file, so that is reported once per
+/// run rather than for every file.
+fn copy_acls_or_warn(source: &Path, dest: &Path) {
+    if !ACL_SUPPORT {
+        if take_acl_support_warning() {
+            eprintln!(
+                "Warning: preserve_acls is set, but ACL support is not available in this build (requires the `acl` feature on Linux)"
+            );
         }
+        return;
     }
 
-    Ok(count)
+    if let Err(e) = copy_acls(source, dest) {
+        eprintln!(
+            "Warning: Could not preserve ACLs for {}: {e}",
+            source.display()
+        );
+    }
 }
 
-/// Clean up any temporary files that might be left over
-pub fn cleanup_temp_files(dir: &Path) -> Result<()> {
-    if !dir.is_dir() {
-        return Ok(());
+/// Warn about a FIFO, socket or device skipped during directory traversal
+fn warn_special_file(
+    path: &Path,
+    backup: &Path,
+    metadata: &fs::Metadata,
+    config: &Config,
+    result: &mut BackupResult,
+) {
+    if let Some(kind) = special_file_kind(&metadata.file_type()) {
+        eprintln!("Warning: Skipping {kind}: {}", path.display());
+        result.skip(path, SkipReason::SpecialFile);
+        result.record(
+            config,
+            path,
+            backup,
+            0,
+            EntryOutcome::Skipped(kind.to_string()),
+        );
     }
+}
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if let Some(filename) = path.file_name().and_then(|name| name.to_str()) {
-            if filename.starts_with(".qbak_temp_") {
-                // Try to remove temp file
-                let _ = fs::remove_file(&path);
-            }
-        }
+/// Compare the SHA-256 checksums of a source file and its backup
+fn verify_backup(source: &Path, backup: &Path) -> Result<()> {
+    if sha256_file(source)? != sha256_file(backup)? {
+        return Err(QbakError::VerificationFailed {
+            path: backup.to_path_buf(),
+        });
     }
-
     Ok(())
 }
 
-/// Backup a directory with progress indication
-pub fn backup_directory_with_progress(
-    source: &Path,
-    config: &Config,
-    force_progress: bool,
-    quiet: bool,
-) -> Result<BackupResult> {
-    let start_time = Instant::now();
-
-    // Validate source
-    validate_source(source)?;
-
-    // Generate backup name
-    let backup_path = generate_backup_name(source, config)?;
-    let final_backup_path = resolve_collision(&backup_path)?;
-
-    // Register operation for cleanup tracking
-    let _operation_guard = crate::signal::create_backup_guard(final_backup_path.clone());
-
-    // First, count files and calculate size (scanning phase)
-    let (file_count, total_size) = count_files_and_size(source, config)?;
-
-    // Check if we should show progress
-    let mut progress = if !quiet {
-        create_progress_bar(&config.progress, file_count, total_size, force_progress)
-    } else {
-        None
-    };
-
-    // Start progress if available
-    if let Some(ref mut prog) = progress {
-        prog.start_scanning();
-        prog.finish_scanning(file_count, total_size);
+/// Compare the SHA-256 checksum of a source file with its decompressed gzip backup
+fn verify_compressed_backup(source: &Path, backup: &Path) -> Result<()> {
+    let decoder = flate2::read::GzDecoder::new(fs::File::open(backup)?);
+    if sha256_file(source)? != sha256_reader(decoder)? {
+        return Err(QbakError::VerificationFailed {
+            path: backup.to_path_buf(),
+        });
     }
+    Ok(())
+}
 
-    // Create backup directory
-    fs::create_dir_all(&final_backup_path)?;
-
-    // Initialize result
-    let mut result = BackupResult::new(source.to_path_buf(), final_backup_path.clone());
-
-    // Copy contents with progress tracking
-    let copy_result = copy_directory_contents_with_progress(
-        source,
-        &final_backup_path,
-        config,
-        &mut result,
-        &mut progress.as_mut(),
-    );
+/// A difference between an existing backup and its source, found by `verify_against_source`
+///
+/// Paths are relative to the backup and source roots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// A file in the source is missing from the backup
+    Missing(PathBuf),
+    /// A file in the backup no longer exists in the source
+    Extra(PathBuf),
+    /// A file differs in size between source and backup
+    SizeMismatch {
+        path: PathBuf,
+        source: u64,
+        backup: u64,
+    },
+    /// A file has the same size but a different SHA-256 checksum
+    ChecksumMismatch(PathBuf),
+}
 
-    // If interrupted, clear progress bar immediately
-    if let Err(QbakError::Interrupted) = copy_result {
-        if let Some(ref mut prog) = progress {
-            prog.finish();
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "missing from backup: {}", path.display()),
+            Self::Extra(path) => write!(f, "not in source: {}", path.display()),
+            Self::SizeMismatch {
+                path,
+                source,
+                backup,
+            } => write!(
+                f,
+                "size differs: {} (source {source} bytes, backup {backup} bytes)",
+                path.display()
+            ),
+            Self::ChecksumMismatch(path) => write!(f, "content differs: {}", path.display()),
         }
-        return Err(QbakError::Interrupted);
     }
+}
 
-    // Handle other errors
-    copy_result?;
+/// Outcome of checking an existing backup against its source
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Files present in both source and backup that were compared
+    pub files_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
 
-    // Finish progress normally
-    if let Some(ref mut prog) = progress {
-        prog.finish();
+impl VerifyReport {
+    /// Whether the backup matches the source
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
     }
-
-    let duration = start_time.elapsed();
-    result.duration = duration;
-
-    // Mark operation as completed (prevents cleanup)
-    _operation_guard.complete();
-
-    Ok(result)
 }
 
-/// Count files and calculate total size, with optional progress
-pub fn count_files_and_size_with_progress(source: &Path, config: &Config) -> Result<(usize, u64)> {
-    let mut progress = create_progress_bar(&config.progress, 0, 0, true);
-    if let Some(ref mut prog) = progress {
-        prog.start_scanning();
+/// Check an existing plain file or directory backup against the current source
+///
+/// The source is walked with the same hidden-file, pattern, filter, depth and symlink
+/// rules as a backup, so entries a backup leaves out aren't reported as missing. Files
+/// are compared by size, and also by SHA-256 checksum with `checksums`. Symlinks preserved
+/// as links are not compared.
+pub fn verify_against_source(
+    backup: &Path,
+    source: &Path,
+    config: &Config,
+    checksums: bool,
+) -> Result<VerifyReport> {
+    if !source.exists() {
+        return Err(QbakError::SourceNotFound {
+            path: source.to_path_buf(),
+        });
+    }
+    if !backup.exists() {
+        return Err(QbakError::SourceNotFound {
+            path: backup.to_path_buf(),
+        });
     }
 
-    let result = count_files_and_size_recursive(source, config, &mut progress.as_mut());
-
-    if let Some(ref mut prog) = progress {
-        prog.finish();
+    let mut source_files = std::collections::BTreeMap::new();
+    let mut backup_files = std::collections::BTreeMap::new();
+    if source.is_dir() {
+        if !backup.is_dir() {
+            return Err(QbakError::validation(format!(
+                "{} is a directory but {} is not",
+                source.display(),
+                backup.display()
+            )));
+        }
+        collect_source_files(
+            source,
+            Path::new(""),
+            config,
+            &mut Traversal::new(config)?,
+            &mut source_files,
+        )?;
+        collect_backup_files(backup, Path::new(""), &mut backup_files)?;
+    } else {
+        if !backup.is_file() {
+            return Err(QbakError::validation(format!(
+                "{} is a file but {} is not",
+                source.display(),
+                backup.display()
+            )));
+        }
+        let name = PathBuf::from(source.file_name().unwrap_or_default());
+        source_files.insert(name.clone(), source.to_path_buf());
+        backup_files.insert(name, backup.to_path_buf());
     }
 
-    result
-}
+    let mut report = VerifyReport::default();
+    for (relative, source_path) in &source_files {
+        let Some(backup_path) = backup_files.remove(relative) else {
+            report
+                .discrepancies
+                .push(Discrepancy::Missing(relative.clone()));
+            continue;
+        };
+        report.files_checked += 1;
+
+        let source_size = fs::metadata(source_path)?.len();
+        let backup_size = fs::metadata(&backup_path)?.len();
+        if source_size != backup_size {
+            report.discrepancies.push(Discrepancy::SizeMismatch {
+                path: relative.clone(),
+                source: source_size,
+                backup: backup_size,
+            });
+        } else if checksums && sha256_file(source_path)? != sha256_file(&backup_path)? {
+            report
+                .discrepancies
+                .push(Discrepancy::ChecksumMismatch(relative.clone()));
+        }
+    }
+    report
+        .discrepancies
+        .extend(backup_files.into_keys().map(Discrepancy::Extra));
 
-/// Count files and calculate total size, without progress
-pub fn count_files_and_size(source: &Path, config: &Config) -> Result<(usize, u64)> {
-    let mut none_progress = None;
-    count_files_and_size_recursive(source, config, &mut none_progress)
+    Ok(report)
 }
 
-/// Recursive function to count files and calculate total size
-fn count_files_and_size_recursive(
+/// Collect the files a backup of `dir` would contain, keyed by path relative to the source
+fn collect_source_files(
     dir: &Path,
+    relative: &Path,
     config: &Config,
-    progress: &mut Option<&mut BackupProgress>,
-) -> Result<(usize, u64)> {
-    let mut total_files = 0;
-    let mut total_size = 0;
+    walk: &mut Traversal,
+    files: &mut std::collections::BTreeMap<PathBuf, PathBuf>,
+) -> Result<()> {
+    let canonical = walk.enter(dir)?;
 
     for entry in fs::read_dir(dir)? {
-        // Check for interrupt signal during scanning
         if crate::signal::is_interrupted() {
             return Err(QbakError::Interrupted);
         }
 
         let entry = entry?;
         let path = entry.path();
-
-        // Skip hidden files if not configured to include them
         if !config.include_hidden && is_hidden(&path) {
             continue;
         }
+        if !walk.filters.matches(&path, path.is_dir()) {
+            continue;
+        }
 
-        let metadata = entry.metadata()?;
+        let mut metadata = entry.metadata()?;
+        let mut real_path = path.clone();
+        if metadata.file_type().is_symlink() {
+            // Preserved and dangling links aren't compared
+            if !config.follow_symlinks || !path.exists() {
+                continue;
+            }
+            real_path = resolve_symlink_target(&path)?;
+            metadata = fs::metadata(&real_path)?;
+        }
 
+        let name = relative.join(entry.file_name());
         if metadata.is_file() {
-            total_files += 1;
-            total_size += metadata.len();
-
-            // Update scanning progress occasionally
-            if let Some(ref mut p) = progress {
-                if total_files % 100 == 0 {
-                    p.update_scan_progress(total_files, &path);
-                }
+            if passes_file_filters(&metadata, config) {
+                files.insert(name, real_path);
             }
-        } else if metadata.is_dir() {
-            let (sub_files, sub_size) = count_files_and_size_recursive(&path, config, progress)?;
-            total_files += sub_files;
-            total_size += sub_size;
+        } else if metadata.is_dir() && walk.can_descend(config) {
+            collect_source_files(&real_path, &name, config, walk, files)?;
         }
-        // Skip symlinks for size calculation
     }
 
-    Ok((total_files, total_size))
+    walk.leave(&canonical);
+    Ok(())
 }
 
-/// Copy directory contents with progress tracking
-fn copy_directory_contents_with_progress(
-    source_dir: &Path,
-    backup_dir: &Path,
+/// Collect the regular files in a backup directory, keyed by path relative to it
+fn collect_backup_files(
+    dir: &Path,
+    relative: &Path,
+    files: &mut std::collections::BTreeMap<PathBuf, PathBuf>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let name = relative.join(entry.file_name());
+        if file_type.is_file() {
+            files.insert(name, entry.path());
+        } else if file_type.is_dir() {
+            collect_backup_files(&entry.path(), &name, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy file contents, trying a copy-on-write clone first if configured
+fn copy_file_data(
+    source: &Path,
+    dest: &Path,
     config: &Config,
-    result: &mut BackupResult,
-    progress: &mut Option<&mut BackupProgress>,
+    progress: Option<&mut BackupProgress>,
 ) -> Result<()> {
-    for entry in fs::read_dir(source_dir)? {
-        // Check for interrupt signal
+    if config.use_reflink != ReflinkMode::Never {
         if crate::signal::is_interrupted() {
             return Err(QbakError::Interrupted);
         }
 
-        let entry = entry?;
-        let source_path = entry.path();
-        let filename = source_path.file_name().unwrap();
-        let backup_path = backup_dir.join(filename);
+        if try_reflink(source, dest)? {
+            return if config.fsync {
+                sync_file(dest)
+            } else {
+                Ok(())
+            };
+        }
 
-        // Skip hidden files if not configured to include them
-        if !config.include_hidden && is_hidden(&source_path) {
-            continue;
+        if config.use_reflink == ReflinkMode::Always {
+            let _ = fs::remove_file(dest);
+            return Err(QbakError::validation(format!(
+                "Reflink not supported for {}",
+                source.display()
+            )));
         }
+    }
 
-        let metadata = entry.metadata()?;
+    // Sparse copies bypass the chunk loop, so they can't be throttled
+    if config.preserve_sparse && config.rate_limit.is_none() && try_sparse_copy(source, dest)? {
+        return if config.fsync {
+            sync_file(dest)
+        } else {
+            Ok(())
+        };
+    }
 
-        if metadata.is_file() {
-            copy_file_to_backup(&source_path, &backup_path, config, result)?;
+    copy_file_with_interrupt_check(source, dest, config, progress)
+}
 
-            // Update progress
-            if let Some(ref mut prog) = progress {
-                prog.update_backup_progress(
-                    result.files_processed,
-                    result.total_size,
-                    &source_path,
-                );
+/// Hardlink a file from the previous backup if it is unchanged since then
+///
+/// Files match by size and mtime, or by size and checksum with `compare_checksums`.
+/// Returns false if the file changed (or can't be linked) and must be copied.
+fn link_unchanged_file(
+    source: &Path,
+    previous: &Path,
+    backup: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+    compare_checksums: bool,
+) -> Result<bool> {
+    let source_metadata = fs::metadata(source)?;
+    let previous_metadata = match fs::symlink_metadata(previous) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return Ok(false),
+    };
+
+    if source_metadata.len() != previous_metadata.len() {
+        return Ok(false);
+    }
+    let unchanged = if compare_checksums {
+        sha256_file(source)? == sha256_file(previous)?
+    } else {
+        same_mtime(&source_metadata, &previous_metadata)
+    };
+    if !unchanged {
+        return Ok(false);
+    }
+
+    // Linking can fail across filesystems or on filesystems without hardlinks
+    if fs::hard_link(previous, backup).is_err() {
+        return Ok(false);
+    }
+
+    // A checksum match already verified the file
+    if config.verify {
+        if !compare_checksums {
+            verify_backup(source, backup)?;
+        }
+        result.files_verified += 1;
+    }
+
+    result.files_processed += 1;
+    result.files_linked += 1;
+    result.total_size += source_metadata.len();
+    result.record(
+        config,
+        source,
+        backup,
+        source_metadata.len(),
+        EntryOutcome::Linked,
+    );
+
+    Ok(true)
+}
+
+/// Compare modification times of two files at nanosecond precision
+///
+/// Second granularity would treat a file rewritten within the same second as unchanged.
+fn same_mtime(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    mtime_nanos(a) == mtime_nanos(b)
+}
+
+/// Copies made after the first when `recopy_changed_files` finds the source changed
+const MAX_RECOPIES: usize = 3;
+
+/// Run `copy`, which copies `source` and returns the bytes it wrote, watching `source`
+///
+/// The source changed during the copy if its size or mtime differs afterwards, or if the
+/// copy wrote a different number of bytes than it now holds. With `recopy_changed_files`
+/// a changed file is copied again, up to `MAX_RECOPIES` times. Returns the bytes written
+/// by the last copy and whether the source changed during it.
+fn copy_watching_source(
+    source: &Path,
+    config: &Config,
+    mut copy: impl FnMut() -> Result<u64>,
+) -> Result<(u64, bool)> {
+    let mut recopies = 0;
+    loop {
+        let before = fs::metadata(source)?;
+        let written = copy()?;
+        let after = fs::metadata(source)?;
+
+        let changed =
+            before.len() != after.len() || !same_mtime(&before, &after) || written != after.len();
+        if !changed || !config.recopy_changed_files || recopies == MAX_RECOPIES {
+            return Ok((written, changed));
+        }
+        recopies += 1;
+    }
+}
+
+/// Copy a file while checking for interrupt signals and reporting bytes written
+fn copy_file_with_interrupt_check(
+    source: &Path,
+    dest: &Path,
+    config: &Config,
+    progress: Option<&mut BackupProgress>,
+) -> Result<()> {
+    copy_file_with_retries(source, dest, config, progress, |path| fs::File::open(path))
+}
+
+/// Copy a file, resuming up to `io_retries` times after transient I/O errors
+///
+/// A retry reopens both files and continues after the last chunk written in full,
+/// waiting `io_retry_delay` (doubled for each further retry, up to `MAX_IO_RETRY_DELAY`)
+/// first. An interrupt during the wait ends the copy instead.
+fn copy_file_with_retries<R: std::io::Read + std::io::Seek>(
+    source: &Path,
+    dest: &Path,
+    config: &Config,
+    mut progress: Option<&mut BackupProgress>,
+    mut open_source: impl FnMut(&Path) -> std::io::Result<R>,
+) -> Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let mut source_file = open_source(source)?;
+    let mut dest_file = fs::File::create(dest)?;
+    let mut bytes_copied = 0u64;
+    let mut retries = 0;
+
+    loop {
+        let error = match copy_chunks_with_interrupt_check(
+            &mut source_file,
+            &mut dest_file,
+            Some(dest),
+            config,
+            progress.as_deref_mut(),
+            &mut bytes_copied,
+        ) {
+            Ok(()) => break,
+            Err(QbakError::Io(error))
+                if retries < config.io_retries && is_transient_io_error(&error) =>
+            {
+                error
             }
-        } else if metadata.is_dir() {
-            fs::create_dir_all(&backup_path)?;
-            copy_directory_contents_with_progress(
-                &source_path,
-                &backup_path,
-                config,
-                result,
-                progress,
-            )?;
-        } else if metadata.file_type().is_symlink() {
-            handle_symlink_with_progress(&source_path, &backup_path, config, result, progress)?;
+            Err(error) => return Err(error),
+        };
+
+        retries += 1;
+        if config.verbose {
+            let message = format!(
+                "Retrying {} after I/O error: {error} (retry {retries} of {})",
+                source.display(),
+                config.io_retries
+            );
+            match progress {
+                Some(ref prog) => prog.suspend(|| eprintln!("{message}")),
+                None => eprintln!("{message}"),
+            }
+        }
+
+        if !wait_before_retry(retry_delay(config, retries)) {
+            if !crate::signal::keeps_partial() {
+                let _ = fs::remove_file(dest);
+            }
+            return Err(QbakError::Interrupted);
         }
+
+        source_file = open_source(source)?;
+        source_file.seek(SeekFrom::Start(bytes_copied))?;
+        dest_file = fs::OpenOptions::new().write(true).open(dest)?;
+        dest_file.set_len(bytes_copied)?;
+        dest_file.seek(SeekFrom::Start(bytes_copied))?;
+    }
+
+    dest_file.flush()?;
+    if config.fsync {
+        dest_file.sync_all()?;
     }
     Ok(())
 }
 
-/// Handle symlink with progress tracking
-fn handle_symlink_with_progress(
+/// Gzip-compress a file while checking for interrupt signals and reporting bytes read
+fn compress_file_with_interrupt_check(
+    source: &Path,
+    dest: &Path,
+    config: &Config,
+    progress: Option<&mut BackupProgress>,
+) -> Result<u64> {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut source_file = fs::File::open(source)?;
+    let mut encoder = GzEncoder::new(fs::File::create(dest)?, flate2::Compression::default());
+
+    let mut bytes_copied = 0;
+    copy_chunks_with_interrupt_check(
+        &mut source_file,
+        &mut encoder,
+        Some(dest),
+        config,
+        progress,
+        &mut bytes_copied,
+    )?;
+
+    let mut dest_file = encoder.finish()?;
+    dest_file.flush()?;
+    if config.fsync {
+        dest_file.sync_all()?;
+    }
+    Ok(bytes_copied)
+}
+
+/// Stream data in `copy_buffer_size` chunks, removing the partial `dest` (if any) when
+/// interrupted
+///
+/// Interrupts are checked between chunks. With a `rate_limit`, sleeps between chunks to
+/// stay under it. `bytes_copied` counts the bytes written in full, so a failed copy can be
+/// resumed from there.
+fn copy_chunks_with_interrupt_check(
+    reader: &mut impl std::io::Read,
+    writer: &mut impl std::io::Write,
+    dest: Option<&Path>,
+    config: &Config,
+    mut progress: Option<&mut BackupProgress>,
+    bytes_copied: &mut u64,
+) -> Result<()> {
+    let mut buffer = vec![0u8; config.copy_buffer_size];
+    let mut throttle = config.rate_limit.map(Throttle::new);
+
+    loop {
+        // Check for interrupt before reading each chunk
+        if crate::signal::is_interrupted() {
+            // Clean up partial file, unless partials are kept for inspection
+            if let Some(dest) = dest.filter(|_| !crate::signal::keeps_partial()) {
+                let _ = fs::remove_file(dest);
+            }
+            return Err(QbakError::Interrupted);
+        }
+
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break; // EOF
+        }
+
+        writer.write_all(&buffer[..bytes_read])?;
+
+        *bytes_copied += bytes_read as u64;
+        if let Some(ref mut prog) = progress {
+            prog.update_bytes_progress(*bytes_copied);
+        }
+
+        // An interrupt cuts the wait short and is handled at the top of the loop
+        if let Some(ref mut throttle) = throttle {
+            throttle.consume(bytes_read as u64);
+        }
+    }
+
+    Ok(())
+}
+
+/// How long to wait before the `retries`-th retry: `io_retry_delay`, doubled for each
+/// further retry and capped at `MAX_IO_RETRY_DELAY`
+fn retry_delay(config: &Config, retries: u32) -> Duration {
+    2u32.checked_pow(retries.saturating_sub(1))
+        .and_then(|factor| config.io_retry_delay.checked_mul(factor))
+        .unwrap_or(MAX_IO_RETRY_DELAY)
+        .min(MAX_IO_RETRY_DELAY)
+}
+
+/// Sleep for `delay` before retrying a copy, returning false if interrupted meanwhile
+fn wait_before_retry(delay: Duration) -> bool {
+    let deadline = Instant::now() + delay;
+    while !crate::signal::is_interrupted() {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(Throttle::MAX_SLEEP));
+    }
+    false
+}
+
+/// Keeps the average transfer rate of a stream under a limit by sleeping
+///
+/// Each file gets its own throttle with no initial burst. Files are copied one after
+/// another, so the rate across a directory backup stays under the limit too.
+struct Throttle {
+    bytes_per_sec: u64,
+    start: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    /// Longest single sleep, so interrupts are still noticed promptly
+    const MAX_SLEEP: Duration = Duration::from_millis(50);
+
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Account for `bytes` just transferred, sleeping until the average rate is under the
+    /// limit again or an interrupt arrives
+    fn consume(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64);
+
+        while !crate::signal::is_interrupted() {
+            let elapsed = self.start.elapsed();
+            if elapsed >= due {
+                break;
+            }
+            std::thread::sleep((due - elapsed).min(Self::MAX_SLEEP));
+        }
+    }
+}
+
+/// Handle symlink based on configuration
+fn handle_symlink(
     source: &Path,
     backup: &Path,
     config: &Config,
     result: &mut BackupResult,
-    progress: &mut Option<&mut BackupProgress>,
+    walk: &mut Traversal,
 ) -> Result<()> {
     if config.follow_symlinks {
         // Follow the symlink and copy the target
@@ -622,22 +1669,21 @@ fn handle_symlink_with_progress(
         if resolved_target.exists() {
             let metadata = fs::metadata(&resolved_target)?;
             if metadata.is_file() {
-                copy_file_to_backup(&resolved_target, backup, config, result)?;
-
-                // Update progress
-                if let Some(ref mut prog) = progress {
-                    prog.update_backup_progress(result.files_processed, result.total_size, source);
+                if skip_filtered_file(source, backup, &metadata, config, result) {
+                    return Ok(());
                 }
+                copy_or_dedup_file(&resolved_target, backup, config, result, walk)?;
             } else if metadata.is_dir() {
-                fs::create_dir_all(backup)?;
-                copy_directory_contents_with_progress(
-                    &resolved_target,
-                    backup,
-                    config,
-                    result,
-                    progress,
-                )?;
+                if !walk.can_descend(config) {
+                    return create_depth_limited_dir(backup, config);
+                }
+                create_backup_dir(backup, config)?;
+                copy_directory_contents(&resolved_target, backup, config, result, walk)?;
+                walk.directories
+                    .push((resolved_target.clone(), backup.to_path_buf()));
             }
+        } else {
+            handle_broken_symlink(source, backup, config, result)?;
         }
     } else {
         // Preserve the symlink as-is
@@ -646,6 +1692,7 @@ fn handle_symlink_with_progress(
             use std::os::unix::fs::symlink;
             let target = fs::read_link(source)?;
             symlink(target, backup)?;
+            result.record(config, source, backup, 0, EntryOutcome::Symlink);
         }
 
         #[cfg(not(unix))]
@@ -658,436 +1705,4240 @@ fn handle_symlink_with_progress(
                 source.parent().unwrap_or(Path::new(".")).join(target)
             };
 
-            if resolved_target.exists() && resolved_target.is_file() {
-                copy_file_to_backup(&resolved_target, backup, config, result)?;
+            if resolved_target.exists() && resolved_target.is_file() {
+                copy_or_dedup_file(&resolved_target, backup, config, result, walk)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply `symlink_broken_policy` to a followed symlink whose target doesn't exist
+fn handle_broken_symlink(
+    source: &Path,
+    backup: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+) -> Result<()> {
+    match broken_symlink_to_preserve(source, config)? {
+        Some(target) => {
+            #[cfg(unix)]
+            {
+                std::os::unix::fs::symlink(&target, backup)?;
+                result.record(config, source, backup, 0, EntryOutcome::Symlink);
+            }
+
+            #[cfg(not(unix))]
+            eprintln!(
+                "Warning: Cannot preserve broken symlink {} -> {} on this platform",
+                source.display(),
+                target.display()
+            );
+        }
+        None => {
+            result.skip(source, SkipReason::BrokenSymlink);
+            result.record(
+                config,
+                source,
+                backup,
+                0,
+                EntryOutcome::Skipped("broken symlink".to_string()),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the skip, warn and error cases of `symlink_broken_policy`
+///
+/// Returns the link target if the dangling link should be preserved in the backup.
+fn broken_symlink_to_preserve(source: &Path, config: &Config) -> Result<Option<PathBuf>> {
+    let target = fs::read_link(source)?;
+
+    match config.symlink_broken_policy {
+        BrokenSymlinkPolicy::Skip => Ok(None),
+        BrokenSymlinkPolicy::Warn => {
+            eprintln!(
+                "Warning: Skipping broken symlink {} -> {}",
+                source.display(),
+                target.display()
+            );
+            Ok(None)
+        }
+        BrokenSymlinkPolicy::PreserveAsLink => Ok(Some(target)),
+        BrokenSymlinkPolicy::Error => Err(broken_symlink_error(source, &target)),
+    }
+}
+
+fn broken_symlink_error(source: &Path, target: &Path) -> QbakError {
+    QbakError::validation(format!(
+        "Broken symlink: {} -> {}",
+        source.display(),
+        target.display()
+    ))
+}
+
+/// Remove a backup subdirectory that, with `prune_empty_dirs`, ended up empty because of
+/// include patterns or the `newer_than` cutoff
+///
+/// Returns true if the directory was removed.
+fn prune_empty_directory(backup_dir: &Path, config: &Config) -> bool {
+    if !prunes_empty_dirs(config) {
+        return false;
+    }
+
+    // remove_dir only succeeds on empty directories
+    fs::remove_dir(backup_dir).is_ok()
+}
+
+/// Whether directories left empty by the filters are dropped from the backup
+///
+/// Without a filter an empty directory was empty in the source and is always kept.
+fn prunes_empty_dirs(config: &Config) -> bool {
+    let filtered = !config.include_patterns.is_empty() || config.newer_than.is_some();
+    config.prune_empty_dirs && filtered
+}
+
+/// Create a temporary backup path for atomic operations
+fn create_temp_backup_path(backup_path: &Path) -> Result<PathBuf> {
+    let parent = backup_path.parent().unwrap_or(Path::new("."));
+    let filename = backup_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| QbakError::validation("Invalid backup filename"))?;
+
+    // Use cryptographically secure random string instead of predictable process ID
+    let random_suffix = crate::utils::generate_secure_random_string(16);
+    let temp_name = format!(".qbak_temp_{random_suffix}_{filename}");
+    Ok(parent.join(temp_name))
+}
+
+/// Work out where a backup would be restored to, based on its qbak-generated name
+pub fn restore_target_path(backup: &Path, config: &Config) -> Result<PathBuf> {
+    let parent = backup.parent().unwrap_or(Path::new("."));
+
+    // A manifest records the original name, so there's no need to reverse-parse it
+    if let Some(name) = manifest_source_name(backup)? {
+        return Ok(parent.join(name));
+    }
+
+    let parsed = parse_backup_name(backup, config)?;
+    Ok(parent.join(parsed.original_name()))
+}
+
+/// Restore a file or directory backup over its original name
+pub fn restore_backup(backup: &Path, config: &Config, force: bool) -> Result<BackupResult> {
+    let start_time = Instant::now();
+
+    // Validate backup
+    validate_source(backup, config)?;
+
+    let target = restore_target_path(backup, config)?;
+    if target.exists() && !force {
+        return Err(QbakError::validation(format!(
+            "Restore target already exists: {} (use --force to overwrite)",
+            target.display()
+        )));
+    }
+
+    // Restore everything in the backup as-is, regardless of filters
+    let mut restore_config = config.clone();
+    restore_config.follow_symlinks = false;
+    restore_config.include_hidden = true;
+    restore_config.include_patterns.clear();
+    restore_config.exclude_patterns.clear();
+    // The existing target (only there with force) is replaced once the restored copy is
+    // staged next to it, so a failed restore leaves the original untouched
+    restore_config.overwrite = true;
+
+    let mut result = BackupResult::new(backup.to_path_buf(), target.clone());
+
+    if backup.is_dir() {
+        // Copy into a temporary sibling first so the restore is all-or-nothing
+        let temp_path = create_temp_backup_path(&target)?;
+        let _operation_guard = crate::signal::create_backup_guard(temp_path.clone());
+
+        create_backup_dir(&temp_path, &restore_config)?;
+        let mut walk = Traversal::new(&restore_config)?;
+        copy_directory_contents(backup, &temp_path, &restore_config, &mut result, &mut walk)?;
+        walk.directories
+            .push((backup.to_path_buf(), temp_path.clone()));
+        apply_directory_metadata(&walk.directories, &restore_config)?;
+
+        finalize_backup(&temp_path, &target, &restore_config)?;
+        _operation_guard.complete();
+    } else {
+        // copy_file_to_backup copies via a temp file and only then replaces the target
+        copy_file_to_backup(backup, &target, &restore_config, &mut result)?;
+    }
+
+    result.duration = start_time.elapsed();
+
+    Ok(result)
+}
+
+/// Path of the `.qbak.json` sidecar manifest for a backup
+pub fn manifest_path(backup: &Path) -> PathBuf {
+    let mut name = backup.file_name().unwrap_or_default().to_os_string();
+    name.push(".qbak.json");
+    backup.with_file_name(name)
+}
+
+/// Write a `.qbak.json` manifest describing a completed backup next to it
+///
+/// Records the absolute source path, creation time, qbak version, file count and total
+/// size, plus SHA-256 checksums of the backed up files with `manifest_checksums`. The
+/// manifest sits outside the backup, so it is never part of its own checksums, and is
+/// written to a temporary file first so it only appears once complete.
+pub fn write_manifest(result: &BackupResult, config: &Config) -> Result<PathBuf> {
+    let source = match result.source_path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => std::env::current_dir()?.join(&result.source_path),
+    };
+
+    let mut manifest = serde_json::json!({
+        "qbak_version": env!("CARGO_PKG_VERSION"),
+        "source": source.display().to_string(),
+        "backup": result.backup_path.display().to_string(),
+        "created": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        "files": result.files_processed,
+        "total_size": result.total_size,
+    });
+
+    if config.manifest_checksums {
+        let mut checksums = serde_json::Map::new();
+        if result.backup_path.is_dir() {
+            collect_checksums(&result.backup_path, &result.backup_path, &mut checksums)?;
+        } else {
+            let name = result.backup_path.file_name().unwrap_or_default();
+            checksums.insert(
+                name.to_string_lossy().into_owned(),
+                checksum_hex(&result.backup_path)?.into(),
+            );
+        }
+        manifest["checksums"] = serde_json::Value::Object(checksums);
+    }
+
+    let path = manifest_path(&result.backup_path);
+    let temp_path = create_temp_backup_path(&path)?;
+    let _operation_guard = crate::signal::create_backup_guard(temp_path.clone());
+
+    let mut contents = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| QbakError::validation(format!("Could not serialize manifest: {e}")))?;
+    contents.push('\n');
+    fs::write(&temp_path, contents)?;
+    if config.fsync {
+        sync_file(&temp_path)?;
+    }
+    fs::rename(&temp_path, &path)?;
+    if config.fsync {
+        sync_parent_dir(&path)?;
+    }
+
+    _operation_guard.complete();
+    Ok(path)
+}
+
+fn write_manifest_if_enabled(result: &BackupResult, config: &Config) -> Result<()> {
+    if config.write_manifest {
+        write_manifest(result, config)?;
+    }
+    Ok(())
+}
+
+/// Add the checksum of every regular file under `dir`, keyed by its `/`-separated path
+/// relative to `root`
+fn collect_checksums(
+    root: &Path,
+    dir: &Path,
+    checksums: &mut serde_json::Map<String, serde_json::Value>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = fs::symlink_metadata(&path)?;
+
+        if metadata.is_dir() {
+            collect_checksums(root, &path, checksums)?;
+        } else if metadata.is_file() {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let key = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            checksums.insert(key, checksum_hex(&path)?.into());
+        }
+    }
+    Ok(())
+}
+
+fn checksum_hex(path: &Path) -> Result<String> {
+    Ok(sha256_file(path)?
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Original file or directory name recorded in a backup's manifest, if it has one
+fn manifest_source_name(backup: &Path) -> Result<Option<String>> {
+    let path = manifest_path(backup);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| QbakError::validation(format!("Invalid manifest {}: {e}", path.display())))?;
+    let name = manifest["source"]
+        .as_str()
+        .and_then(|source| Path::new(source).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            QbakError::validation(format!("Manifest has no source path: {}", path.display()))
+        })?;
+    Ok(Some(name))
+}
+
+/// Delete all but the newest `keep` backups of `source`, returning the removed paths
+///
+/// Backups are matched by name (see `find_existing_backups`) and ordered by their embedded
+/// timestamp, then modification time. `created` is the backup this run just made: it is never
+/// removed and takes one of the `keep` slots. With `dry_run` nothing is deleted and the paths
+/// that would be removed are returned instead.
+pub fn prune_old_backups(
+    source: &Path,
+    config: &Config,
+    keep: usize,
+    created: Option<&Path>,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut backups = find_existing_backups(source, config)?;
+    let mut keep = keep;
+    if let Some(created) = created {
+        let before = backups.len();
+        // All candidates live in one directory, and `created` may be spelled without `./`
+        backups.retain(|backup| backup.file_name() != created.file_name());
+        if backups.len() < before {
+            keep = keep.saturating_sub(1);
+        }
+    }
+    let old_backups: Vec<PathBuf> = backups.into_iter().skip(keep).collect();
+
+    if !dry_run {
+        for backup in &old_backups {
+            let metadata = fs::symlink_metadata(backup)?;
+            if metadata.is_dir() {
+                fs::remove_dir_all(backup)?;
+            } else {
+                fs::remove_file(backup)?;
+            }
+
+            let manifest = manifest_path(backup);
+            if manifest.is_file() {
+                fs::remove_file(manifest)?;
+            }
+        }
+    }
+
+    Ok(old_backups)
+}
+
+/// Clean up any temporary files that might be left over
+pub fn cleanup_temp_files(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if let Some(filename) = path.file_name().and_then(|name| name.to_str()) {
+            if filename.starts_with(".qbak_temp_") {
+                // Try to remove temp file
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Back up a symlink target by recreating the link itself next to it
+///
+/// Used instead of following the link when `follow_symlinks` is off (see
+/// `is_preserved_symlink`). The link target is copied verbatim, so a dangling link is
+/// preserved as well.
+#[cfg(unix)]
+fn backup_symlink(source: &Path, into: Option<&Path>, config: &Config) -> Result<BackupResult> {
+    let start_time = Instant::now();
+
+    let link_target = fs::read_link(source)?;
+    let backup_path = generate_backup_name_in(source, into, config)?;
+    let final_backup_path = resolve_collision_with(source, &backup_path, config)?;
+    prepare_output_dir(&final_backup_path, into, config)?;
+
+    std::os::unix::fs::symlink(&link_target, &final_backup_path)?;
+
+    let mut result = BackupResult::new(source.to_path_buf(), final_backup_path.clone());
+    result.files_processed = 1;
+    result.record(config, source, &final_backup_path, 0, EntryOutcome::Symlink);
+    result.duration = start_time.elapsed();
+    Ok(result)
+}
+
+#[cfg(not(unix))]
+fn backup_symlink(_source: &Path, _into: Option<&Path>, _config: &Config) -> Result<BackupResult> {
+    unreachable!("symlinks are only preserved on Unix")
+}
+
+/// Backup a directory with progress indication
+pub fn backup_directory_with_progress(
+    source: &Path,
+    config: &Config,
+    force_progress: bool,
+    quiet: bool,
+) -> Result<BackupResult> {
+    if is_preserved_symlink(source, config) {
+        return backup_symlink(source, None, config);
+    }
+
+    let start_time = Instant::now();
+
+    // Validate source
+    validate_source(source, config)?;
+
+    if uses_single_pass(source, config) {
+        return backup_directory_single_pass(source, config, force_progress, quiet);
+    }
+
+    // First, count files and calculate size (scanning phase)
+    let (file_count, total_size) = count_files_and_size(source, config)?;
+
+    // Check if we should show progress; small operations get a bar only if they turn out slow
+    let mut progress = if !quiet {
+        create_progress_bar(&config.progress, file_count, total_size, force_progress)
+            .or_else(|| create_deferred_progress_bar(&config.progress))
+    } else {
+        None
+    };
+
+    // Start progress if available
+    if let Some(ref mut prog) = progress {
+        prog.start_scanning();
+        prog.finish_scanning(file_count, total_size);
+    }
+
+    let backup_result =
+        backup_scanned_directory(source, config, Some(total_size), progress.as_mut());
+
+    // Clear the progress bar whether or not the backup succeeded
+    if let Some(ref mut prog) = progress {
+        prog.finish();
+    }
+
+    let mut result = backup_result?;
+    result.duration = start_time.elapsed();
+
+    Ok(result)
+}
+
+/// Whether a directory backup of `source` copies while walking instead of scanning first
+///
+/// True with `single_pass`, and for sources on network filesystems, where listing every
+/// directory twice is expensive.
+pub fn uses_single_pass(source: &Path, config: &Config) -> bool {
+    config.single_pass || (source.is_dir() && crate::utils::is_remote_filesystem(source))
+}
+
+/// Back up a directory without counting it first, reporting running totals
+fn backup_directory_single_pass(
+    source: &Path,
+    config: &Config,
+    force_progress: bool,
+    quiet: bool,
+) -> Result<BackupResult> {
+    let start_time = Instant::now();
+
+    // Nothing is known about the size up front, so the bar appears if forced or slow
+    let mut progress = if !quiet {
+        create_progress_bar(&config.progress, 0, 0, force_progress)
+            .or_else(|| create_deferred_progress_bar(&config.progress))
+    } else {
+        None
+    };
+    if let Some(ref mut prog) = progress {
+        prog.start_unscanned();
+    }
+
+    let backup_result = backup_scanned_directory(source, config, None, progress.as_mut());
+
+    if let Some(ref mut prog) = progress {
+        prog.finish();
+    }
+
+    let mut result = backup_result?;
+    result.duration = start_time.elapsed();
+
+    Ok(result)
+}
+
+/// Backup a directory, reporting progress to `callback` instead of a terminal
+///
+/// For library users that draw their own progress UI. The callback sees the scan, an
+/// update after every file and a final `ProgressEvent::Finished`, even if the backup fails.
+pub fn backup_directory_with_callback(
+    source: &Path,
+    config: &Config,
+    callback: impl FnMut(ProgressEvent),
+) -> Result<BackupResult> {
+    if is_preserved_symlink(source, config) {
+        return backup_symlink(source, None, config);
+    }
+
+    let start_time = Instant::now();
+
+    // Validate source
+    validate_source(source, config)?;
+
+    let mut progress = BackupProgress::with_callback(config.progress.clone(), callback);
+    progress.start_scanning();
+
+    let backup_result = count_files_and_size_recursive(source, config, &mut Some(&mut progress))
+        .and_then(|totals| {
+            progress.finish_scanning(totals.files, totals.size);
+            backup_scanned_directory(source, config, Some(totals.size), Some(&mut progress))
+        });
+
+    progress.finish();
+
+    let mut result = backup_result?;
+    result.duration = start_time.elapsed();
+
+    Ok(result)
+}
+
+/// Backup a directory as part of a larger operation that drives a shared progress bar
+///
+/// The caller has already scanned the directory (see `count_files_and_size`) and passes
+/// its total size; per-file updates are added on top of the progress made so far.
+pub fn backup_directory_with_shared_progress(
+    source: &Path,
+    config: &Config,
+    total_size: u64,
+    progress: &mut BackupProgress,
+) -> Result<BackupResult> {
+    progress.start_target();
+    if is_preserved_symlink(source, config) {
+        let result = backup_symlink(source, None, config)?;
+        progress.update_backup_progress(1, 0, source);
+        return Ok(result);
+    }
+    backup_scanned_directory(source, config, Some(total_size), Some(progress))
+}
+
+/// Backup a single file as part of a larger operation that drives a shared progress bar
+pub fn backup_file_with_shared_progress(
+    source: &Path,
+    config: &Config,
+    progress: &mut BackupProgress,
+) -> Result<BackupResult> {
+    progress.start_target();
+    progress.update_backup_progress(0, 0, source);
+    let result = backup_single_file(source, None, config, false, true, Some(progress))?;
+    progress.update_backup_progress(1, result.total_size, source);
+    Ok(result)
+}
+
+/// Back up several targets into a single tar or zip archive
+///
+/// The archive gets a synthetic name (e.g. `backup-20250603T145231-qbak.tar`, see
+/// `generate_combined_backup_name`) and holds each target as a top-level entry named after
+/// it, so two targets with the same name are rejected. Collision resolution and the space
+/// check apply to the one archive. The result's `source_path` is the synthetic name.
+pub fn backup_combined(
+    sources: &[PathBuf],
+    config: &Config,
+    mut progress: Option<&mut BackupProgress>,
+) -> Result<BackupResult> {
+    let start_time = Instant::now();
+
+    if config.archive == ArchiveMode::None {
+        return Err(QbakError::validation(
+            "Combining targets needs an archive format (--tar or --zip)",
+        ));
+    }
+
+    let mut entries: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(sources.len());
+    let mut total_size = 0;
+    for source in sources {
+        let preserved_link = is_preserved_symlink(source, config);
+        if !preserved_link {
+            validate_source(source, config)?;
+        }
+
+        let root = archive_root_name(source)?;
+        if let Some((other, _)) = entries.iter().find(|(_, name)| *name == root) {
+            return Err(QbakError::validation(format!(
+                "{} and {} would both be stored as {} in the combined archive",
+                other.display(),
+                source.display(),
+                root.display()
+            )));
+        }
+
+        total_size += if preserved_link {
+            0
+        } else if source.is_dir() {
+            count_files_and_size(source, config)?.1
+        } else {
+            calculate_size(source)?
+        };
+        entries.push((source.clone(), root));
+    }
+
+    let backup_path = generate_combined_backup_name(config)?;
+    let final_backup_path = resolve_combined_collision(&backup_path, config)?;
+    for (source, _) in &entries {
+        if !is_preserved_symlink(source, config) {
+            validate_backup_location(source, &final_backup_path)?;
+        }
+    }
+    prepare_output_dir(&final_backup_path, None, config)?;
+
+    // Fail up front if the backup can't fit
+    if config.check_space {
+        check_available_space_for(total_size, backup_parent(&final_backup_path), config)?;
+    }
+
+    // Write to a temporary name so the final name only appears once complete
+    let staging_path = create_temp_backup_path(&final_backup_path)?;
+    let _operation_guard = crate::signal::create_backup_guard(staging_path.clone());
+
+    let mut result = BackupResult::new(
+        PathBuf::from(COMBINED_BACKUP_STEM),
+        final_backup_path.clone(),
+    );
+    archive_sources(&entries, &staging_path, config, &mut result, &mut progress)?;
+    if let (false, Some(mode)) = (config.preserve_permissions, config.file_mode) {
+        set_mode(&staging_path, mode)?;
+    }
+
+    finalize_backup(&staging_path, &final_backup_path, config)?;
+    result.rebase_entries(&staging_path, &final_backup_path);
+    write_manifest_if_enabled(&result, config)?;
+
+    result.duration = start_time.elapsed();
+    _operation_guard.complete();
+
+    Ok(result)
+}
+
+/// Copy an already scanned directory into a staged backup, reporting to `progress`
+///
+/// `total_size` is `None` for a single-pass backup, which skips the space check.
+fn backup_scanned_directory(
+    source: &Path,
+    config: &Config,
+    total_size: Option<u64>,
+    progress: Option<&mut BackupProgress>,
+) -> Result<BackupResult> {
+    let start_time = Instant::now();
+
+    // Validate source
+    validate_source(source, config)?;
+
+    // Generate backup name
+    let backup_path = generate_backup_name(source, config)?;
+    let final_backup_path = resolve_collision_with(source, &backup_path, config)?;
+    prepare_output_dir(&final_backup_path, None, config)?;
+
+    // In incremental mode, unchanged files are hardlinked from the newest previous backup
+    let previous_backup = if config.incremental && config.archive == ArchiveMode::None {
+        find_existing_backups(source, config)?
+            .into_iter()
+            .find(|path| path.is_dir())
+    } else {
+        None
+    };
+
+    let mut result = stage_directory_backup(
+        source,
+        &final_backup_path,
+        previous_backup.as_deref(),
+        Traversal::new(config)?,
+        config,
+        total_size,
+        progress,
+    )?;
+    result.duration = start_time.elapsed();
+
+    Ok(result)
+}
+
+/// Copy `source` into a staging directory and move it to `final_backup_path` once complete
+///
+/// Files unchanged since `previous` (if given) are hardlinked from it instead of copied.
+/// `needed_size` is what the space check requires on the destination, if known.
+fn stage_directory_backup(
+    source: &Path,
+    final_backup_path: &Path,
+    previous: Option<&Path>,
+    mut walk: Traversal,
+    config: &Config,
+    needed_size: Option<u64>,
+    progress: Option<&mut BackupProgress>,
+) -> Result<BackupResult> {
+    // Copy into a temporary staging directory so the final name only appears once complete
+    let staging_path = create_temp_backup_path(final_backup_path)?;
+
+    // Register operation for cleanup tracking
+    let _operation_guard = crate::signal::create_backup_guard(staging_path.clone());
+
+    // Fail up front if the backup can't fit
+    if let (true, Some(needed_size)) = (config.check_space, needed_size) {
+        check_available_space_for(needed_size, backup_parent(final_backup_path), config)?;
+    }
+
+    // Initialize result
+    let mut result = BackupResult::new(source.to_path_buf(), final_backup_path.to_path_buf());
+
+    if config.archive != ArchiveMode::None {
+        // Stream the tree into a single archive file
+        archive_directory(source, &staging_path, config, &mut result, &mut {
+            progress
+        })?;
+        if let (false, Some(mode)) = (config.preserve_permissions, config.file_mode) {
+            set_mode(&staging_path, mode)?;
+        }
+    } else {
+        // Create staging directory
+        create_backup_dir(&staging_path, config)?;
+
+        // Copy contents with progress tracking
+        copy_directory_contents_with_progress(
+            source,
+            &staging_path,
+            previous,
+            config,
+            &mut result,
+            &mut { progress },
+            &mut walk,
+        )?;
+        walk.directories
+            .push((source.to_path_buf(), staging_path.clone()));
+        apply_directory_metadata(&walk.directories, config)?;
+    }
+
+    // Move the complete backup into place in a single step
+    finalize_backup(&staging_path, final_backup_path, config)?;
+    result.rebase_entries(&staging_path, final_backup_path);
+    write_manifest_if_enabled(&result, config)?;
+
+    // Mark operation as completed (prevents cleanup)
+    _operation_guard.complete();
+
+    Ok(result)
+}
+
+/// Finish a directory backup whose partial copy was kept after an interrupt
+///
+/// `partial` is the `.qbak_temp_` staging directory left by the interrupted run (see
+/// `keep_partial_on_interrupt`). Files it already holds that are unchanged in `source`, by
+/// size and mtime or, with `verify`, by size and SHA-256, are hardlinked into a fresh
+/// staging directory as in an incremental backup; everything else is copied. The backup
+/// gets the name the interrupted run would have used, and the partial is then removed.
+pub fn resume_directory_backup(
+    partial: &Path,
+    source: &Path,
+    config: &Config,
+    force_progress: bool,
+    quiet: bool,
+) -> Result<BackupResult> {
+    let start_time = Instant::now();
+
+    validate_source(source, config)?;
+    if !source.is_dir() {
+        return Err(QbakError::validation("Source is not a directory"));
+    }
+    if config.archive != ArchiveMode::None {
+        return Err(QbakError::validation(
+            "Archive backups can't be resumed; only directory copies can",
+        ));
+    }
+
+    let final_name = partial
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix(".qbak_temp_"))
+        .and_then(|rest| rest.split_once('_'))
+        .map(|(_, name)| name)
+        .filter(|_| partial.is_dir())
+        .ok_or_else(|| {
+            QbakError::validation(format!(
+                "{} is not an incomplete directory backup (expected a .qbak_temp_ directory)",
+                partial.display()
+            ))
+        })?;
+    let backup_path = partial.with_file_name(final_name);
+    validate_backup_location(source, &backup_path)?;
+    let final_backup_path = resolve_collision_with(source, &backup_path, config)?;
+
+    let (file_count, total_size) = count_files_and_size(source, config)?;
+    let mut progress = if !quiet {
+        create_progress_bar(&config.progress, file_count, total_size, force_progress)
+            .or_else(|| create_deferred_progress_bar(&config.progress))
+    } else {
+        None
+    };
+    if let Some(ref mut prog) = progress {
+        prog.start_scanning();
+        prog.finish_scanning(file_count, total_size);
+    }
+
+    // Files linked from the partial take no new space
+    let needed_size = total_size.saturating_sub(calculate_size(partial)?);
+    let walk = Traversal {
+        compare_checksums: config.verify,
+        ..Traversal::new(config)?
+    };
+    let staged = stage_directory_backup(
+        source,
+        &final_backup_path,
+        Some(partial),
+        walk,
+        config,
+        Some(needed_size),
+        progress.as_mut(),
+    );
+
+    if let Some(ref mut prog) = progress {
+        prog.finish();
+    }
+    let mut result = staged?;
+
+    fs::remove_dir_all(partial)?;
+    result.duration = start_time.elapsed();
+
+    Ok(result)
+}
+
+/// Totals gathered by a scan of a backup source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanTotals {
+    /// Files that will be copied
+    pub files: usize,
+    /// Combined size of those files in bytes, counting hardlinked files once
+    pub size: u64,
+    /// Entries left out by hidden-file settings, include/exclude patterns, file filters
+    /// or the broken-symlink policy
+    pub skipped: usize,
+}
+
+/// Scan a backup source, optionally showing scanning progress
+pub fn scan_directory(source: &Path, config: &Config, show_progress: bool) -> Result<ScanTotals> {
+    // Without progress, a spinner still appears if the scan itself is slow
+    let mut progress = if show_progress {
+        create_progress_bar(&config.progress, 0, 0, true)
+    } else if config.progress.format == ProgressFormat::Bar {
+        create_deferred_progress_bar(&config.progress)
+    } else {
+        None
+    };
+    if let Some(ref mut prog) = progress {
+        prog.start_scanning();
+    }
+
+    let result = count_files_and_size_recursive(source, config, &mut progress.as_mut());
+
+    if let Some(ref mut prog) = progress {
+        prog.finish();
+    }
+
+    result
+}
+
+/// Count files and calculate total size, with optional progress
+pub fn count_files_and_size_with_progress(source: &Path, config: &Config) -> Result<(usize, u64)> {
+    scan_directory(source, config, true).map(|totals| (totals.files, totals.size))
+}
+
+/// Count files and calculate total size, without progress
+pub fn count_files_and_size(source: &Path, config: &Config) -> Result<(usize, u64)> {
+    scan_directory(source, config, false).map(|totals| (totals.files, totals.size))
+}
+
+/// Recursive function to count files and calculate total size
+///
+/// This is the only file counter, shared by verbose output, progress and space checks.
+/// Followed symlinks are counted the same way the copy path handles them, so the totals
+/// match what will actually be written. A single file counts as one.
+fn count_files_and_size_recursive(
+    dir: &Path,
+    config: &Config,
+    progress: &mut Option<&mut BackupProgress>,
+) -> Result<ScanTotals> {
+    // A preserved symlink is a single entry with no data
+    if is_preserved_symlink(dir, config) {
+        return Ok(ScanTotals {
+            files: 1,
+            size: 0,
+            skipped: 0,
+        });
+    }
+    if !dir.is_dir() {
+        return Ok(ScanTotals {
+            files: 1,
+            size: fs::metadata(dir)?.len(),
+            skipped: 0,
+        });
+    }
+
+    let mut totals = ScanTotals::default();
+    count_directory_recursive(
+        dir,
+        config,
+        progress,
+        &mut Traversal::new(config)?,
+        &mut totals,
+    )?;
+    Ok(totals)
+}
+
+/// Recursive helper for `count_files_and_size_recursive` with cycle detection
+fn count_directory_recursive(
+    dir: &Path,
+    config: &Config,
+    progress: &mut Option<&mut BackupProgress>,
+    walk: &mut Traversal,
+    totals: &mut ScanTotals,
+) -> Result<()> {
+    let canonical = walk.enter(dir)?;
+
+    for entry in fs::read_dir(dir)? {
+        // Check for interrupt signal during scanning
+        if crate::signal::is_interrupted() {
+            return Err(QbakError::Interrupted);
+        }
+
+        let entry = entry?;
+        let path = entry.path();
+
+        // Skip hidden files if not configured to include them
+        if !config.include_hidden && is_hidden(&path) {
+            totals.skipped += 1;
+            continue;
+        }
+
+        if !walk.filters.matches(&path, path.is_dir()) {
+            totals.skipped += 1;
+            continue;
+        }
+
+        let mut metadata = entry.metadata()?;
+        let mut scan_path = path.clone();
+
+        if metadata.file_type().is_symlink() {
+            if !config.follow_symlinks {
+                // Preserved as a link; nothing is copied
+                continue;
+            }
+            if !path.exists() {
+                if config.symlink_broken_policy == BrokenSymlinkPolicy::Error {
+                    // Fail during the scan, before anything has been copied
+                    return Err(broken_symlink_error(&path, &fs::read_link(&path)?));
+                }
+                totals.skipped += 1;
+                continue;
+            }
+            scan_path = resolve_symlink_target(&path)?;
+            metadata = fs::metadata(&scan_path)?;
+        }
+
+        if metadata.is_file() {
+            if !passes_file_filters(&metadata, config) {
+                totals.skipped += 1;
+                continue;
+            }
+            totals.files += 1;
+            // Hardlinks to the same file take up its space only once
+            if walk.inodes.first_link(&metadata) {
+                totals.size += metadata.len();
+            }
+
+            // Update scanning progress occasionally
+            if let Some(ref mut p) = progress {
+                if p.scan_update_due(totals.files) {
+                    p.update_scan_progress(totals.files, &path);
+                }
+            }
+        } else if metadata.is_dir() && walk.can_descend(config) {
+            count_directory_recursive(&scan_path, config, progress, walk, totals)?;
+        }
+    }
+
+    walk.leave(&canonical);
+    Ok(())
+}
+
+/// State carried through a recursive directory walk
+#[derive(Default)]
+struct Traversal {
+    /// Canonical paths of the directories currently being walked, for cycle detection
+    visited: HashSet<PathBuf>,
+    /// Number of directories currently being walked
+    depth: usize,
+    /// Backup path of the first copy of each file content (by size and SHA-256), for `dedup`
+    written: HashMap<(u64, [u8; 32]), PathBuf>,
+    /// Copied directories as (source, backup) pairs, children before their parents
+    directories: Vec<(PathBuf, PathBuf)>,
+    /// Hardlinked files already counted by a scan
+    inodes: InodeSet,
+    /// Backup path of the first copy of each hardlinked source file, for `preserve_hardlinks`
+    hardlinks: HashMap<(u64, u64), PathBuf>,
+    /// Match files of the previous backup by checksum instead of mtime (resuming with `verify`)
+    compare_checksums: bool,
+    /// Compiled include/exclude patterns
+    filters: Filters,
+}
+
+impl Traversal {
+    /// Start a walk, compiling the configured include/exclude patterns
+    fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            filters: Filters::new(config)?,
+            ..Self::default()
+        })
+    }
+
+    /// Mark a directory as being walked, failing if it is already an ancestor
+    ///
+    /// Returns the canonical path, which must be passed to `leave` once done.
+    fn enter(&mut self, dir: &Path) -> Result<PathBuf> {
+        // Resolve symlinks to detect cycles
+        let canonical = dir.canonicalize()?;
+        if !self.visited.insert(canonical.clone()) {
+            return Err(QbakError::SymlinkLoop {
+                path: dir.to_path_buf(),
+            });
+        }
+        self.depth += 1;
+        Ok(canonical)
+    }
+
+    fn leave(&mut self, canonical: &Path) {
+        self.visited.remove(canonical);
+        self.depth -= 1;
+    }
+
+    /// Whether subdirectories of the directory being walked are within `max_depth`
+    ///
+    /// Entries directly inside the source are at depth 0.
+    fn can_descend(&self, config: &Config) -> bool {
+        config.max_depth.map_or(true, |max| self.depth <= max)
+    }
+}
+
+/// Whether a file is larger than `max_file_size`
+fn is_oversized(metadata: &fs::Metadata, config: &Config) -> bool {
+    config
+        .max_file_size
+        .is_some_and(|max_size| metadata.len() > max_size)
+}
+
+/// Whether a file was modified after the `newer_than` cutoff (always true without one)
+fn is_newer_than_cutoff(metadata: &fs::Metadata, config: &Config) -> bool {
+    match (config.newer_than, metadata.modified()) {
+        (Some(cutoff), Ok(modified)) => modified > cutoff,
+        _ => true,
+    }
+}
+
+/// Whether a file passes the size and modification time filters
+fn passes_file_filters(metadata: &fs::Metadata, config: &Config) -> bool {
+    !is_oversized(metadata, config) && is_newer_than_cutoff(metadata, config)
+}
+
+/// Check the file filters for a file about to be copied, logging oversized files in verbose mode
+fn skip_filtered_file(
+    path: &Path,
+    backup: &Path,
+    metadata: &fs::Metadata,
+    config: &Config,
+    result: &mut BackupResult,
+) -> bool {
+    let (reason, description) = if is_oversized(metadata, config) {
+        if config.verbose {
+            eprintln!(
+                "Skipping {} ({}, larger than max_file_size)",
+                path.display(),
+                format_size_for(metadata.len(), config)
+            );
+        }
+        (SkipReason::Oversize, "larger than max_file_size")
+    } else if !is_newer_than_cutoff(metadata, config) {
+        (SkipReason::Unchanged, "not modified since newer_than")
+    } else {
+        return false;
+    };
+
+    result.skip(path, reason);
+    let outcome = EntryOutcome::Skipped(description.to_string());
+    result.record(config, path, backup, metadata.len(), outcome);
+    true
+}
+
+/// Handle a directory beyond `max_depth` according to `max_depth_mode`
+fn create_depth_limited_dir(backup: &Path, config: &Config) -> Result<()> {
+    if config.max_depth_mode == DepthLimitMode::Create {
+        fs::create_dir_all(backup)?;
+        if let (false, Some(mode)) = (config.preserve_permissions, config.dir_mode) {
+            set_mode(backup, mode)?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the target of a symlink relative to the directory containing it
+fn resolve_symlink_target(link: &Path) -> Result<PathBuf> {
+    let target = fs::read_link(link)?;
+    Ok(if target.is_absolute() {
+        target
+    } else {
+        link.parent().unwrap_or(Path::new(".")).join(target)
+    })
+}
+
+/// Copy directory contents with progress tracking
+fn copy_directory_contents_with_progress(
+    source_dir: &Path,
+    backup_dir: &Path,
+    previous_dir: Option<&Path>,
+    config: &Config,
+    result: &mut BackupResult,
+    progress: &mut Option<&mut BackupProgress>,
+    walk: &mut Traversal,
+) -> Result<()> {
+    let canonical = walk.enter(source_dir)?;
+
+    for entry in fs::read_dir(source_dir)? {
+        // Check for interrupt signal
+        if crate::signal::is_interrupted() {
+            return Err(QbakError::Interrupted);
+        }
+
+        let entry = entry?;
+        let source_path = entry.path();
+        let filename = source_path.file_name().unwrap();
+        let backup_path = backup_dir.join(filename);
+        let previous_path = previous_dir.map(|dir| dir.join(filename));
+
+        // Skip hidden files if not configured to include them
+        if !config.include_hidden && is_hidden(&source_path) {
+            result.skip(&source_path, SkipReason::Hidden);
+            continue;
+        }
+
+        // Skip entries filtered out by include/exclude patterns
+        if !walk.filters.matches(&source_path, source_path.is_dir()) {
+            result.skip(&source_path, SkipReason::Excluded);
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+
+        if metadata.is_file() {
+            if skip_filtered_file(&source_path, &backup_path, &metadata, config, result) {
+                continue;
+            }
+
+            let linked = match previous_path {
+                Some(ref previous) => link_unchanged_file(
+                    &source_path,
+                    previous,
+                    &backup_path,
+                    config,
+                    result,
+                    walk.compare_checksums,
+                )?,
+                None => false,
+            };
+            if !linked {
+                copy_or_dedup_file(&source_path, &backup_path, config, result, walk)?;
+            }
+
+            // Update progress
+            if let Some(ref mut prog) = progress {
+                prog.update_backup_progress(
+                    result.files_processed,
+                    result.total_size,
+                    &source_path,
+                );
+            }
+        } else if metadata.is_dir() {
+            if !walk.can_descend(config) {
+                create_depth_limited_dir(&backup_path, config)?;
+                continue;
+            }
+            create_backup_dir(&backup_path, config)?;
+            copy_directory_contents_with_progress(
+                &source_path,
+                &backup_path,
+                previous_path.as_deref(),
+                config,
+                result,
+                progress,
+                walk,
+            )?;
+            if !prune_empty_directory(&backup_path, config) {
+                walk.directories.push((source_path, backup_path));
+            }
+        } else if metadata.file_type().is_symlink() {
+            handle_symlink_with_progress(
+                &source_path,
+                &backup_path,
+                previous_path.as_deref(),
+                config,
+                result,
+                progress,
+                walk,
+            )?;
+        } else {
+            warn_special_file(&source_path, &backup_path, &metadata, config, result);
+        }
+    }
+
+    walk.leave(&canonical);
+    Ok(())
+}
+
+/// Handle symlink with progress tracking
+fn handle_symlink_with_progress(
+    source: &Path,
+    backup: &Path,
+    previous: Option<&Path>,
+    config: &Config,
+    result: &mut BackupResult,
+    progress: &mut Option<&mut BackupProgress>,
+    walk: &mut Traversal,
+) -> Result<()> {
+    if config.follow_symlinks {
+        // Follow the symlink and copy the target
+        let target = fs::read_link(source)?;
+        let resolved_target = if target.is_absolute() {
+            target
+        } else {
+            source.parent().unwrap_or(Path::new(".")).join(target)
+        };
+
+        if resolved_target.exists() {
+            let metadata = fs::metadata(&resolved_target)?;
+            if metadata.is_file() {
+                if skip_filtered_file(source, backup, &metadata, config, result) {
+                    return Ok(());
+                }
+                copy_or_dedup_file(&resolved_target, backup, config, result, walk)?;
+
+                // Update progress
+                if let Some(ref mut prog) = progress {
+                    prog.update_backup_progress(result.files_processed, result.total_size, source);
+                }
+            } else if metadata.is_dir() {
+                if !walk.can_descend(config) {
+                    return create_depth_limited_dir(backup, config);
+                }
+                create_backup_dir(backup, config)?;
+                copy_directory_contents_with_progress(
+                    &resolved_target,
+                    backup,
+                    previous,
+                    config,
+                    result,
+                    progress,
+                    walk,
+                )?;
+                walk.directories
+                    .push((resolved_target.clone(), backup.to_path_buf()));
+            }
+        } else {
+            handle_broken_symlink(source, backup, config, result)?;
+        }
+    } else {
+        // Preserve the symlink as-is
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::symlink;
+            let target = fs::read_link(source)?;
+            symlink(target, backup)?;
+            result.record(config, source, backup, 0, EntryOutcome::Symlink);
+        }
+
+        #[cfg(not(unix))]
+        {
+            // On non-Unix systems, copy the target file instead
+            let target = fs::read_link(source)?;
+            let resolved_target = if target.is_absolute() {
+                target
+            } else {
+                source.parent().unwrap_or(Path::new(".")).join(target)
+            };
+
+            if resolved_target.exists() && resolved_target.is_file() {
+                copy_or_dedup_file(&resolved_target, backup, config, result, walk)?;
+
+                // Update progress
+                if let Some(ref mut prog) = progress {
+                    prog.update_backup_progress(result.files_processed, result.total_size, source);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stream a directory into a tar or zip archive at `archive_path`
+///
+/// Tar archives are gzipped if compression is enabled. The partial archive is removed if
+/// writing fails or is interrupted.
+fn archive_directory(
+    source: &Path,
+    archive_path: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+    progress: &mut Option<&mut BackupProgress>,
+) -> Result<()> {
+    let root = archive_root_name(source)?;
+    archive_sources(
+        &[(source.to_path_buf(), root)],
+        archive_path,
+        config,
+        result,
+        progress,
+    )
+}
+
+/// Stream several sources into one archive, each under its own top-level name
+///
+/// Takes `(source, root)` pairs; see `archive_directory` for compression and cleanup.
+fn archive_sources(
+    sources: &[(PathBuf, PathBuf)],
+    archive_path: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+    progress: &mut Option<&mut BackupProgress>,
+) -> Result<()> {
+    let file = ThrottledWriter {
+        inner: fs::File::create(archive_path)?,
+        throttle: config.rate_limit.map(Throttle::new),
+    };
+
+    let written = if config.archive == ArchiveMode::Zip {
+        let mut archive = ZipArchiveWriter::new(file, config.zip_compression_level);
+        write_archive(&mut archive, sources, config, result, progress)
+            .and_then(|()| archive.zip.finish().map_err(zip_error)?.finish(config))
+    } else if config.compression == CompressionMode::Gzip {
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar_builder(encoder);
+        write_archive(&mut builder, sources, config, result, progress).and_then(|()| {
+            let encoder = builder.into_inner().map_err(archive_error)?;
+            encoder.finish()?.finish(config)
+        })
+    } else {
+        let mut builder = tar_builder(file);
+        write_archive(&mut builder, sources, config, result, progress)
+            .and_then(|()| builder.into_inner().map_err(archive_error)?.finish(config))
+    };
+
+    if let Err(error) = written {
+        let keep = matches!(error, QbakError::Interrupted) && crate::signal::keeps_partial();
+        if !keep {
+            let _ = fs::remove_file(archive_path);
+        }
+        return Err(error);
+    }
+
+    // Plain tar archives are stored uncompressed
+    if config.archive == ArchiveMode::Zip || config.compression == CompressionMode::Gzip {
+        result.stored_size = Some(fs::metadata(archive_path)?.len());
+    }
+    Ok(())
+}
+
+/// Name of the top-level directory inside the archive
+fn archive_root_name(source: &Path) -> Result<PathBuf> {
+    match source.file_name() {
+        Some(name) => Ok(PathBuf::from(name)),
+        None => source
+            .canonicalize()?
+            .file_name()
+            .map(PathBuf::from)
+            .ok_or_else(|| QbakError::validation("Cannot determine directory name to archive")),
+    }
+}
+
+/// Write all entries for each source under its top-level name
+///
+/// Directories are archived recursively; files and preserved symlinks given as sources
+/// become single top-level entries.
+fn write_archive<A: ArchiveWriter>(
+    archive: &mut A,
+    sources: &[(PathBuf, PathBuf)],
+    config: &Config,
+    result: &mut BackupResult,
+    progress: &mut Option<&mut BackupProgress>,
+) -> Result<()> {
+    for (source, root) in sources {
+        if is_preserved_symlink(source, config) {
+            let metadata = fs::symlink_metadata(source)?;
+            archive.append_link(&metadata, root, &fs::read_link(source)?)?;
+            result.files_processed += 1;
+            result.record(config, source, root, 0, EntryOutcome::Symlink);
+        } else if source.is_dir() {
+            append_tree_to_archive(
+                archive,
+                source,
+                root,
+                config,
+                result,
+                progress,
+                &mut Traversal::new(config)?,
+            )?;
+            archive.append_dir(source, root)?;
+        } else {
+            let metadata = fs::metadata(source)?;
+            archive.append_file(source, &metadata, root)?;
+            result.files_processed += 1;
+            result.files_copied += 1;
+            result.total_size += metadata.len();
+            result.record(config, source, root, metadata.len(), EntryOutcome::Copied);
+        }
+
+        if let Some(ref mut prog) = progress {
+            prog.update_backup_progress(result.files_processed, result.total_size, source);
+        }
+    }
+    Ok(())
+}
+
+/// Append the contents of a directory to the archive, applying the same rules as a copy
+///
+/// Directory entries are written after their contents so read-only directories still
+/// extract cleanly. Returns the number of entries appended.
+fn append_tree_to_archive<A: ArchiveWriter>(
+    archive: &mut A,
+    source_dir: &Path,
+    archive_dir: &Path,
+    config: &Config,
+    result: &mut BackupResult,
+    progress: &mut Option<&mut BackupProgress>,
+    walk: &mut Traversal,
+) -> Result<usize> {
+    let canonical = walk.enter(source_dir)?;
+    let mut appended = 0;
+
+    for entry in fs::read_dir(source_dir)? {
+        // Check for interrupt signal
+        if crate::signal::is_interrupted() {
+            return Err(QbakError::Interrupted);
+        }
+
+        let entry = entry?;
+        let source_path = entry.path();
+        let archive_path = archive_dir.join(entry.file_name());
+
+        // Skip hidden files if not configured to include them
+        if !config.include_hidden && is_hidden(&source_path) {
+            result.skip(&source_path, SkipReason::Hidden);
+            continue;
+        }
+
+        // Skip entries filtered out by include/exclude patterns
+        if !walk.filters.matches(&source_path, source_path.is_dir()) {
+            result.skip(&source_path, SkipReason::Excluded);
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+
+        // Followed symlinks are archived as their target under the link's name
+        let (target_path, metadata) = if metadata.file_type().is_symlink() {
+            let target = resolve_symlink_target(&source_path)?;
+            if !config.follow_symlinks {
+                let link_target = fs::read_link(&source_path)?;
+                archive.append_link(&metadata, &archive_path, &link_target)?;
+                result.record(
+                    config,
+                    &source_path,
+                    &archive_path,
+                    0,
+                    EntryOutcome::Symlink,
+                );
+                appended += 1;
+                continue;
+            }
+            if !target.exists() {
+                match broken_symlink_to_preserve(&source_path, config)? {
+                    Some(link_target) => {
+                        archive.append_link(&metadata, &archive_path, &link_target)?;
+                        result.record(
+                            config,
+                            &source_path,
+                            &archive_path,
+                            0,
+                            EntryOutcome::Symlink,
+                        );
+                        appended += 1;
+                    }
+                    None => {
+                        result.skip(&source_path, SkipReason::BrokenSymlink);
+                        let outcome = EntryOutcome::Skipped("broken symlink".to_string());
+                        result.record(config, &source_path, &archive_path, 0, outcome);
+                    }
+                }
+                continue;
+            }
+            let target_metadata = fs::metadata(&target)?;
+            (target, target_metadata)
+        } else {
+            (source_path.clone(), metadata)
+        };
+
+        if metadata.is_file() {
+            if skip_filtered_file(&source_path, &archive_path, &metadata, config, result) {
+                continue;
+            }
+
+            archive.append_file(&target_path, &metadata, &archive_path)?;
+            appended += 1;
+
+            result.files_processed += 1;
+            result.files_copied += 1;
+            result.total_size += metadata.len();
+            let size = metadata.len();
+            result.record(
+                config,
+                &source_path,
+                &archive_path,
+                size,
+                EntryOutcome::Copied,
+            );
+
+            // Update progress
+            if let Some(ref mut prog) = progress {
+                prog.update_backup_progress(
+                    result.files_processed,
+                    result.total_size,
+                    &source_path,
+                );
+            }
+        } else if metadata.is_dir() {
+            if !walk.can_descend(config) {
+                if config.max_depth_mode == DepthLimitMode::Create {
+                    archive.append_dir(&target_path, &archive_path)?;
+                    appended += 1;
+                }
+                continue;
+            }
+
+            let children = append_tree_to_archive(
+                archive,
+                &target_path,
+                &archive_path,
+                config,
+                result,
+                progress,
+                walk,
+            )?;
+
+            // Drop directories left empty by the filters
+            if children == 0 && prunes_empty_dirs(config) {
+                continue;
+            }
+            archive.append_dir(&target_path, &archive_path)?;
+            appended += children + 1;
+        } else {
+            warn_special_file(&source_path, &archive_path, &metadata, config, result);
+        }
+    }
+
+    walk.leave(&canonical);
+    Ok(appended)
+}
+
+/// Entry writer shared by the tar and zip archive formats
+///
+/// Entries keep their permissions and mtimes as far as the format allows.
+trait ArchiveWriter {
+    /// Append a directory entry for `source`
+    fn append_dir(&mut self, source: &Path, archive_path: &Path) -> Result<()>;
+
+    /// Append a regular file, streaming its contents from `source`
+    fn append_file(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        archive_path: &Path,
+    ) -> Result<()>;
+
+    /// Append a symlink entry pointing at `target`
+    fn append_link(
+        &mut self,
+        metadata: &fs::Metadata,
+        archive_path: &Path,
+        target: &Path,
+    ) -> Result<()>;
+}
+
+fn tar_builder<W: std::io::Write>(writer: W) -> tar::Builder<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    builder
+}
+
+fn tar_header(metadata: &fs::Metadata) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata_in_mode(metadata, tar::HeaderMode::Complete);
+    header
+}
+
+impl<W: std::io::Write> ArchiveWriter for tar::Builder<W> {
+    fn append_dir(&mut self, source: &Path, archive_path: &Path) -> Result<()> {
+        let mut header = tar_header(&fs::metadata(source)?);
+        self.append_data(&mut header, archive_path, std::io::empty())
+            .map_err(archive_error)
+    }
+
+    fn append_file(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        archive_path: &Path,
+    ) -> Result<()> {
+        let mut header = tar_header(metadata);
+        let reader = InterruptCheckedReader(fs::File::open(source)?);
+        self.append_data(&mut header, archive_path, reader)
+            .map_err(archive_error)
+    }
+
+    fn append_link(
+        &mut self,
+        metadata: &fs::Metadata,
+        archive_path: &Path,
+        target: &Path,
+    ) -> Result<()> {
+        let mut header = tar_header(metadata);
+        tar::Builder::append_link(self, &mut header, archive_path, target).map_err(archive_error)
+    }
+}
+
+/// Zip archive writer using a fixed compression level (0 stores files uncompressed)
+struct ZipArchiveWriter<W: std::io::Write + std::io::Seek> {
+    zip: zip::ZipWriter<W>,
+    compression_level: u32,
+}
+
+impl<W: std::io::Write + std::io::Seek> ZipArchiveWriter<W> {
+    fn new(writer: W, compression_level: u32) -> Self {
+        Self {
+            zip: zip::ZipWriter::new(writer),
+            compression_level,
+        }
+    }
+
+    fn options(&self, metadata: &fs::Metadata) -> zip::write::FileOptions {
+        let mut options = zip::write::FileOptions::default();
+        options = if self.compression_level == 0 {
+            options.compression_method(zip::CompressionMethod::Stored)
+        } else {
+            options
+                .compression_method(zip::CompressionMethod::Deflated)
+                .compression_level(Some(self.compression_level as i32))
+        };
+
+        if let Some(modified) = zip_timestamp(metadata) {
+            options = options.last_modified_time(modified);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            options = options.unix_permissions(metadata.permissions().mode() & 0o7777);
+        }
+
+        options
+    }
+}
+
+impl<W: std::io::Write + std::io::Seek> ArchiveWriter for ZipArchiveWriter<W> {
+    fn append_dir(&mut self, source: &Path, archive_path: &Path) -> Result<()> {
+        let options = self.options(&fs::metadata(source)?);
+        self.zip
+            .add_directory(zip_entry_name(archive_path), options)
+            .map_err(zip_error)
+    }
+
+    fn append_file(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        archive_path: &Path,
+    ) -> Result<()> {
+        // Entries of 4 GiB or more need zip64 headers
+        let options = self
+            .options(metadata)
+            .large_file(metadata.len() >= u64::from(u32::MAX));
+        self.zip
+            .start_file(zip_entry_name(archive_path), options)
+            .map_err(zip_error)?;
+
+        let mut reader = InterruptCheckedReader(fs::File::open(source)?);
+        std::io::copy(&mut reader, &mut self.zip).map_err(archive_error)?;
+        Ok(())
+    }
+
+    fn append_link(
+        &mut self,
+        metadata: &fs::Metadata,
+        archive_path: &Path,
+        target: &Path,
+    ) -> Result<()> {
+        let options = self.options(metadata);
+        self.zip
+            .add_symlink(
+                zip_entry_name(archive_path),
+                target.to_string_lossy(),
+                options,
+            )
+            .map_err(zip_error)
+    }
+}
+
+/// Zip entry names always use `/` separators
+fn zip_entry_name(archive_path: &Path) -> String {
+    archive_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Convert a file's mtime to the local-time DOS timestamp zip stores
+///
+/// Returns None for times outside the 1980-2107 range zip can represent.
+fn zip_timestamp(metadata: &fs::Metadata) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+
+    let modified: chrono::DateTime<chrono::Local> = metadata.modified().ok()?.into();
+    zip::DateTime::from_date_and_time(
+        u16::try_from(modified.year()).ok()?,
+        modified.month() as u8,
+        modified.day() as u8,
+        modified.hour() as u8,
+        modified.minute() as u8,
+        modified.second() as u8,
+    )
+    .ok()
+}
+
+fn zip_error(error: zip::result::ZipError) -> QbakError {
+    archive_error(error.into())
+}
+
+/// Report a failed archive write as an interruption if that's what stopped it
+fn archive_error(error: std::io::Error) -> QbakError {
+    if crate::signal::is_interrupted() {
+        QbakError::Interrupted
+    } else {
+        error.into()
+    }
+}
+
+/// Writer that applies the configured rate limit to the archive as it is written
+struct ThrottledWriter<W> {
+    inner: W,
+    throttle: Option<Throttle>,
+}
+
+impl ThrottledWriter<fs::File> {
+    /// Flush the completed archive, syncing it to disk if `fsync` is enabled
+    fn finish(mut self, config: &Config) -> Result<()> {
+        use std::io::Write;
+
+        self.flush()?;
+        if config.fsync {
+            self.inner.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for ThrottledWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(ref mut throttle) = self.throttle {
+            throttle.consume(written as u64);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: std::io::Seek> std::io::Seek for ThrottledWriter<W> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Reader that fails once an interrupt has been requested, so archiving stops mid-file
+struct InterruptCheckedReader<R>(R);
+
+impl<R: std::io::Read> std::io::Read for InterruptCheckedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if crate::signal::is_interrupted() {
+            // Not ErrorKind::Interrupted, which io::copy would retry
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "backup interrupted",
+            ));
+        }
+        self.0.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_config;
+    use crate::naming::resolve_collision;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_backup_file() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("test.txt");
+
+        // Create source file
+        let mut file = File::create(&source_path).unwrap();
+        writeln!(file, "Hello, World!").unwrap();
+
+        let config = default_config();
+        let result = backup_file(&source_path, &config).unwrap();
+
+        // Check that backup was created
+        assert!(result.backup_path.exists());
+        assert_eq!(result.files_processed, 1);
+        assert_eq!(result.total_size, 14); // "Hello, World!\n"
+
+        // Check backup content
+        let backup_content = fs::read_to_string(&result.backup_path).unwrap();
+        assert_eq!(backup_content, "Hello, World!\n");
+
+        // Verify backup summary
+        let summary = result.summary();
+        assert!(summary.contains("Created backup:"));
+        assert!(summary.contains("14 B"));
+    }
+
+    #[test]
+    fn test_backup_file_nonexistent() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("nonexistent.txt");
+
+        let config = default_config();
+        let result = backup_file(&source_path, &config);
+
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            QbakError::SourceNotFound { path } => assert_eq!(path, source_path),
+            _ => panic!("Expected SourceNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_backup_directory_preserves_directory_mtimes() {
+        use filetime::FileTime;
+
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(source_dir.join("sub/deep")).unwrap();
+        fs::write(source_dir.join("top.txt"), "top").unwrap();
+        fs::write(source_dir.join("sub/middle.txt"), "middle").unwrap();
+        fs::write(source_dir.join("sub/deep/bottom.txt"), "bottom").unwrap();
+
+        let old_time = FileTime::from_unix_time(1_500_000_000, 0);
+        for path in ["sub/deep", "sub", ""] {
+            filetime::set_file_mtime(source_dir.join(path), old_time).unwrap();
+        }
+
+        let config = default_config();
+        let plain = backup_directory(&source_dir, &config, false).unwrap();
+        let with_progress =
+            backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+
+        // Writing children must not leave any backed up directory with a fresh mtime
+        for backup in [plain.backup_path, with_progress.backup_path] {
+            for path in ["sub/deep", "sub", ""] {
+                let metadata = fs::metadata(backup.join(path)).unwrap();
+                assert_eq!(
+                    FileTime::from_last_modification_time(&metadata),
+                    old_time,
+                    "{path:?} in {}",
+                    backup.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_backup_directory() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        // Create some files
+        File::create(source_dir.join("file1.txt")).unwrap();
+        File::create(source_dir.join("file2.txt")).unwrap();
+
+        let subdir = source_dir.join("subdir");
+        fs::create_dir_all(&subdir).unwrap();
+        File::create(subdir.join("file3.txt")).unwrap();
+
+        let config = default_config();
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+
+        // Check that backup directory was created
+        assert!(result.backup_path.exists());
+        assert!(result.backup_path.is_dir());
+        assert_eq!(result.files_processed, 3);
+
+        // Check that files were copied
+        assert!(result.backup_path.join("file1.txt").exists());
+        assert!(result.backup_path.join("file2.txt").exists());
+        assert!(result.backup_path.join("subdir").join("file3.txt").exists());
+
+        // Verify backup summary for multiple files
+        let summary = result.summary();
+        assert!(summary.contains("Created backup:"));
+        assert!(summary.contains("3 files"));
+    }
+
+    #[test]
+    fn test_backup_directory_on_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("file.txt");
+        File::create(&file_path).unwrap();
+
+        let config = default_config();
+        let result = backup_directory(&file_path, &config, false);
+
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            QbakError::Validation { message } => assert!(message.contains("not a directory")),
+            _ => panic!("Expected Validation error"),
+        }
+    }
+
+    #[test]
+    fn test_backup_collision_resolution() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("test.txt");
+        File::create(&source_path).unwrap();
+
+        let config = default_config();
+
+        // First backup
+        let result1 = backup_file(&source_path, &config).unwrap();
+        assert!(result1.backup_path.exists());
+
+        // Create a file with the same timestamp pattern to force collision
+        let backup_name = result1.backup_path.file_name().unwrap().to_str().unwrap();
+        let collision_path = dir.path().join(backup_name);
+        File::create(&collision_path).unwrap();
+
+        // This should work because resolve_collision will handle it
+        // Note: In real usage, this is less likely due to timestamp precision
+    }
+
+    #[test]
+    fn test_hidden_file_handling() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        // Create visible and hidden files
+        File::create(source_dir.join("visible.txt")).unwrap();
+        File::create(source_dir.join(".hidden.txt")).unwrap();
+
+        // Test with include_hidden = true
+        let mut config = default_config();
+        config.include_hidden = true;
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert!(result.backup_path.join(".hidden.txt").exists());
+
+        // Clean up for next test
+        fs::remove_dir_all(&result.backup_path).unwrap();
+
+        // Test with include_hidden = false
+        config.include_hidden = false;
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(!result.backup_path.join(".hidden.txt").exists());
+        assert!(result.backup_path.join("visible.txt").exists());
+    }
+
+    #[test]
+    fn test_backup_empty_directory() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("empty_source");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        let config = default_config();
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+
+        assert!(result.backup_path.exists());
+        assert!(result.backup_path.is_dir());
+        assert_eq!(result.files_processed, 0);
+        assert_eq!(result.total_size, 0);
+    }
+
+    #[test]
+    fn test_backup_file_with_permissions_disabled() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("test.txt");
+
+        let mut file = File::create(&source_path).unwrap();
+        writeln!(file, "Content").unwrap();
+
+        let mut config = default_config();
+        config.preserve_permissions = false;
+
+        let result = backup_file(&source_path, &config).unwrap();
+        assert!(result.backup_path.exists());
+
+        // Should still create backup successfully
+        let backup_content = fs::read_to_string(&result.backup_path).unwrap();
+        assert_eq!(backup_content, "Content\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_backup_modes() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let mode_of = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("project");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("sub").join("data.txt"), "data").unwrap();
+        fs::set_permissions(source.join("sub"), fs::Permissions::from_mode(0o711)).unwrap();
+        let linked = dir.path().join("linked");
+        fs::create_dir(&linked).unwrap();
+        fs::set_permissions(&linked, fs::Permissions::from_mode(0o751)).unwrap();
+        symlink(&linked, source.join("link")).unwrap();
+
+        // Without preserved permissions the configured modes apply
+        let config = Config {
+            preserve_permissions: false,
+            dir_mode: Some(0o750),
+            file_mode: Some(0o604),
+            ..default_config()
+        };
+        let result = backup_directory_with_progress(&source, &config, false, true).unwrap();
+        assert_eq!(mode_of(&result.backup_path), 0o750);
+        assert_eq!(mode_of(&result.backup_path.join("sub")), 0o750);
+        assert_eq!(mode_of(&result.backup_path.join("link")), 0o750);
+        assert_eq!(
+            mode_of(&result.backup_path.join("sub").join("data.txt")),
+            0o604
+        );
+        let file = backup_file(&source.join("sub").join("data.txt"), &config).unwrap();
+        assert_eq!(mode_of(&file.backup_path), 0o604);
+
+        // Preserved permissions win, including for directories reached through a symlink
+        let config = Config {
+            dir_mode: Some(0o750),
+            ..default_config()
+        };
+        let result = backup_directory_with_progress(&source, &config, false, true).unwrap();
+        assert_eq!(mode_of(&result.backup_path.join("sub")), 0o711);
+        assert_eq!(mode_of(&result.backup_path.join("link")), 0o751);
+
+        // Directories start out private until their final mode is applied
+        let staging = dir.path().join("staging");
+        create_backup_dir(&staging.join("nested"), &config).unwrap();
+        assert_eq!(mode_of(&staging), 0o700);
+        assert_eq!(mode_of(&staging.join("nested")), 0o700);
+    }
+
+    #[test]
+    fn test_backup_deeply_nested_directory() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+
+        // Create deeply nested structure
+        let deep_path = source_dir.join("a").join("b").join("c").join("d").join("e");
+        fs::create_dir_all(&deep_path).unwrap();
+
+        // Add files at different levels
+        fs::write(source_dir.join("root.txt"), "root").unwrap();
+        fs::write(source_dir.join("a").join("a.txt"), "a").unwrap();
+        fs::write(source_dir.join("a").join("b").join("b.txt"), "b").unwrap();
+        fs::write(deep_path.join("deep.txt"), "deep").unwrap();
+
+        let config = default_config();
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+
+        assert_eq!(result.files_processed, 4);
+        assert!(result.backup_path.join("root.txt").exists());
+        assert!(result.backup_path.join("a").join("a.txt").exists());
+        assert!(result
+            .backup_path
+            .join("a")
+            .join("b")
+            .join("b.txt")
+            .exists());
+        assert!(result
+            .backup_path
+            .join("a")
+            .join("b")
+            .join("c")
+            .join("d")
+            .join("e")
+            .join("deep.txt")
+            .exists());
+    }
+
+    #[test]
+    fn test_backup_file_large_content() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("large.txt");
+
+        // Create file with substantial content
+        let large_content = "x".repeat(10000);
+        fs::write(&source_path, &large_content).unwrap();
+
+        let config = default_config();
+        let result = backup_file(&source_path, &config).unwrap();
+
+        assert!(result.backup_path.exists());
+        assert_eq!(result.total_size, 10000);
+
+        let backup_content = fs::read_to_string(&result.backup_path).unwrap();
+        assert_eq!(backup_content, large_content);
+    }
+
+    #[test]
+    fn test_backup_result_new() {
+        let source = PathBuf::from("/source/path");
+        let backup = PathBuf::from("/backup/path");
+
+        let result = BackupResult::new(source.clone(), backup.clone());
+
+        assert_eq!(result.source_path, source);
+        assert_eq!(result.backup_path, backup);
+        assert_eq!(result.files_processed, 0);
+        assert_eq!(result.total_size, 0);
+        assert_eq!(result.duration.as_secs(), 0);
+    }
+
+    #[test]
+    fn test_backup_result_summary_single_file() {
+        let mut result =
+            BackupResult::new(PathBuf::from("source.txt"), PathBuf::from("backup.txt"));
+        result.files_processed = 1;
+        result.total_size = 1024;
+
+        let summary = result.summary();
+        assert!(summary.contains("Created backup:"));
+        assert!(summary.contains("1.0 KB"));
+        assert!(!summary.contains("files")); // Should not mention "files" for single file
+    }
+
+    #[test]
+    fn test_backup_result_summary_multiple_files() {
+        let mut result =
+            BackupResult::new(PathBuf::from("source_dir"), PathBuf::from("backup_dir"));
+        result.files_processed = 5;
+        result.total_size = 2048;
+
+        let summary = result.summary();
+        assert!(summary.contains("Created backup:"));
+        assert!(summary.contains("5 files"));
+        assert!(summary.contains("2.0 KB"));
+    }
+
+    #[test]
+    fn test_backup_result_summary_size_units() {
+        let mut result = BackupResult::new(PathBuf::from("source"), PathBuf::from("backup"));
+        result.files_processed = 1;
+        result.total_size = 1_500_000;
+
+        let si = Config {
+            size_units: crate::config::SizeUnits::Si,
+            ..default_config()
+        };
+        assert!(result.summary_with(&si).contains("(1.5 MB)"));
+
+        let iec = Config {
+            size_units: crate::config::SizeUnits::Iec,
+            ..default_config()
+        };
+        assert!(result.summary_with(&iec).contains("(1.4 MiB)"));
+    }
+
+    #[test]
+    fn test_backup_result_size_with_stored_size() {
+        let mut result = BackupResult::new(PathBuf::from("source"), PathBuf::from("backup"));
+        result.files_processed = 1;
+        result.total_size = 10 * 1024 * 1024;
+        assert_eq!(result.stored_percent(), None);
+        assert_eq!(result.size_with(&default_config()), "10.0 MB");
+
+        result.stored_size = Some(2_202_010);
+        assert_eq!(result.stored_percent(), Some(21));
+        assert_eq!(
+            result.size_with(&default_config()),
+            "10.0 MB → 2.1 MB (21%)"
+        );
+        assert!(result.summary().contains("(10.0 MB → 2.1 MB (21%))"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_backup_directory_broken_symlink_policy() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("regular.txt"), "regular content").unwrap();
+        symlink("missing.txt", source_dir.join("dangling.txt")).unwrap();
+
+        let mut config = default_config();
+        for policy in [BrokenSymlinkPolicy::Skip, BrokenSymlinkPolicy::Warn] {
+            config.symlink_broken_policy = policy;
+            assert_eq!(count_files_and_size(&source_dir, &config).unwrap().0, 1);
+            let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+            assert_eq!(result.files_processed, 1);
+            assert!(fs::symlink_metadata(result.backup_path.join("dangling.txt")).is_err());
+            fs::remove_dir_all(&result.backup_path).unwrap();
+        }
+
+        config.symlink_broken_policy = BrokenSymlinkPolicy::PreserveAsLink;
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert_eq!(
+            fs::read_link(result.backup_path.join("dangling.txt")).unwrap(),
+            Path::new("missing.txt")
+        );
+        fs::remove_dir_all(&result.backup_path).unwrap();
+
+        // The error surfaces during the scan, before any backup is created
+        config.symlink_broken_policy = BrokenSymlinkPolicy::Error;
+        assert!(count_files_and_size(&source_dir, &config).is_err());
+        assert!(backup_directory_with_progress(&source_dir, &config, false, true).is_err());
+        assert!(backup_directory(&source_dir, &config, false).is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_count_matches_copy_with_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("shared.txt"), "shared data").unwrap();
+
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("regular.txt"), "regular content").unwrap();
+        symlink(outside.join("shared.txt"), source_dir.join("file-link.txt")).unwrap();
+        symlink(&outside, source_dir.join("dir-link")).unwrap();
+
+        let config = default_config();
+        let (file_count, total_size) = count_files_and_size(&source_dir, &config).unwrap();
+        assert_eq!(file_count, 3);
+        assert_eq!(total_size, 15 + 11 + 11);
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, file_count);
+        assert_eq!(result.total_size, total_size);
+
+        // The non-progress path agrees too
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, file_count);
+        assert_eq!(result.total_size, total_size);
+
+        // Preserved links contribute nothing
+        let mut config = default_config();
+        config.follow_symlinks = false;
+        assert_eq!(count_files_and_size(&source_dir, &config).unwrap(), (1, 15));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlink_loop_is_detected() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("sub").join("file.txt"), "content").unwrap();
+        symlink(&source_dir, source_dir.join("sub").join("back-to-root")).unwrap();
+
+        let config = default_config();
+        let is_loop =
+            |result: Result<BackupResult>| matches!(result, Err(QbakError::SymlinkLoop { .. }));
+
+        assert!(is_loop(backup_directory(&source_dir, &config, false)));
+        assert!(is_loop(backup_directory_with_progress(
+            &source_dir,
+            &config,
+            false,
+            true
+        )));
+        assert!(matches!(
+            count_files_and_size(&source_dir, &config),
+            Err(QbakError::SymlinkLoop { .. })
+        ));
+
+        // The copy itself also stops instead of recursing until the stack overflows
+        let staging = dir.path().join("staging");
+        fs::create_dir_all(&staging).unwrap();
+        let mut result = BackupResult::new(source_dir.clone(), staging.clone());
+        let copied = copy_directory_contents_with_progress(
+            &source_dir,
+            &staging,
+            None,
+            &config,
+            &mut result,
+            &mut None,
+            &mut Traversal::default(),
+        );
+        assert!(matches!(copied, Err(QbakError::SymlinkLoop { .. })));
+    }
+
+    #[test]
+    fn test_backup_directory_max_depth() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("tree");
+        fs::create_dir_all(source_dir.join("a").join("b")).unwrap();
+        fs::write(source_dir.join("top.txt"), "top").unwrap();
+        fs::write(source_dir.join("a").join("a.txt"), "level a").unwrap();
+        fs::write(source_dir.join("a").join("b").join("b.txt"), "level b").unwrap();
+
+        // Depth 0 backs up only the immediate children
+        let mut config = default_config();
+        config.max_depth = Some(0);
+        assert_eq!(count_files_and_size(&source_dir, &config).unwrap(), (1, 3));
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(result.backup_path.join("top.txt").exists());
+        assert!(result.backup_path.join("a").is_dir());
+        assert!(!result.backup_path.join("a").join("a.txt").exists());
+        fs::remove_dir_all(&result.backup_path).unwrap();
+
+        config.max_depth_mode = DepthLimitMode::Skip;
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(!result.backup_path.join("a").exists());
+        fs::remove_dir_all(&result.backup_path).unwrap();
+
+        // Depth 1 includes the first level of subdirectories
+        config.max_depth = Some(1);
+        config.max_depth_mode = DepthLimitMode::Create;
+        assert_eq!(count_files_and_size(&source_dir, &config).unwrap(), (2, 10));
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert!(result.backup_path.join("a").join("a.txt").exists());
+        assert!(result.backup_path.join("a").join("b").is_dir());
+        assert!(!result
+            .backup_path
+            .join("a")
+            .join("b")
+            .join("b.txt")
+            .exists());
+    }
+
+    #[test]
+    fn test_backup_directory_max_file_size() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("project");
+        fs::create_dir_all(source_dir.join("build")).unwrap();
+        fs::write(source_dir.join("small.txt"), "small").unwrap();
+        fs::write(
+            source_dir.join("build").join("artifact.bin"),
+            vec![0u8; 4096],
+        )
+        .unwrap();
+
+        let mut config = default_config();
+        config.max_file_size = Some(1024);
+
+        assert_eq!(count_files_and_size(&source_dir, &config).unwrap(), (1, 5));
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert_eq!(result.total_size, 5);
+        assert!(result.backup_path.join("small.txt").exists());
+        assert!(!result
+            .backup_path
+            .join("build")
+            .join("artifact.bin")
+            .exists());
+
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+    }
+
+    #[test]
+    fn test_backup_directory_newer_than() {
+        use filetime::FileTime;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("project");
+        fs::create_dir_all(source_dir.join("old")).unwrap();
+        fs::create_dir_all(source_dir.join("mixed")).unwrap();
+        let old_time = FileTime::from_unix_time(1_600_000_000, 0);
+        for file in ["old/a.txt", "mixed/b.txt"] {
+            fs::write(source_dir.join(file), "old").unwrap();
+            filetime::set_file_mtime(source_dir.join(file), old_time).unwrap();
+        }
+        fs::write(source_dir.join("mixed").join("new.txt"), "new").unwrap();
+
+        let mut config = default_config();
+        config.newer_than = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert_eq!(count_files_and_size(&source_dir, &config).unwrap(), (1, 3));
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(result.backup_path.join("mixed").join("new.txt").exists());
+        assert!(!result.backup_path.join("mixed").join("b.txt").exists());
+        assert!(result.backup_path.join("old").is_dir());
+        fs::remove_dir_all(&result.backup_path).unwrap();
+
+        // Directories left empty by the cutoff can be pruned
+        config.prune_empty_dirs = true;
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(!result.backup_path.join("old").exists());
+        assert!(result.backup_path.join("mixed").join("new.txt").exists());
+    }
+
+    #[test]
+    fn test_backup_file_gzip() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("server.log");
+        let content = "GET /index.html 200\n".repeat(1000);
+        fs::write(&source, &content).unwrap();
+
+        let mut config = default_config();
+        config.compression = CompressionMode::Gzip;
+        config.verify = true;
+
+        let result = backup_file(&source, &config).unwrap();
+        let name = result.backup_path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("server-") && name.ends_with("-qbak.log.gz"));
+        assert_eq!(result.total_size, content.len() as u64);
+        assert_eq!(result.files_verified, 1);
+        assert!(fs::metadata(&result.backup_path).unwrap().len() < content.len() as u64);
+        assert_eq!(
+            result.stored_size,
+            Some(fs::metadata(&result.backup_path).unwrap().len())
+        );
+
+        let mut decompressed = String::new();
+        GzDecoder::new(File::open(&result.backup_path).unwrap())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, content);
+    }
+
+    #[test]
+    fn test_backup_file_to_writer() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("data.txt");
+        fs::write(&source, "streamed content").unwrap();
+
+        let mut output = Vec::new();
+        let result =
+            backup_file_to_writer(&source, &default_config(), &mut output, false, true).unwrap();
+        assert_eq!(output, b"streamed content");
+        assert_eq!(result.backup_path, PathBuf::from("-"));
+        assert_eq!(result.total_size, 16);
+        // Nothing is written next to the source
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let config = Config {
+            compression: CompressionMode::Gzip,
+            ..default_config()
+        };
+        let mut output = Vec::new();
+        backup_file_to_writer(&source, &config, &mut output, false, true).unwrap();
+        let mut decoded = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(output.as_slice()),
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded, "streamed content");
+
+        assert!(
+            backup_file_to_writer(dir.path(), &default_config(), &mut Vec::new(), false, true)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_verify_against_source() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("same.txt"), "same").unwrap();
+        fs::write(source_dir.join("edited.txt"), "before").unwrap();
+        fs::write(source_dir.join("sub/grown.txt"), "small").unwrap();
+        fs::write(source_dir.join("removed.txt"), "gone").unwrap();
+
+        let config = default_config();
+        let backup = backup_directory(&source_dir, &config, false)
+            .unwrap()
+            .backup_path;
+        let report = verify_against_source(&backup, &source_dir, &config, true).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.files_checked, 4);
+
+        fs::write(source_dir.join("edited.txt"), "after!").unwrap();
+        fs::write(source_dir.join("sub/grown.txt"), "much larger").unwrap();
+        fs::remove_file(source_dir.join("removed.txt")).unwrap();
+        fs::write(source_dir.join("added.txt"), "new").unwrap();
+
+        // Sizes alone don't catch same-sized edits
+        let report = verify_against_source(&backup, &source_dir, &config, false).unwrap();
+        assert_eq!(report.discrepancies.len(), 3);
+
+        let report = verify_against_source(&backup, &source_dir, &config, true).unwrap();
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::Missing(PathBuf::from("added.txt")),
+                Discrepancy::ChecksumMismatch(PathBuf::from("edited.txt")),
+                Discrepancy::SizeMismatch {
+                    path: PathBuf::from("sub/grown.txt"),
+                    source: 11,
+                    backup: 5,
+                },
+                Discrepancy::Extra(PathBuf::from("removed.txt")),
+            ]
+        );
+        assert_eq!(report.files_checked, 3);
+    }
+
+    #[test]
+    fn test_backup_file_rate_limit() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("data.bin");
+        let content = vec![7u8; 256 * 1024];
+        fs::write(&source, &content).unwrap();
+
+        let config = Config {
+            rate_limit: Some(1024 * 1024),
+            use_reflink: ReflinkMode::Never,
+            ..default_config()
+        };
+
+        // 256 KB at 1 MB/s must take at least a quarter of a second
+        let start = Instant::now();
+        let result = backup_file(&source, &config).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(240));
+        assert_eq!(fs::read(&result.backup_path).unwrap(), content);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_backup_directory_tar() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("project");
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(source.join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(source.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(source.join(".env"), "SECRET=1").unwrap();
+        symlink("src/main.rs", source.join("entry.rs")).unwrap();
+
+        let mut config = default_config();
+        config.archive = ArchiveMode::Tar;
+        config.follow_symlinks = false;
+        config.include_hidden = false;
+
+        let result = backup_directory(&source, &config, false).unwrap();
+        let name = result.backup_path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("project-") && name.ends_with("-qbak.tar"));
+        assert!(result.backup_path.is_file());
+        assert_eq!(result.files_processed, 2);
+
+        let mut archive = tar::Archive::new(File::open(&result.backup_path).unwrap());
+        let mut entries = std::collections::HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            entries.insert(path, entry.header().clone());
+        }
+
+        assert!(entries.contains_key("project/src/main.rs"));
+        assert!(!entries.contains_key("project/.env"));
+        assert!(entries["project/src"].entry_type().is_dir());
+        assert_eq!(entries["project/run.sh"].mode().unwrap() & 0o777, 0o755);
+        let mtime = fs::metadata(source.join("run.sh"))
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert_eq!(entries["project/run.sh"].mtime().unwrap(), mtime);
+
+        let link = &entries["project/entry.rs"];
+        assert!(link.entry_type().is_symlink());
+        assert_eq!(link.link_name().unwrap().unwrap(), Path::new("src/main.rs"));
+    }
+
+    #[test]
+    fn test_backup_directory_tar_gzip() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("logs");
+        fs::create_dir(&source).unwrap();
+        let content = "GET /index.html 200\n".repeat(1000);
+        fs::write(source.join("access.log"), &content).unwrap();
+
+        let mut config = default_config();
+        config.archive = ArchiveMode::Tar;
+        config.compression = CompressionMode::Gzip;
+
+        let result = backup_directory_with_progress(&source, &config, false, true).unwrap();
+        let name = result.backup_path.file_name().unwrap().to_str().unwrap();
+        assert!(name.ends_with("-qbak.tar.gz"));
+        assert_eq!(result.total_size, content.len() as u64);
+
+        let decoder = GzDecoder::new(File::open(&result.backup_path).unwrap());
+        let mut archive = tar::Archive::new(decoder);
+        let mut entry = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.path().unwrap() == Path::new("logs/access.log"))
+            .unwrap();
+        let mut extracted = String::new();
+        entry.read_to_string(&mut extracted).unwrap();
+        assert_eq!(extracted, content);
+
+        // No staging files are left behind
+        let leftovers = fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(leftovers, 2);
+    }
+
+    #[test]
+    fn test_backup_combined() {
+        let dir = tempdir().unwrap();
+        let hosts = dir.path().join("hosts");
+        let app = dir.path().join("etc/app");
+        fs::write(&hosts, "127.0.0.1 localhost\n").unwrap();
+        fs::create_dir_all(&app).unwrap();
+        fs::write(app.join("app.conf"), "debug = false\n").unwrap();
+        let output = dir.path().join("out");
+
+        let mut config = default_config();
+        config.output_dir = Some(output.clone());
+        let sources = vec![hosts.clone(), app.clone()];
+
+        // Without an archive format there is nothing to combine into
+        assert!(matches!(
+            backup_combined(&sources, &config, None),
+            Err(QbakError::Validation { .. })
+        ));
+
+        config.archive = ArchiveMode::Tar;
+        let result = backup_combined(&sources, &config, None).unwrap();
+        let name = result.backup_path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("backup-") && name.ends_with("-qbak.tar"));
+        assert_eq!(result.backup_path.parent(), Some(output.as_path()));
+        assert_eq!(result.files_processed, 2);
+        assert_eq!(result.total_size, 20 + 14);
+
+        let mut archive = tar::Archive::new(File::open(&result.backup_path).unwrap());
+        let mut paths: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["app", "app/app.conf", "hosts"]);
+
+        // Only the archive is written
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 1);
+
+        // Targets sharing a name would overwrite each other inside the archive
+        let other = dir.path().join("other/app");
+        fs::create_dir_all(&other).unwrap();
+        let clash = backup_combined(&[app, other], &config, None);
+        assert!(matches!(clash, Err(QbakError::Validation { .. })));
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_backup_directory_zip() {
+        use std::io::Read;
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("docs");
+        fs::create_dir_all(source.join("guide")).unwrap();
+        let content = "Installation steps\n".repeat(500);
+        fs::write(source.join("guide/install.md"), &content).unwrap();
+        fs::write(source.join("README.md"), "# Docs").unwrap();
+
+        for level in [0, 9] {
+            let mut config = default_config();
+            config.archive = ArchiveMode::Zip;
+            config.zip_compression_level = level;
+            // Gzip doesn't apply to zip archives
+            config.compression = CompressionMode::Gzip;
+
+            let result = backup_directory(&source, &config, false).unwrap();
+            let name = result.backup_path.file_name().unwrap().to_str().unwrap();
+            assert!(name.starts_with("docs-") && name.ends_with("-qbak.zip"));
+            assert_eq!(result.files_processed, 2);
+
+            let mut archive =
+                zip::ZipArchive::new(File::open(&result.backup_path).unwrap()).unwrap();
+            assert!(archive.by_name("docs/guide/").unwrap().is_dir());
+
+            let mut entry = archive.by_name("docs/guide/install.md").unwrap();
+            let expected = if level == 0 {
+                zip::CompressionMethod::Stored
+            } else {
+                zip::CompressionMethod::Deflated
+            };
+            assert_eq!(entry.compression(), expected);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = fs::metadata(source.join("guide/install.md"))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(entry.unix_mode().unwrap() & 0o777, mode & 0o777);
+            }
+
+            let mut extracted = String::new();
+            entry.read_to_string(&mut extracted).unwrap();
+            assert_eq!(extracted, content);
+            drop(entry);
+
+            fs::remove_file(&result.backup_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_backup_directory_with_symlinks() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        // Create a regular file
+        fs::write(source_dir.join("regular.txt"), "regular content").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::symlink;
+
+            // Create a symlink to the regular file
+            let target = source_dir.join("regular.txt");
+            let link = source_dir.join("link.txt");
+            symlink(&target, &link).unwrap();
+
+            // Test with follow_symlinks = true (default)
+            let mut config = default_config();
+            config.follow_symlinks = true;
+
+            let result = backup_directory(&source_dir, &config, false).unwrap();
+
+            // Should process both the regular file and the symlink target
+            assert_eq!(result.files_processed, 2);
+            assert!(result.backup_path.join("regular.txt").exists());
+            assert!(result.backup_path.join("link.txt").exists());
+
+            // Clean up
+            fs::remove_dir_all(&result.backup_path).unwrap();
+
+            // Test with follow_symlinks = false
+            config.follow_symlinks = false;
+            let result = backup_directory(&source_dir, &config, false).unwrap();
+
+            // Should still handle symlinks
+            assert!(result.backup_path.join("regular.txt").exists());
+        }
+    }
+
+    #[test]
+    fn test_prune_old_backups_same_second() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("server.log");
+        fs::write(&source, "log").unwrap();
+
+        // `qbak --gzip` followed by a plain backup within the same second
+        let gzipped = dir.path().join("server-20250101T120000-qbak.log.gz");
+        let plain = dir.path().join("server-20250101T120000-qbak.log");
+        fs::write(&gzipped, "gz").unwrap();
+        fs::write(&plain, "log").unwrap();
+        let earlier = filetime::FileTime::from_unix_time(1_700_000_000, 0);
+        let later = filetime::FileTime::from_unix_time(1_700_000_001, 0);
+        filetime::set_file_mtime(&gzipped, earlier).unwrap();
+        filetime::set_file_mtime(&plain, later).unwrap();
+
+        // The modification time breaks the tie between equal timestamps
+        let config = default_config();
+        let would_remove = prune_old_backups(&source, &config, 1, None, true).unwrap();
+        assert_eq!(would_remove, vec![gzipped.clone()]);
+
+        // The backup just created is kept even when it looks older
+        filetime::set_file_mtime(&gzipped, later).unwrap();
+        filetime::set_file_mtime(&plain, earlier).unwrap();
+        let created = Path::new("server-20250101T120000-qbak.log");
+        let removed = prune_old_backups(&source, &config, 1, Some(created), false).unwrap();
+        assert_eq!(removed, vec![gzipped.clone()]);
+        assert!(plain.exists());
+        assert!(!gzipped.exists());
+    }
+
+    #[test]
+    fn test_prune_old_backups() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("report.txt");
+        fs::write(&source, "report").unwrap();
+
+        for name in [
+            "report-20250101T120000-qbak.txt",
+            "report-20250201T120000-qbak.txt",
+            "report-20250301T120000-qbak.txt",
+            "report-20250401T120000-qbak.txt",
+            "report-20250101T120000-qbak.md",
+            "notes-20250101T120000-qbak.txt",
+        ] {
+            fs::write(dir.path().join(name), "old").unwrap();
+        }
+        fs::create_dir(dir.path().join("report-20250115T120000-qbak.txt")).unwrap();
+
+        let config = default_config();
+        let would_remove = prune_old_backups(&source, &config, 2, None, true).unwrap();
+        let names: Vec<_> = would_remove
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "report-20250201T120000-qbak.txt",
+                "report-20250115T120000-qbak.txt",
+                "report-20250101T120000-qbak.txt",
+            ]
+        );
+        assert!(would_remove.iter().all(|path| path.exists()));
+
+        let removed = prune_old_backups(&source, &config, 2, None, false).unwrap();
+        assert_eq!(removed, would_remove);
+        assert!(removed.iter().all(|path| !path.exists()));
+        assert!(dir.path().join("report-20250401T120000-qbak.txt").exists());
+        assert!(dir.path().join("report-20250301T120000-qbak.txt").exists());
+        assert!(dir.path().join("report-20250101T120000-qbak.md").exists());
+        assert!(dir.path().join("notes-20250101T120000-qbak.txt").exists());
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn test_backup_directory_with_callback() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("photos");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.jpg"), "aaaa").unwrap();
+        fs::write(source.join("b.jpg"), "bb").unwrap();
+
+        let mut events = Vec::new();
+        let result =
+            backup_directory_with_callback(&source, &default_config(), |event| events.push(event))
+                .unwrap();
+        assert_eq!(result.files_processed, 2);
+
+        assert_eq!(events.first(), Some(&ProgressEvent::ScanStarted));
+        assert!(events.contains(&ProgressEvent::ScanFinished { files: 2, bytes: 6 }));
+        let backing: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::BackingUpdate { files, .. } => Some(*files),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(backing, vec![1, 2]);
+        assert_eq!(events.last(), Some(&ProgressEvent::Finished));
+    }
+
+    #[test]
+    fn test_backup_file_with_shared_progress_reports_bytes() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("first.txt");
+        let large = dir.path().join("large.bin");
+        fs::write(&first, vec![b'a'; 100]).unwrap();
+        fs::write(&large, vec![b'b'; 64 * 1024]).unwrap();
+
+        let mut config = default_config();
+        config.copy_buffer_size = crate::config::MIN_COPY_BUFFER_SIZE;
+        config.use_reflink = ReflinkMode::Never;
+        config.preserve_sparse = false;
+
+        let mut events = Vec::new();
+        let mut progress =
+            BackupProgress::with_callback(config.progress.clone(), |event| events.push(event));
+        backup_file_with_shared_progress(&first, &config, &mut progress).unwrap();
+        backup_file_with_shared_progress(&large, &config, &mut progress).unwrap();
+        drop(progress);
+
+        // The second target reports each chunk on top of the first target's bytes
+        let bytes: Vec<u64> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::BackingUpdate { bytes, current, .. } if *current == large => {
+                    Some(*bytes)
+                }
+                _ => None,
+            })
+            .collect();
+        assert!(bytes.len() > 2, "{bytes:?}");
+        assert!(bytes.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(bytes.iter().any(|&b| b > 100 && b < 100 + 64 * 1024));
+        assert_eq!(bytes.last(), Some(&(100 + 64 * 1024)));
+    }
+
+    #[test]
+    fn test_backup_directory_records_entries() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("site");
+        fs::create_dir_all(source.join("assets")).unwrap();
+        fs::write(source.join("index.html"), "<html>").unwrap();
+        fs::write(source.join("assets/video.mp4"), vec![0u8; 2048]).unwrap();
+
+        let mut config = default_config();
+        config.max_file_size = Some(1024);
+
+        // Nothing is recorded unless asked for
+        let result = backup_directory_with_progress(&source, &config, false, true).unwrap();
+        assert!(result.entries.is_empty());
+
+        config.record_entries = true;
+        let result = backup_directory_with_progress(&source, &config, false, true).unwrap();
+        assert_eq!(result.entries.len(), 2);
+
+        let copied = result
+            .entries
+            .iter()
+            .find(|entry| entry.source == source.join("index.html"))
+            .unwrap();
+        assert_eq!(copied.outcome, EntryOutcome::Copied);
+        assert_eq!(copied.size, 6);
+        assert_eq!(copied.backup, result.backup_path.join("index.html"));
+        assert!(copied.backup.exists());
+
+        let skipped = result
+            .entries
+            .iter()
+            .find(|entry| entry.source == source.join("assets/video.mp4"))
+            .unwrap();
+        assert_eq!(
+            skipped.outcome,
+            EntryOutcome::Skipped("larger than max_file_size".to_string())
+        );
+        assert!(!skipped.backup.exists());
+    }
+
+    #[test]
+    fn test_backup_directory_skipped_summary() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("site");
+        fs::create_dir_all(source.join("cache")).unwrap();
+        fs::write(source.join("index.html"), "<html>").unwrap();
+        fs::write(source.join("debug.log"), "log").unwrap();
+        fs::write(source.join("cache/page"), "cached").unwrap();
+        fs::write(source.join("video.mp4"), vec![0u8; 2048]).unwrap();
+
+        let config = Config {
+            exclude_patterns: vec!["*.log".to_string(), "cache/".to_string()],
+            max_file_size: Some(1024),
+            ..default_config()
+        };
+        for result in [
+            backup_directory(&source, &config, false).unwrap(),
+            backup_directory_with_progress(&source, &config, false, true).unwrap(),
+        ] {
+            let mut skipped = result.skipped.clone();
+            skipped.sort();
+            assert_eq!(
+                skipped,
+                vec![
+                    (source.join("cache"), SkipReason::Excluded),
+                    (source.join("debug.log"), SkipReason::Excluded),
+                    (source.join("video.mp4"), SkipReason::Oversize),
+                ]
+            );
+            assert_eq!(
+                result.skipped_summary().unwrap(),
+                "3 entries skipped (2 excluded, 1 oversize)"
+            );
+        }
+
+        let result = backup_directory(&source, &default_config(), false).unwrap();
+        assert_eq!(result.skipped_summary(), None);
+    }
+
+    #[test]
+    fn test_backup_into_existing_directory() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("project");
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("README.md"), "readme").unwrap();
+        fs::write(source.join("src/main.rs"), "fn main() {}").unwrap();
+        let dest = dir.path().join("fixed");
+
+        let config = default_config();
+        let result = backup_into(&source, &dest, &config).unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert_eq!(result.total_size, 18);
+
+        let names = |dir: &Path| {
+            let mut names: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let top = names(&dest);
+        assert_eq!(top.len(), 2);
+        assert!(top[0].starts_with("README-") && top[0].ends_with("-qbak.md"));
+        assert_eq!(top[1], "src");
+        let nested = names(&dest.join("src"));
+        assert_eq!(nested.len(), 1);
+        assert!(nested[0].starts_with("main-") && nested[0].ends_with("-qbak.rs"));
+        assert_eq!(
+            fs::read_to_string(dest.join("src").join(&nested[0])).unwrap(),
+            "fn main() {}"
+        );
+
+        // A second run merges into the same directories and resolves each name separately
+        backup_into(&source, &dest, &config).unwrap();
+        let nested = names(&dest.join("src"));
+        assert_eq!(nested.len(), 2);
+        assert!(nested.iter().any(|name| name.ends_with("-qbak-1.rs")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_backup_into_reports_skipped_symlinks() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("project");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("main.rs"), "fn main() {}").unwrap();
+        let link = source.join("latest.rs");
+        std::os::unix::fs::symlink("main.rs", &link).unwrap();
+        let dest = dir.path().join("fixed");
+
+        let mut config = default_config();
+        config.follow_symlinks = false;
+        config.record_entries = true;
+        let result = backup_into(&source, &dest, &config).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert_eq!(result.skipped, vec![(link.clone(), SkipReason::Symlink)]);
+        let skipped = result
+            .entries
+            .iter()
+            .find(|entry| entry.source == link)
+            .unwrap();
+        assert_eq!(
+            skipped.outcome,
+            EntryOutcome::Skipped("symlink".to_string())
+        );
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_backup_into_inside_source_rejected() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("src");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("main.rs"), "fn main() {}").unwrap();
+
+        // `--into src/out src` would keep backing up its own output
+        let config = default_config();
+        for dest in [source.join("out"), source.clone()] {
+            assert!(matches!(
+                backup_into(&source, &dest, &config),
+                Err(QbakError::Validation { .. })
+            ));
+        }
+        assert!(!source.join("out").exists());
+        assert_eq!(fs::read_dir(&source).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_cleanup_temp_files() {
+        let dir = tempdir().unwrap();
+
+        // Create some temp files that look like qbak temp files
+        let temp1 = dir.path().join(".qbak_temp_12345_file1.txt");
+        let temp2 = dir.path().join(".qbak_temp_67890_file2.txt");
+        let normal = dir.path().join("normal_file.txt");
+
+        File::create(&temp1).unwrap();
+        File::create(&temp2).unwrap();
+        File::create(&normal).unwrap();
+
+        // Run cleanup
+        assert!(cleanup_temp_files(dir.path()).is_ok());
+
+        // Temp files should be gone, normal file should remain
+        assert!(!temp1.exists());
+        assert!(!temp2.exists());
+        assert!(normal.exists());
+    }
+
+    #[test]
+    fn test_cleanup_temp_files_nonexistent_dir() {
+        let nonexistent = Path::new("/nonexistent/directory");
+        let result = cleanup_temp_files(nonexistent);
+        assert!(result.is_ok()); // Should handle gracefully
+    }
+
+    #[test]
+    fn test_backup_file_reflink_modes() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("image.bin");
+        let content = vec![7u8; 200 * 1024];
+        fs::write(&source_path, &content).unwrap();
+
+        // Auto falls back to a regular copy on filesystems without reflink support
+        let mut config = default_config();
+        config.use_reflink = ReflinkMode::Auto;
+        let result = backup_file(&source_path, &config).unwrap();
+        assert_eq!(fs::read(&result.backup_path).unwrap(), content);
+
+        config.use_reflink = ReflinkMode::Never;
+        let result = backup_file(&source_path, &config).unwrap();
+        assert_eq!(fs::read(&result.backup_path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_backup_file_preserves_mtime() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("old.txt");
+        fs::write(&source_path, "old data").unwrap();
+
+        // Backdate the source so a fresh copy would have a visibly different mtime
+        let past = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&source_path, past).unwrap();
+
+        let result = backup_file(&source_path, &default_config()).unwrap();
+
+        let source_mtime = fs::metadata(&source_path).unwrap().modified().unwrap();
+        let backup_mtime = fs::metadata(&result.backup_path)
+            .unwrap()
+            .modified()
+            .unwrap();
+        let difference = match backup_mtime.duration_since(source_mtime) {
+            Ok(duration) => duration,
+            Err(e) => e.duration(),
+        };
+        assert!(difference < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_backup_with_preserve_acls() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("shared");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("sub").join("file.txt"), "shared").unwrap();
+
+        let mut config = default_config();
+        config.preserve_acls = true;
+
+        // ACL failures (unsupported filesystem or build) only warn
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+        let result = backup_file(&source_dir.join("sub").join("file.txt"), &config).unwrap();
+        assert!(result.backup_path.exists());
+
+        // A build without ACL support says so only once
+        if !ACL_SUPPORT {
+            assert!(!take_acl_support_warning());
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_backup_directory_skips_fifo() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("project");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("data.txt"), "data").unwrap();
+        let fifo = source_dir.join("pipe");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+
+        let config = default_config();
+        assert_eq!(count_files_and_size(&source_dir, &config).unwrap(), (1, 4));
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(result.backup_path.join("data.txt").exists());
+        assert!(fs::symlink_metadata(result.backup_path.join("pipe")).is_err());
+
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_backup_sparse_file() {
+        use std::io::{Seek, SeekFrom};
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("disk.img");
+        let length = 16 * 1024 * 1024;
+        {
+            let mut file = File::create(&source_path).unwrap();
+            file.set_len(length).unwrap();
+            file.seek(SeekFrom::Start(4 * 1024 * 1024)).unwrap();
+            file.write_all(b"data in the middle").unwrap();
+        }
+
+        let mut config = default_config();
+        config.use_reflink = ReflinkMode::Never;
+
+        let result = backup_file(&source_path, &config).unwrap();
+        assert_eq!(result.total_size, length);
+        assert_eq!(
+            fs::read(&result.backup_path).unwrap(),
+            fs::read(&source_path).unwrap()
+        );
+
+        // Only check allocation when the filesystem actually created a sparse source
+        let source_metadata = fs::metadata(&source_path).unwrap();
+        if source_metadata.blocks() * 512 < length {
+            let backup_metadata = fs::metadata(&result.backup_path).unwrap();
+            assert_eq!(backup_metadata.len(), length);
+            assert!(backup_metadata.blocks() * 512 < length);
+        }
+
+        // Dense copy still produces identical content
+        config.preserve_sparse = false;
+        let result = backup_file(&source_path, &config).unwrap();
+        assert_eq!(
+            fs::read(&result.backup_path).unwrap(),
+            fs::read(&source_path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_backup_with_verify() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("important.txt");
+        fs::write(&source_path, "important data").unwrap();
+
+        let mut config = default_config();
+        config.verify = true;
+
+        let result = backup_file(&source_path, &config).unwrap();
+        assert_eq!(result.files_verified, 1);
+
+        let source_dir = dir.path().join("data");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("a.txt"), "a").unwrap();
+        fs::write(source_dir.join("sub").join("b.txt"), "b").unwrap();
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_verified, 2);
+
+        // Verification is off by default
+        let result = backup_file(&source_path, &default_config()).unwrap();
+        assert_eq!(result.files_verified, 0);
+    }
 
-                // Update progress
-                if let Some(ref mut prog) = progress {
-                    prog.update_backup_progress(result.files_processed, result.total_size, source);
-                }
-            }
+    #[test]
+    fn test_verify_backup_mismatch() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source.txt");
+        let backup = dir.path().join("backup.txt");
+        fs::write(&source, "original").unwrap();
+        fs::write(&backup, "corrupted").unwrap();
+
+        match verify_backup(&source, &backup).unwrap_err() {
+            QbakError::VerificationFailed { path } => assert_eq!(path, backup),
+            other => panic!("Expected VerificationFailed error, got {other:?}"),
         }
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_restore_backup_file() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("config.ini");
+        fs::write(&source_path, "original").unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::default_config;
-    use std::fs::File;
-    use std::io::Write;
-    use tempfile::tempdir;
+        let config = default_config();
+        let backup = backup_file(&source_path, &config).unwrap();
+
+        // Refuses to overwrite without force
+        fs::write(&source_path, "modified").unwrap();
+        let result = restore_backup(&backup.backup_path, &config, false);
+        assert!(matches!(result, Err(QbakError::Validation { .. })));
+        assert_eq!(fs::read_to_string(&source_path).unwrap(), "modified");
+
+        let result = restore_backup(&backup.backup_path, &config, true).unwrap();
+        assert_eq!(result.backup_path, source_path);
+        assert_eq!(fs::read_to_string(&source_path).unwrap(), "original");
+        assert!(backup.backup_path.exists());
+    }
 
     #[test]
-    fn test_backup_file() {
+    fn test_write_manifest() {
         let dir = tempdir().unwrap();
-        let source_path = dir.path().join("test.txt");
+        let source = dir.path().join("config");
+        fs::create_dir_all(source.join("nginx")).unwrap();
+        fs::write(source.join("nginx/site.conf"), "server {}").unwrap();
+        fs::write(source.join("hosts"), "127.0.0.1 localhost").unwrap();
 
-        // Create source file
-        let mut file = File::create(&source_path).unwrap();
-        writeln!(file, "Hello, World!").unwrap();
-
-        let config = default_config();
-        let result = backup_file(&source_path, &config).unwrap();
+        let mut config = default_config();
+        config.write_manifest = true;
+        config.manifest_checksums = true;
+
+        let result = backup_directory_with_progress(&source, &config, false, true).unwrap();
+        let path = manifest_path(&result.backup_path);
+        assert!(path.to_str().unwrap().ends_with("-qbak.qbak.json"));
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            manifest["source"],
+            source.canonicalize().unwrap().display().to_string()
+        );
+        assert_eq!(manifest["qbak_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest["files"], 2);
+        assert_eq!(manifest["total_size"], 28);
+        assert!(manifest["created"].as_str().unwrap().ends_with('Z'));
+        let checksums = manifest["checksums"].as_object().unwrap();
+        assert_eq!(checksums.len(), 2);
+        assert_eq!(
+            checksums["nginx/site.conf"],
+            checksum_hex(&source.join("nginx/site.conf")).unwrap()
+        );
 
-        // Check that backup was created
-        assert!(result.backup_path.exists());
-        assert_eq!(result.files_processed, 1);
-        assert_eq!(result.total_size, 14); // "Hello, World!\n"
+        // No temporary manifest is left behind
+        let leftovers: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                let entry = entry.as_ref().unwrap();
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".qbak_temp_")
+            })
+            .collect();
+        assert!(leftovers.is_empty());
+
+        // Pruning removes the manifest along with its backup
+        let old = dir.path().join("config-20200101T000000-qbak");
+        fs::create_dir(&old).unwrap();
+        fs::write(manifest_path(&old), "{}").unwrap();
+        prune_old_backups(&source, &config, 1, None, false).unwrap();
+        assert!(!manifest_path(&old).exists());
+        assert!(path.exists());
+    }
 
-        // Check backup content
-        let backup_content = fs::read_to_string(&result.backup_path).unwrap();
-        assert_eq!(backup_content, "Hello, World!\n");
+    #[test]
+    fn test_restore_uses_manifest_name() {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("app.conf");
+        fs::write(&source_path, "original").unwrap();
 
-        // Verify backup summary
-        let summary = result.summary();
-        assert!(summary.contains("Created backup:"));
-        assert!(summary.contains("14 B"));
+        let mut config = default_config();
+        config.write_manifest = true;
+        let backup = backup_file(&source_path, &config).unwrap();
+
+        // The manifest wins over whatever the backup name suggests
+        let renamed = dir.path().join("snapshot");
+        fs::rename(&backup.backup_path, &renamed).unwrap();
+        fs::rename(manifest_path(&backup.backup_path), manifest_path(&renamed)).unwrap();
+        assert_eq!(restore_target_path(&renamed, &config).unwrap(), source_path);
     }
 
     #[test]
-    fn test_backup_file_nonexistent() {
+    fn test_restore_backup_directory() {
         let dir = tempdir().unwrap();
-        let source_path = dir.path().join("nonexistent.txt");
+        let source_dir = dir.path().join("site");
+        fs::create_dir_all(source_dir.join("css")).unwrap();
+        fs::write(source_dir.join("index.html"), "<html>").unwrap();
+        fs::write(source_dir.join("css").join("style.css"), "body {}").unwrap();
 
         let config = default_config();
-        let result = backup_file(&source_path, &config);
+        let backup = backup_directory(&source_dir, &config, false).unwrap();
 
-        assert!(result.is_err());
-        match result.unwrap_err() {
-            QbakError::SourceNotFound { path } => assert_eq!(path, source_path),
-            _ => panic!("Expected SourceNotFound error"),
-        }
+        // Restoring into a missing original works without force
+        fs::remove_dir_all(&source_dir).unwrap();
+        let result = restore_backup(&backup.backup_path, &config, false).unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert_eq!(
+            fs::read_to_string(source_dir.join("css").join("style.css")).unwrap(),
+            "body {}"
+        );
+
+        // Forced restore replaces the existing directory entirely
+        fs::write(source_dir.join("extra.txt"), "new").unwrap();
+        restore_backup(&backup.backup_path, &config, true).unwrap();
+        assert!(!source_dir.join("extra.txt").exists());
+        assert!(source_dir.join("index.html").exists());
     }
 
     #[test]
-    fn test_backup_directory() {
+    fn test_restore_backup_file_over_directory() {
         let dir = tempdir().unwrap();
-        let source_dir = dir.path().join("source");
-        fs::create_dir_all(&source_dir).unwrap();
+        let source_path = dir.path().join("settings");
+        fs::write(&source_path, "original").unwrap();
 
-        // Create some files
-        File::create(source_dir.join("file1.txt")).unwrap();
-        File::create(source_dir.join("file2.txt")).unwrap();
+        let config = default_config();
+        let backup = backup_file(&source_path, &config).unwrap();
 
-        let subdir = source_dir.join("subdir");
-        fs::create_dir_all(&subdir).unwrap();
-        File::create(subdir.join("file3.txt")).unwrap();
+        // The original name is now a directory
+        fs::remove_file(&source_path).unwrap();
+        fs::create_dir(&source_path).unwrap();
+        fs::write(source_path.join("keep.txt"), "keep").unwrap();
 
-        let config = default_config();
-        let result = backup_directory(&source_dir, &config, false).unwrap();
+        // A failed restore leaves the existing target alone
+        let context = crate::signal::BackupContext::new();
+        context.set_interrupted(true);
+        let result = context.scope(|| restore_backup(&backup.backup_path, &config, true));
+        assert!(matches!(result, Err(QbakError::Interrupted)));
+        assert_eq!(
+            fs::read_to_string(source_path.join("keep.txt")).unwrap(),
+            "keep"
+        );
 
-        // Check that backup directory was created
-        assert!(result.backup_path.exists());
-        assert!(result.backup_path.is_dir());
-        assert_eq!(result.files_processed, 3);
+        restore_backup(&backup.backup_path, &config, true).unwrap();
+        assert_eq!(fs::read_to_string(&source_path).unwrap(), "original");
+        let leftovers: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().starts_with(".qbak_"))
+            .collect();
+        assert!(leftovers.is_empty(), "{leftovers:?}");
+    }
 
-        // Check that files were copied
-        assert!(result.backup_path.join("file1.txt").exists());
-        assert!(result.backup_path.join("file2.txt").exists());
-        assert!(result.backup_path.join("subdir").join("file3.txt").exists());
+    #[test]
+    fn test_restore_backup_invalid_name() {
+        let dir = tempdir().unwrap();
+        let not_a_backup = dir.path().join("plain.txt");
+        fs::write(&not_a_backup, "content").unwrap();
 
-        // Verify backup summary for multiple files
-        let summary = result.summary();
-        assert!(summary.contains("Created backup:"));
-        assert!(summary.contains("3 files"));
+        let result = restore_backup(&not_a_backup, &default_config(), true);
+        assert!(matches!(result, Err(QbakError::Validation { .. })));
     }
 
     #[test]
-    fn test_backup_directory_on_file() {
+    fn test_backup_file_zero_size() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("file.txt");
-        File::create(&file_path).unwrap();
+        let source_path = dir.path().join("empty.txt");
+
+        // Create empty file
+        File::create(&source_path).unwrap();
 
         let config = default_config();
-        let result = backup_directory(&file_path, &config, false);
+        let result = backup_file(&source_path, &config).unwrap();
 
-        assert!(result.is_err());
-        match result.unwrap_err() {
-            QbakError::Validation { message } => assert!(message.contains("not a directory")),
-            _ => panic!("Expected Validation error"),
-        }
+        assert!(result.backup_path.exists());
+        assert_eq!(result.total_size, 0);
+        assert_eq!(result.files_processed, 1);
     }
 
     #[test]
-    fn test_backup_collision_resolution() {
+    fn test_count_files_and_size() {
         let dir = tempdir().unwrap();
-        let source_path = dir.path().join("test.txt");
-        File::create(&source_path).unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
 
-        let config = default_config();
+        // Create test structure
+        File::create(source_dir.join("file1.txt")).unwrap();
+        File::create(source_dir.join("file2.txt")).unwrap();
 
-        // First backup
-        let result1 = backup_file(&source_path, &config).unwrap();
-        assert!(result1.backup_path.exists());
+        let subdir = source_dir.join("subdir");
+        fs::create_dir_all(&subdir).unwrap();
+        File::create(subdir.join("file3.txt")).unwrap();
+        File::create(subdir.join("file4.txt")).unwrap();
 
-        // Create a file with the same timestamp pattern to force collision
-        let backup_name = result1.backup_path.file_name().unwrap().to_str().unwrap();
-        let collision_path = dir.path().join(backup_name);
-        File::create(&collision_path).unwrap();
+        let config = default_config();
+        let count = count_files_and_size(&source_dir, &config).unwrap().0;
+        assert_eq!(count, 4);
+    }
 
-        // This should work because resolve_collision will handle it
-        // Note: In real usage, this is less likely due to timestamp precision
+    #[test]
+    #[cfg(unix)]
+    fn test_count_files_and_size_hardlinks() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("a.bin"), vec![1u8; 4096]).unwrap();
+        fs::hard_link(source_dir.join("a.bin"), source_dir.join("b.bin")).unwrap();
+        fs::hard_link(source_dir.join("a.bin"), source_dir.join("c.bin")).unwrap();
+
+        // Every link is a file, but the data is only on disk once
+        let config = default_config();
+        assert_eq!(
+            count_files_and_size(&source_dir, &config).unwrap(),
+            (3, 4096)
+        );
     }
 
     #[test]
-    fn test_hidden_file_handling() {
+    fn test_count_files_and_size_with_hidden() {
         let dir = tempdir().unwrap();
         let source_dir = dir.path().join("source");
         fs::create_dir_all(&source_dir).unwrap();
 
-        // Create visible and hidden files
-        File::create(source_dir.join("visible.txt")).unwrap();
-        File::create(source_dir.join(".hidden.txt")).unwrap();
+        // Create normal and hidden files
+        File::create(source_dir.join("file1.txt")).unwrap();
+        File::create(source_dir.join(".hidden")).unwrap();
 
         // Test with include_hidden = true
         let mut config = default_config();
         config.include_hidden = true;
-        let result = backup_directory(&source_dir, &config, false).unwrap();
-        assert_eq!(result.files_processed, 2);
-        assert!(result.backup_path.join(".hidden.txt").exists());
-
-        // Clean up for next test
-        fs::remove_dir_all(&result.backup_path).unwrap();
+        let count = count_files_and_size(&source_dir, &config).unwrap().0;
+        assert_eq!(count, 2);
 
         // Test with include_hidden = false
         config.include_hidden = false;
-        let result = backup_directory(&source_dir, &config, false).unwrap();
-        assert_eq!(result.files_processed, 1);
-        assert!(!result.backup_path.join(".hidden.txt").exists());
-        assert!(result.backup_path.join("visible.txt").exists());
+        let count = count_files_and_size(&source_dir, &config).unwrap().0;
+        assert_eq!(count, 1);
     }
 
     #[test]
-    fn test_backup_empty_directory() {
+    fn test_scan_directory_counts_skipped() {
         let dir = tempdir().unwrap();
-        let source_dir = dir.path().join("empty_source");
+        let source_dir = dir.path().join("source");
         fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("keep.txt"), "keep").unwrap();
+        fs::write(source_dir.join("debug.log"), "log").unwrap();
+        File::create(source_dir.join(".hidden")).unwrap();
 
-        let config = default_config();
-        let result = backup_directory(&source_dir, &config, false).unwrap();
+        let config = Config {
+            include_hidden: false,
+            exclude_patterns: vec!["*.log".to_string()],
+            ..default_config()
+        };
+        let totals = scan_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(
+            totals,
+            ScanTotals {
+                files: 1,
+                size: 4,
+                skipped: 2,
+            }
+        );
+    }
 
-        assert!(result.backup_path.exists());
-        assert!(result.backup_path.is_dir());
-        assert_eq!(result.files_processed, 0);
-        assert_eq!(result.total_size, 0);
+    #[test]
+    fn test_count_files_and_size_single_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("single.txt");
+        File::create(&file_path).unwrap();
+
+        let config = default_config();
+        let count = count_files_and_size(&file_path, &config).unwrap().0;
+        assert_eq!(count, 1);
     }
 
     #[test]
-    fn test_backup_file_with_permissions_disabled() {
+    fn test_backup_directory_with_include_patterns() {
         let dir = tempdir().unwrap();
-        let source_path = dir.path().join("test.txt");
+        let source_dir = dir.path().join("project");
+        let src = source_dir.join("src");
+        let docs = source_dir.join("docs");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&docs).unwrap();
 
-        let mut file = File::create(&source_path).unwrap();
-        writeln!(file, "Content").unwrap();
+        fs::write(source_dir.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(source_dir.join("README.md"), "readme").unwrap();
+        fs::write(src.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(docs.join("guide.md"), "guide").unwrap();
 
         let mut config = default_config();
-        config.preserve_permissions = false;
+        config.include_patterns = vec!["*.rs".to_string(), "*.toml".to_string()];
+
+        // Counting must agree with copying
+        let (file_count, total_size) = count_files_and_size(&source_dir, &config).unwrap();
+        assert_eq!(file_count, 2);
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, file_count);
+        assert_eq!(result.total_size, total_size);
+        assert!(result.backup_path.join("Cargo.toml").exists());
+        assert!(result.backup_path.join("src").join("main.rs").exists());
+        assert!(!result.backup_path.join("README.md").exists());
+
+        // Directories with no matching files keep the source structure by default
+        let docs_backup = result.backup_path.join("docs");
+        assert_eq!(fs::read_dir(&docs_backup).unwrap().count(), 0);
+
+        // and are left out with `prune_empty_dirs`
+        config.prune_empty_dirs = true;
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert!(result.backup_path.join("src").join("main.rs").exists());
+        assert!(!result.backup_path.join("docs").exists());
+
+        // An invalid pattern fails the backup instead of matching nothing
+        config.include_patterns = vec!["[unclosed".to_string()];
+        assert!(matches!(
+            backup_directory(&source_dir, &config, false),
+            Err(QbakError::Validation { .. })
+        ));
+    }
 
-        let result = backup_file(&source_path, &config).unwrap();
-        assert!(result.backup_path.exists());
+    #[test]
+    fn test_backup_directory_exclude_wins_over_include() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("project");
+        let target = source_dir.join("target");
+        fs::create_dir_all(&target).unwrap();
 
-        // Should still create backup successfully
-        let backup_content = fs::read_to_string(&result.backup_path).unwrap();
-        assert_eq!(backup_content, "Content\n");
+        fs::write(source_dir.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(source_dir.join("build.rs"), "fn main() {}").unwrap();
+        fs::write(target.join("generated.rs"), "// generated").unwrap();
+
+        let mut config = default_config();
+        config.include_patterns = vec!["*.rs".to_string()];
+        config.exclude_patterns = vec!["build.rs".to_string(), "target".to_string()];
+
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_processed, 1);
+        assert!(result.backup_path.join("main.rs").exists());
+        assert!(!result.backup_path.join("build.rs").exists());
+        assert!(!result.backup_path.join("target").exists());
     }
 
     #[test]
-    fn test_backup_deeply_nested_directory() {
+    fn test_backup_to_output_dir() {
         let dir = tempdir().unwrap();
-        let source_dir = dir.path().join("source");
+        let source_path = dir.path().join("notes.txt");
+        fs::write(&source_path, "notes").unwrap();
+        let source_dir = dir.path().join("docs");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("readme.md"), "readme").unwrap();
 
-        // Create deeply nested structure
-        let deep_path = source_dir.join("a").join("b").join("c").join("d").join("e");
-        fs::create_dir_all(&deep_path).unwrap();
+        let output_dir = dir.path().join("backups").join("nested");
+        let mut config = default_config();
+        config.output_dir = Some(output_dir.clone());
 
-        // Add files at different levels
-        fs::write(source_dir.join("root.txt"), "root").unwrap();
-        fs::write(source_dir.join("a").join("a.txt"), "a").unwrap();
-        fs::write(source_dir.join("a").join("b").join("b.txt"), "b").unwrap();
-        fs::write(deep_path.join("deep.txt"), "deep").unwrap();
+        // Missing output directories are created
+        let result = backup_file(&source_path, &config).unwrap();
+        assert_eq!(result.backup_path.parent().unwrap(), output_dir);
+        assert_eq!(fs::read_to_string(&result.backup_path).unwrap(), "notes");
 
-        let config = default_config();
-        let result = backup_directory(&source_dir, &config, false).unwrap();
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.backup_path.parent().unwrap(), output_dir);
+        assert!(result.backup_path.join("readme.md").exists());
 
-        assert_eq!(result.files_processed, 4);
-        assert!(result.backup_path.join("root.txt").exists());
-        assert!(result.backup_path.join("a").join("a.txt").exists());
-        assert!(result
-            .backup_path
-            .join("a")
-            .join("b")
-            .join("b.txt")
-            .exists());
-        assert!(result
-            .backup_path
-            .join("a")
-            .join("b")
-            .join("c")
-            .join("d")
-            .join("e")
-            .join("deep.txt")
-            .exists());
+        // Nothing is written next to the sources
+        let siblings = fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(siblings, 3);
     }
 
     #[test]
-    fn test_backup_file_large_content() {
+    fn test_backup_into_own_source_rejected() {
         let dir = tempdir().unwrap();
-        let source_path = dir.path().join("large.txt");
+        let source = dir.path().join("src");
+        fs::create_dir_all(source.join("backups")).unwrap();
+        fs::write(source.join("main.rs"), "fn main() {}").unwrap();
 
-        // Create file with substantial content
-        let large_content = "x".repeat(10000);
-        fs::write(&source_path, &large_content).unwrap();
+        // `--output src/backups` would put the backup inside the tree being copied
+        let mut config = default_config();
+        config.output_dir = Some(source.join("backups"));
+        for archive in [ArchiveMode::None, ArchiveMode::Tar] {
+            config.archive = archive;
+            assert!(matches!(
+                backup_directory(&source, &config, false),
+                Err(QbakError::Validation { .. })
+            ));
+            assert!(matches!(
+                backup_directory_with_progress(&source, &config, false, true),
+                Err(QbakError::Validation { .. })
+            ));
+        }
+        assert_eq!(fs::read_dir(source.join("backups")).unwrap().count(), 0);
 
-        let config = default_config();
-        let result = backup_file(&source_path, &config).unwrap();
+        // Backing up a single file into a directory next to it is fine
+        let result = backup_file(&source.join("main.rs"), &config).unwrap();
+        assert_eq!(
+            result.backup_path.parent(),
+            Some(source.join("backups").as_path())
+        );
+    }
 
-        assert!(result.backup_path.exists());
-        assert_eq!(result.total_size, 10000);
+    #[test]
+    fn test_backup_to_dated_dir() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "notes").unwrap();
+        let first = dir.path().join("a").join("conf");
+        let second = dir.path().join("b").join("conf");
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(first.join("app.ini"), "a").unwrap();
+        fs::write(second.join("app.ini"), "b").unwrap();
+
+        let base = dir.path().join("backups");
+        let mut config = default_config();
+        config.dated_dir = Some(crate::naming::generate_dated_dir(&base, &config));
+        let dated = config.dated_dir.clone().unwrap();
+        assert_eq!(dated.parent(), Some(base.as_path()));
+
+        // Backups keep their original names; the directory is created on first use
+        let result = backup_file(&notes, &config).unwrap();
+        assert_eq!(result.backup_path, dated.join("notes.txt"));
+        let result = backup_directory(&first, &config, false).unwrap();
+        assert_eq!(result.backup_path, dated.join("conf"));
+
+        // A second target with the same name gets a counter
+        let result = backup_directory_with_progress(&second, &config, false, true).unwrap();
+        assert_eq!(result.backup_path, dated.join("conf-1"));
+        assert_eq!(
+            fs::read_to_string(dated.join("conf-1/app.ini")).unwrap(),
+            "b"
+        );
+        assert_eq!(fs::read_dir(&base).unwrap().count(), 1);
+
+        // Archives and compressed files add their extensions to the original name
+        config.archive = ArchiveMode::Tar;
+        config.compression = CompressionMode::Gzip;
+        assert_eq!(
+            generate_backup_name(&first, &config).unwrap(),
+            dated.join("conf.tar.gz")
+        );
+        assert_eq!(
+            generate_backup_name(&notes, &config).unwrap(),
+            dated.join("notes.txt.gz")
+        );
+    }
 
-        let backup_content = fs::read_to_string(&result.backup_path).unwrap();
-        assert_eq!(backup_content, large_content);
+    #[test]
+    fn test_backup_overwrite() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.txt");
+        let project = dir.path().join("project");
+        fs::write(&notes, "old").unwrap();
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("a.txt"), "old").unwrap();
+        fs::write(project.join("stale.txt"), "stale").unwrap();
+
+        // A date-only timestamp makes the second run produce the same names
+        let config = Config {
+            timestamp_format: "%Y-%m-%d".to_string(),
+            ..default_config()
+        };
+        let first_file = backup_file(&notes, &config).unwrap().backup_path;
+        let first_dir = backup_directory(&project, &config, false)
+            .unwrap()
+            .backup_path;
+
+        fs::write(&notes, "new").unwrap();
+        fs::write(project.join("a.txt"), "new").unwrap();
+        fs::remove_file(project.join("stale.txt")).unwrap();
+
+        let config = Config {
+            overwrite: true,
+            ..config
+        };
+        let result = backup_file(&notes, &config).unwrap();
+        assert_eq!(result.backup_path, first_file);
+        assert_eq!(fs::read_to_string(&first_file).unwrap(), "new");
+
+        // The directory is replaced as a whole, not merged into
+        let result = backup_directory(&project, &config, false).unwrap();
+        assert_eq!(result.backup_path, first_dir);
+        assert_eq!(fs::read_to_string(first_dir.join("a.txt")).unwrap(), "new");
+        assert!(!first_dir.join("stale.txt").exists());
+
+        // No counters, temp or set-aside copies are left behind
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 4, "{names:?}");
+        assert!(names.iter().all(|name| !name.starts_with(".qbak_")));
+
+        // A backup may replace a file with a directory of the same name and vice versa
+        fs::remove_file(&first_file).unwrap();
+        fs::create_dir(&first_file).unwrap();
+        backup_file(&notes, &config).unwrap();
+        assert_eq!(fs::read_to_string(&first_file).unwrap(), "new");
     }
 
     #[test]
-    fn test_backup_result_new() {
-        let source = PathBuf::from("/source/path");
-        let backup = PathBuf::from("/backup/path");
-
-        let result = BackupResult::new(source.clone(), backup.clone());
+    fn test_overwrite_never_targets_source() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "notes").unwrap();
 
-        assert_eq!(result.source_path, source);
-        assert_eq!(result.backup_path, backup);
-        assert_eq!(result.files_processed, 0);
-        assert_eq!(result.total_size, 0);
-        assert_eq!(result.duration.as_secs(), 0);
+        let config = Config {
+            overwrite: true,
+            ..default_config()
+        };
+        assert!(resolve_collision_with(&notes, &notes, &config).is_err());
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "notes");
     }
 
     #[test]
-    fn test_backup_result_summary_single_file() {
-        let mut result =
-            BackupResult::new(PathBuf::from("source.txt"), PathBuf::from("backup.txt"));
-        result.files_processed = 1;
-        result.total_size = 1024;
+    fn test_backup_directory_paths_agree() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("project");
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("README"), "readme").unwrap();
+        fs::write(source.join("src").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(source.join(".env"), "secret").unwrap();
+        fs::write(source.join("build.log"), "log").unwrap();
+
+        let config = Config {
+            include_hidden: false,
+            exclude_patterns: vec!["*.log".to_string()],
+            output_dir: Some(dir.path().join("plain")),
+            ..default_config()
+        };
+        let plain = backup_directory(&source, &config, true).unwrap();
+        let config = Config {
+            output_dir: Some(dir.path().join("progress")),
+            ..config
+        };
+        let with_progress = backup_directory_with_progress(&source, &config, false, true).unwrap();
 
-        let summary = result.summary();
-        assert!(summary.contains("Created backup:"));
-        assert!(summary.contains("1.0 KB"));
-        assert!(!summary.contains("files")); // Should not mention "files" for single file
+        assert_eq!(plain.files_processed, 2);
+        assert_eq!(plain.files_processed, with_progress.files_processed);
+        assert_eq!(plain.total_size, with_progress.total_size);
+        assert_eq!(plain.skipped, with_progress.skipped);
     }
 
     #[test]
-    fn test_backup_result_summary_multiple_files() {
-        let mut result =
-            BackupResult::new(PathBuf::from("source_dir"), PathBuf::from("backup_dir"));
-        result.files_processed = 5;
-        result.total_size = 2048;
+    fn test_backup_directory_single_pass() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("project");
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("README"), "readme").unwrap();
+        fs::write(source.join("src").join("main.rs"), "fn main() {}").unwrap();
+
+        let config = Config {
+            single_pass: true,
+            ..default_config()
+        };
+        assert!(uses_single_pass(&source, &config));
+        assert!(!uses_single_pass(&source, &default_config()));
 
-        let summary = result.summary();
-        assert!(summary.contains("Created backup:"));
-        assert!(summary.contains("5 files"));
-        assert!(summary.contains("2.0 KB"));
+        // Forcing progress draws a spinner instead of a bar; the backup is the same
+        let result = backup_directory_with_progress(&source, &config, true, false).unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert_eq!(result.total_size, 18);
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("src").join("main.rs")).unwrap(),
+            "fn main() {}"
+        );
+
+        let config = Config {
+            output_dir: Some(dir.path().join("plain")),
+            ..config
+        };
+        let plain = backup_directory(&source, &config, false).unwrap();
+        assert_eq!(plain.files_processed, 2);
+        assert_eq!(plain.total_size, 18);
     }
 
     #[test]
-    fn test_backup_directory_with_symlinks() {
+    fn test_backup_directory_is_staged() {
         let dir = tempdir().unwrap();
-        let source_dir = dir.path().join("source");
-        fs::create_dir_all(&source_dir).unwrap();
-
-        // Create a regular file
-        fs::write(source_dir.join("regular.txt"), "regular content").unwrap();
+        let source_dir = dir.path().join("site");
+        fs::create_dir_all(source_dir.join("assets")).unwrap();
+        fs::write(source_dir.join("index.html"), "<html>").unwrap();
+        fs::write(source_dir.join("assets").join("app.js"), "js").unwrap();
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::symlink;
+        let config = default_config();
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert!(result.backup_path.join("assets").join("app.js").exists());
 
-            // Create a symlink to the regular file
-            let target = source_dir.join("regular.txt");
-            let link = source_dir.join("link.txt");
-            symlink(&target, &link).unwrap();
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert!(result.backup_path.join("index.html").exists());
+
+        // The staging directories were renamed into place, not left behind
+        let leftovers = fs::read_dir(dir.path())
+            .unwrap()
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".qbak_temp_")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
 
-            // Test with follow_symlinks = true (default)
-            let mut config = default_config();
-            config.follow_symlinks = true;
+    #[test]
+    fn test_finalize_backup_refuses_existing_directory() {
+        let dir = tempdir().unwrap();
+        let staging = dir.path().join(".qbak_temp_staging");
+        let final_path = dir.path().join("final");
+        fs::create_dir(&staging).unwrap();
+        fs::create_dir(&final_path).unwrap();
+
+        let result = finalize_backup(&staging, &final_path, &default_config());
+        assert!(matches!(result, Err(QbakError::BackupExists { .. })));
+        assert!(staging.exists());
+    }
 
-            let result = backup_directory(&source_dir, &config, false).unwrap();
+    #[test]
+    #[cfg(unix)]
+    fn test_top_level_symlink_targets() {
+        use std::os::unix::fs::symlink;
 
-            // Should process both the regular file and the symlink target
-            assert_eq!(result.files_processed, 2);
-            assert!(result.backup_path.join("regular.txt").exists());
-            assert!(result.backup_path.join("link.txt").exists());
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("data.txt"), "data").unwrap();
+        fs::create_dir(dir.path().join("release")).unwrap();
+        fs::write(dir.path().join("release").join("app"), "app").unwrap();
+        let file_link = dir.path().join("current.txt");
+        let dir_link = dir.path().join("current");
+        symlink("data.txt", &file_link).unwrap();
+        symlink("release", &dir_link).unwrap();
+
+        // Followed: the link's target is copied under a name derived from the link
+        let config = default_config();
+        let result = backup_file(&file_link, &config).unwrap();
+        let name = result.backup_path.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("current-") && name.ends_with("-qbak.txt"));
+        assert!(result.backup_path.is_file() && !result.backup_path.is_symlink());
+        assert_eq!(fs::read_to_string(&result.backup_path).unwrap(), "data");
+
+        let result = backup_directory(&dir_link, &config, false).unwrap();
+        assert!(!result.backup_path.is_symlink());
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("app")).unwrap(),
+            "app"
+        );
 
-            // Clean up
-            fs::remove_dir_all(&result.backup_path).unwrap();
+        // Not followed: the link itself is recreated, even if its target is missing
+        let config = Config {
+            follow_symlinks: false,
+            compression: CompressionMode::Gzip,
+            ..default_config()
+        };
+        let result = backup_file(&file_link, &config).unwrap();
+        assert!(result.backup_path.is_symlink());
+        assert!(!result.backup_path.to_string_lossy().ends_with(".gz"));
+        assert_eq!(
+            fs::read_link(&result.backup_path).unwrap(),
+            Path::new("data.txt")
+        );
 
-            // Test with follow_symlinks = false
-            config.follow_symlinks = false;
-            let result = backup_directory(&source_dir, &config, false).unwrap();
+        let result = backup_directory_with_progress(&dir_link, &config, false, true).unwrap();
+        assert!(result.backup_path.is_symlink());
+        assert_eq!(
+            fs::read_link(&result.backup_path).unwrap(),
+            Path::new("release")
+        );
+        assert_eq!(result.files_processed, 1);
 
-            // Should still handle symlinks
-            assert!(result.backup_path.join("regular.txt").exists());
-        }
+        let broken = dir.path().join("broken");
+        symlink("nowhere", &broken).unwrap();
+        let result = backup_file(&broken, &config).unwrap();
+        assert_eq!(
+            fs::read_link(&result.backup_path).unwrap(),
+            Path::new("nowhere")
+        );
     }
 
     #[test]
-    fn test_cleanup_temp_files() {
-        let dir = tempdir().unwrap();
+    #[cfg(unix)]
+    fn test_dereference_top_level() {
+        use std::os::unix::fs::symlink;
 
-        // Create some temp files that look like qbak temp files
-        let temp1 = dir.path().join(".qbak_temp_12345_file1.txt");
-        let temp2 = dir.path().join(".qbak_temp_67890_file2.txt");
-        let normal = dir.path().join("normal_file.txt");
+        let dir = tempdir().unwrap();
+        let release = dir.path().join("releases").join("v5");
+        fs::create_dir_all(&release).unwrap();
+        fs::write(release.join("app"), "app").unwrap();
+        symlink("app", release.join("latest")).unwrap();
+        let current = dir.path().join("current");
+        symlink("releases/v5", &current).unwrap();
+        let file_link = dir.path().join("app-link");
+        symlink(release.join("app"), &file_link).unwrap();
+
+        let config = Config {
+            follow_symlinks: false,
+            dereference_top_level: true,
+            ..default_config()
+        };
 
-        File::create(&temp1).unwrap();
-        File::create(&temp2).unwrap();
-        File::create(&normal).unwrap();
+        // The target is followed, but the link inside it is kept as a link
+        let result = backup_directory(&current, &config, false).unwrap();
+        assert!(result.backup_path.is_dir() && !result.backup_path.is_symlink());
+        let name = result.backup_path.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("current-"));
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("app")).unwrap(),
+            "app"
+        );
+        assert_eq!(
+            fs::read_link(result.backup_path.join("latest")).unwrap(),
+            Path::new("app")
+        );
 
-        // Run cleanup
-        assert!(cleanup_temp_files(dir.path()).is_ok());
+        let result = backup_file(&file_link, &config).unwrap();
+        assert!(result.backup_path.is_file() && !result.backup_path.is_symlink());
 
-        // Temp files should be gone, normal file should remain
-        assert!(!temp1.exists());
-        assert!(!temp2.exists());
-        assert!(normal.exists());
+        // Archives behave the same way
+        let config = Config {
+            archive: ArchiveMode::Tar,
+            ..config
+        };
+        let result = backup_directory(&current, &config, false).unwrap();
+        let mut archive = tar::Archive::new(fs::File::open(&result.backup_path).unwrap());
+        let link = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.path().unwrap().ends_with("latest"))
+            .unwrap();
+        assert!(link.header().entry_type().is_symlink());
     }
 
     #[test]
-    fn test_cleanup_temp_files_nonexistent_dir() {
-        let nonexistent = Path::new("/nonexistent/directory");
-        let result = cleanup_temp_files(nonexistent);
-        assert!(result.is_ok()); // Should handle gracefully
+    fn test_finalize_backup_cross_device_error() {
+        let dir = tempdir().unwrap();
+        let cross_device = |_: &Path, _: &Path| Err(std::io::Error::from_raw_os_error(18));
+
+        // EXDEV is reported as CrossDevice; nothing appears at the final name
+        let temp_file = dir.path().join(".qbak_temp_file");
+        let final_file = dir.path().join("file-qbak.txt");
+        fs::write(&temp_file, "file data").unwrap();
+        let result = finalize_backup_with(&temp_file, &final_file, cross_device);
+        assert!(matches!(result, Err(QbakError::CrossDevice { .. })));
+        assert_eq!(result.unwrap_err().exit_code(), 3);
+        assert!(!final_file.exists());
+        assert!(temp_file.exists());
+
+        // Other rename errors are passed through untouched
+        let denied =
+            |_: &Path, _: &Path| Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        let result = finalize_backup_with(&temp_file, &final_file, denied);
+        assert!(matches!(result, Err(QbakError::Io(_))));
+        assert!(temp_file.exists());
+
+        finalize_backup_with(&temp_file, &final_file, |from, to| fs::rename(from, to)).unwrap();
+        assert_eq!(fs::read_to_string(&final_file).unwrap(), "file data");
     }
 
     #[test]
-    fn test_backup_file_zero_size() {
-        let dir = tempdir().unwrap();
-        let source_path = dir.path().join("empty.txt");
+    #[cfg(unix)]
+    fn test_backup_directory_dedup() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
-        // Create empty file
-        File::create(&source_path).unwrap();
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("assets");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        let license = "MIT License\n".repeat(100);
+        fs::write(source_dir.join("LICENSE"), &license).unwrap();
+        fs::write(source_dir.join("sub").join("LICENSE"), &license).unwrap();
+        fs::write(source_dir.join("script.sh"), &license).unwrap();
+        fs::set_permissions(
+            source_dir.join("script.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        fs::write(source_dir.join("other.txt"), "different").unwrap();
 
-        let config = default_config();
-        let result = backup_file(&source_path, &config).unwrap();
+        let config = Config {
+            dedup: true,
+            ..default_config()
+        };
+        // Both the plain and the progress-reporting copy paths deduplicate
+        let results = [
+            backup_directory(&source_dir, &config, false).unwrap(),
+            backup_directory_with_progress(&source_dir, &config, false, true).unwrap(),
+        ];
+        for result in results {
+            assert_eq!(result.files_processed, 4);
+            assert_eq!(result.files_linked, 1);
+            assert_eq!(result.bytes_deduplicated, license.len() as u64);
+
+            let ino = |path: PathBuf| fs::metadata(path).unwrap().ino();
+            let backup = &result.backup_path;
+            assert_eq!(
+                ino(backup.join("LICENSE")),
+                ino(backup.join("sub").join("LICENSE"))
+            );
+            // Same content but different permissions is copied
+            assert_ne!(ino(backup.join("LICENSE")), ino(backup.join("script.sh")));
+            assert_eq!(
+                fs::read_to_string(backup.join("sub").join("LICENSE")).unwrap(),
+                license
+            );
+        }
 
-        assert!(result.backup_path.exists());
-        assert_eq!(result.total_size, 0);
-        assert_eq!(result.files_processed, 1);
+        // Without dedup every file is copied
+        let result = backup_directory(&source_dir, &default_config(), false).unwrap();
+        assert_eq!(result.files_linked, 0);
+        assert_eq!(result.bytes_deduplicated, 0);
     }
 
     #[test]
-    fn test_count_files_recursive() {
-        let dir = tempdir().unwrap();
-        let source_dir = dir.path().join("source");
-        fs::create_dir_all(&source_dir).unwrap();
-
-        // Create test structure
-        File::create(source_dir.join("file1.txt")).unwrap();
-        File::create(source_dir.join("file2.txt")).unwrap();
+    #[cfg(unix)]
+    fn test_backup_directory_preserve_hardlinks() {
+        use std::os::unix::fs::MetadataExt;
 
-        let subdir = source_dir.join("subdir");
-        fs::create_dir_all(&subdir).unwrap();
-        File::create(subdir.join("file3.txt")).unwrap();
-        File::create(subdir.join("file4.txt")).unwrap();
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("store");
+        fs::create_dir_all(source_dir.join("by-name")).unwrap();
+        let blob = "object data\n".repeat(100);
+        fs::write(source_dir.join("blob"), &blob).unwrap();
+        fs::hard_link(source_dir.join("blob"), source_dir.join("by-name/readme")).unwrap();
+        fs::write(source_dir.join("copy"), &blob).unwrap();
+
+        let config = Config {
+            preserve_hardlinks: true,
+            ..default_config()
+        };
+        let results = [
+            backup_directory(&source_dir, &config, false).unwrap(),
+            backup_directory_with_progress(&source_dir, &config, false, true).unwrap(),
+        ];
+        for result in results {
+            assert_eq!(result.files_processed, 3);
+            assert_eq!(result.files_linked, 1);
+            assert_eq!(result.bytes_hardlinked, blob.len() as u64);
+            assert_eq!(result.bytes_deduplicated, 0);
+
+            let ino = |path: PathBuf| fs::metadata(path).unwrap().ino();
+            let backup = &result.backup_path;
+            assert_eq!(ino(backup.join("blob")), ino(backup.join("by-name/readme")));
+            assert_ne!(ino(backup.join("blob")), ino(source_dir.join("blob")));
+            // Identical content that isn't a hardlink in the source is still copied
+            assert_ne!(ino(backup.join("blob")), ino(backup.join("copy")));
+        }
 
-        let config = default_config();
-        let count = count_files_recursive(&source_dir, &config).unwrap();
-        assert_eq!(count, 4);
+        // Without the setting each link is copied separately
+        let result = backup_directory(&source_dir, &default_config(), false).unwrap();
+        assert_eq!(result.files_linked, 0);
+        assert_eq!(result.bytes_hardlinked, 0);
     }
 
     #[test]
-    fn test_count_files_recursive_with_hidden() {
+    fn test_backup_directory_incremental() {
         let dir = tempdir().unwrap();
-        let source_dir = dir.path().join("source");
-        fs::create_dir_all(&source_dir).unwrap();
+        let source_dir = dir.path().join("project");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("same.txt"), "unchanged").unwrap();
+        fs::write(source_dir.join("sub").join("changed.txt"), "new content").unwrap();
 
-        // Create normal and hidden files
-        File::create(source_dir.join("file1.txt")).unwrap();
-        File::create(source_dir.join(".hidden")).unwrap();
+        // Previous backup: hardlinking gives the unchanged file identical size and mtime
+        let previous = dir.path().join("project-20250101T120000-qbak");
+        fs::create_dir_all(previous.join("sub")).unwrap();
+        fs::hard_link(source_dir.join("same.txt"), previous.join("same.txt")).unwrap();
+        fs::write(previous.join("sub").join("changed.txt"), "old").unwrap();
 
-        // Test with include_hidden = true
         let mut config = default_config();
-        config.include_hidden = true;
-        let count = count_files_recursive(&source_dir, &config).unwrap();
-        assert_eq!(count, 2);
+        config.incremental = true;
 
-        // Test with include_hidden = false
-        config.include_hidden = false;
-        let count = count_files_recursive(&source_dir, &config).unwrap();
-        assert_eq!(count, 1);
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_processed, 2);
+        assert_eq!(result.files_linked, 1);
+        assert_eq!(result.files_copied, 1);
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("same.txt")).unwrap(),
+            "unchanged"
+        );
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("sub").join("changed.txt")).unwrap(),
+            "new content"
+        );
+
+        // The non-progress path links unchanged files the same way
+        let result = backup_directory(&source_dir, &config, false).unwrap();
+        assert_eq!(result.files_linked, 2);
+        assert_eq!(result.files_copied, 0);
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("sub").join("changed.txt")).unwrap(),
+            "new content"
+        );
+
+        // Without incremental mode every file is copied
+        let result =
+            backup_directory_with_progress(&source_dir, &default_config(), false, true).unwrap();
+        assert_eq!(result.files_copied, 2);
+        assert_eq!(result.files_linked, 0);
+        let result = backup_directory(&source_dir, &default_config(), false).unwrap();
+        assert_eq!(result.files_copied, 2);
+        assert_eq!(result.files_linked, 0);
     }
 
     #[test]
-    fn test_count_files_recursive_single_file() {
+    fn test_backup_directory_incremental_same_second_rewrite() {
+        use filetime::FileTime;
+
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("single.txt");
-        File::create(&file_path).unwrap();
+        let source_dir = dir.path().join("project");
+        fs::create_dir_all(&source_dir).unwrap();
+        let source_file = source_dir.join("data.txt");
+        fs::write(&source_file, "version 1").unwrap();
+
+        // Previous backup holds the first version with the same size and mtime
+        let previous = dir.path().join("project-20250101T120000-qbak");
+        fs::create_dir_all(&previous).unwrap();
+        fs::copy(&source_file, previous.join("data.txt")).unwrap();
+        let first_write = FileTime::from_unix_time(1_700_000_000, 100_000_000);
+        filetime::set_file_mtime(&source_file, first_write).unwrap();
+        filetime::set_file_mtime(previous.join("data.txt"), first_write).unwrap();
+
+        // Rewrite with the same length later within the same wall-clock second
+        fs::write(&source_file, "version 2").unwrap();
+        let second_write = FileTime::from_unix_time(1_700_000_000, 900_000_000);
+        filetime::set_file_mtime(&source_file, second_write).unwrap();
 
-        let config = default_config();
-        let count = count_files_recursive(&file_path, &config).unwrap();
-        assert_eq!(count, 1);
+        let mut config = default_config();
+        config.incremental = true;
+
+        let result = backup_directory_with_progress(&source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_copied, 1);
+        assert_eq!(result.files_linked, 0);
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("data.txt")).unwrap(),
+            "version 2"
+        );
     }
 
     #[test]
@@ -1384,15 +6235,15 @@ mod tests {
         assert!(context.is_interrupted());
 
         // Try to continue backup - should detect interrupt and fail
-        crate::signal::set_interrupt_flag(context.interrupt_flag());
-
-        let result = copy_directory_contents(
-            &source_dir,
-            &final_backup_path,
-            &config,
-            &mut BackupResult::new(source_dir.clone(), final_backup_path.clone()),
-            false,
-        );
+        let result = context.scope(|| {
+            copy_directory_contents(
+                &source_dir,
+                &final_backup_path,
+                &config,
+                &mut BackupResult::new(source_dir.clone(), final_backup_path.clone()),
+                &mut Traversal::default(),
+            )
+        });
 
         // Should fail with Interrupted error
         assert!(result.is_err());
@@ -1412,6 +6263,35 @@ mod tests {
         drop(guard);
     }
 
+    #[test]
+    fn test_concurrent_backups_on_separate_contexts() {
+        let dir = tempdir().unwrap();
+        let interrupted = crate::signal::BackupContext::new();
+        let running = crate::signal::BackupContext::new();
+        interrupted.set_interrupted(true);
+
+        let handles: Vec<_> = [(interrupted, "first"), (running, "second")]
+            .into_iter()
+            .map(|(context, name)| {
+                let source = dir.path().join(name);
+                fs::create_dir_all(&source).unwrap();
+                fs::write(source.join("data.txt"), name).unwrap();
+                std::thread::spawn(move || {
+                    context.scope(|| backup_directory(&source, &default_config(), false))
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        // Only the interrupted context's backup stops; the other completes normally
+        assert!(matches!(results[0], Err(QbakError::Interrupted)));
+        let completed = results[1].as_ref().unwrap();
+        assert_eq!(
+            fs::read_to_string(completed.backup_path.join("data.txt")).unwrap(),
+            "second"
+        );
+    }
+
     #[test]
     fn test_large_file_interrupt_and_cleanup() {
         use std::sync::atomic::{AtomicBool, Ordering};
@@ -1441,9 +6321,8 @@ mod tests {
         let backup_started = Arc::new(AtomicBool::new(false));
         let backup_result = Arc::new(Mutex::new(None));
 
-        crate::signal::set_interrupt_flag(context.interrupt_flag());
-
         // Clone variables for the backup thread
+        let context_clone = context.clone();
         let source_dir_clone = source_dir.clone();
         let config_clone = config.clone();
         let backup_started_clone = backup_started.clone();
@@ -1453,7 +6332,8 @@ mod tests {
         let backup_thread = thread::spawn(move || {
             backup_started_clone.store(true, Ordering::SeqCst);
 
-            let result = backup_directory(&source_dir_clone, &config_clone, false);
+            let result =
+                context_clone.scope(|| backup_directory(&source_dir_clone, &config_clone, false));
 
             // Store result for main thread to examine
             if let Ok(mut guard) = backup_result_clone.lock() {
@@ -1488,17 +6368,21 @@ mod tests {
             Some(Err(QbakError::Interrupted)) => {
                 // Backup was successfully interrupted
 
-                // Check if any partial backup exists
-                if final_backup_path.exists() {
-                    // If partial backup exists, verify it gets cleaned up
-                    let active_ops = crate::signal::get_active_operations();
-                    if !active_ops.is_empty() {
-                        // Simulate cleanup as main thread would do on interrupt
-                        crate::signal::cleanup_active_operations_with_mode(true);
+                // The partial copy lives in the staging directory, never at the final name
+                assert!(
+                    !final_backup_path.exists(),
+                    "Interrupted backup should not appear at its final name"
+                );
+
+                let active_ops = context.get_active_operations();
+                if !active_ops.is_empty() {
+                    // Simulate cleanup as main thread would do on interrupt
+                    context.cleanup_active_operations_with_mode(true);
 
-                        // Verify cleanup worked
+                    // Verify cleanup worked
+                    for path in &active_ops {
                         assert!(
-                            !final_backup_path.exists(),
+                            !path.exists(),
                             "Partial backup should be cleaned up after interrupt"
                         );
                     }
@@ -1552,10 +6436,295 @@ mod tests {
         context.set_interrupted(false);
     }
 
+    /// Source reader that fails with `error` once it reaches `fail_at`
+    struct FlakyReader {
+        data: std::io::Cursor<Vec<u8>>,
+        fail_at: Option<u64>,
+        error: std::io::ErrorKind,
+        on_fail: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+    }
+
+    impl std::io::Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.fail_at.is_some_and(|at| self.data.position() >= at) {
+                if let Some(ref flag) = self.on_fail {
+                    flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                }
+                return Err(self.error.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    impl std::io::Seek for FlakyReader {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.data.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_copy_watching_source() {
+        use std::io::Write;
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("app.log");
+        let dest = dir.path().join("copy.log");
+        let append = |text: &str| {
+            let mut file = fs::OpenOptions::new().append(true).open(&source).unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        // A write landing while the copy runs is detected, and the size is what was written
+        fs::write(&source, "first").unwrap();
+        let mut copies = 0;
+        let outcome = copy_watching_source(&source, &default_config(), || {
+            copies += 1;
+            copy_file_data(&source, &dest, &default_config(), None)?;
+            append(" more");
+            Ok(fs::metadata(&dest)?.len())
+        })
+        .unwrap();
+        assert_eq!(outcome, (5, true));
+        assert_eq!(copies, 1);
+
+        // With recopy_changed_files the file is copied again until it holds still
+        let config = Config {
+            recopy_changed_files: true,
+            ..default_config()
+        };
+        fs::write(&source, "first").unwrap();
+        let mut copies = 0;
+        let outcome = copy_watching_source(&source, &config, || {
+            copies += 1;
+            copy_file_data(&source, &dest, &config, None)?;
+            if copies == 1 {
+                append(" more");
+            }
+            Ok(fs::metadata(&dest)?.len())
+        })
+        .unwrap();
+        assert_eq!(outcome, (10, false));
+        assert_eq!(copies, 2);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "first more");
+
+        // A file that keeps changing is given up on after a few copies
+        let mut copies = 0;
+        let outcome = copy_watching_source(&source, &config, || {
+            copies += 1;
+            append(".");
+            Ok(0)
+        })
+        .unwrap();
+        assert!(outcome.1);
+        assert_eq!(copies, 1 + MAX_RECOPIES);
+    }
+
+    #[test]
+    fn test_copy_retries_transient_errors() {
+        use std::io::ErrorKind;
+
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("dest.bin");
+        let data: Vec<u8> = (0..40 * 1024).map(|i| (i % 251) as u8).collect();
+        let config = Config {
+            copy_buffer_size: 4 * 1024,
+            io_retries: 2,
+            io_retry_delay: Duration::ZERO,
+            ..default_config()
+        };
+        let flaky = |failures: usize, error: ErrorKind| {
+            let data = data.clone();
+            let mut opens = 0;
+            move |_: &Path| {
+                opens += 1;
+                Ok(FlakyReader {
+                    data: std::io::Cursor::new(data.clone()),
+                    // Each failing open gets a little further before failing again
+                    fail_at: (opens <= failures).then_some(opens as u64 * 10 * 1024),
+                    error,
+                    on_fail: None,
+                })
+            }
+        };
+
+        // Transient errors resume where the copy stopped
+        copy_file_with_retries(
+            Path::new("src"),
+            &dest,
+            &config,
+            None,
+            flaky(2, ErrorKind::TimedOut),
+        )
+        .unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), data);
+
+        // More failures than retries give up
+        let err = copy_file_with_retries(
+            Path::new("src"),
+            &dest,
+            &config,
+            None,
+            flaky(3, ErrorKind::TimedOut),
+        )
+        .unwrap_err();
+        assert!(matches!(err, QbakError::Io(ref e) if e.kind() == ErrorKind::TimedOut));
+
+        // Permanent errors aren't retried
+        let err = copy_file_with_retries(
+            Path::new("src"),
+            &dest,
+            &config,
+            None,
+            flaky(1, ErrorKind::PermissionDenied),
+        )
+        .unwrap_err();
+        assert!(matches!(err, QbakError::Io(ref e) if e.kind() == ErrorKind::PermissionDenied));
+
+        // An interrupt wins over a pending retry
+        let context = crate::signal::BackupContext::new();
+        let flag = context.interrupt_flag();
+        let config = Config {
+            io_retry_delay: Duration::from_secs(60),
+            ..config
+        };
+        let result = context.scope(|| {
+            copy_file_with_retries(Path::new("src"), &dest, &config, None, |_: &Path| {
+                Ok(FlakyReader {
+                    data: std::io::Cursor::new(data.clone()),
+                    fail_at: Some(4 * 1024),
+                    error: ErrorKind::TimedOut,
+                    on_fail: Some(flag.clone()),
+                })
+            })
+        });
+        assert!(matches!(result, Err(QbakError::Interrupted)));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_retry_delay_is_capped() {
+        let config = Config {
+            io_retry_delay: Duration::from_millis(200),
+            ..default_config()
+        };
+        assert_eq!(retry_delay(&config, 1), Duration::from_millis(200));
+        assert_eq!(retry_delay(&config, 3), Duration::from_millis(800));
+        assert_eq!(retry_delay(&config, 10), MAX_IO_RETRY_DELAY);
+        // Delays that would overflow don't panic
+        assert_eq!(retry_delay(&config, 40), MAX_IO_RETRY_DELAY);
+        let config = Config {
+            io_retry_delay: Duration::MAX,
+            ..config
+        };
+        assert_eq!(retry_delay(&config, 2), MAX_IO_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_keep_partial_on_interrupt() {
+        let dir = tempdir().unwrap();
+        let data = vec![7u8; 16 * 1024];
+        let config = Config {
+            copy_buffer_size: 4 * 1024,
+            io_retry_delay: Duration::from_secs(60),
+            ..default_config()
+        };
+        let context = crate::signal::BackupContext::new();
+        context.set_keep_partial(true);
+
+        // A copy interrupted after its first chunk leaves that chunk behind
+        let dest = dir.path().join("partial.bin");
+        let result = context.scope(|| {
+            copy_file_with_retries(Path::new("src"), &dest, &config, None, |_: &Path| {
+                Ok(FlakyReader {
+                    data: std::io::Cursor::new(data.clone()),
+                    fail_at: Some(4 * 1024),
+                    error: std::io::ErrorKind::TimedOut,
+                    on_fail: Some(context.interrupt_flag()),
+                })
+            })
+        });
+        assert!(matches!(result, Err(QbakError::Interrupted)));
+        assert_eq!(fs::read(&dest).unwrap().len(), 4 * 1024);
+
+        // The guard of an interrupted directory backup leaves its staging directory to
+        // cleanup, which keeps it
+        let staging = create_temp_backup_path(&dir.path().join("project-qbak")).unwrap();
+        context.scope(|| {
+            let _guard = crate::signal::create_backup_guard(staging.clone());
+            fs::create_dir(&staging).unwrap();
+            fs::write(staging.join("a.txt"), "a").unwrap();
+            context.set_interrupted(true);
+        });
+        assert!(context.cleanup_active_operations_with_mode(true));
+        assert_eq!(fs::read_to_string(staging.join("a.txt")).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_resume_directory_backup() {
+        use filetime::FileTime;
+
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("project");
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("done.txt"), "copied before the interrupt").unwrap();
+        fs::write(source_dir.join("stale.txt"), "new").unwrap();
+        fs::write(source_dir.join("sub").join("missing.txt"), "not copied yet").unwrap();
+
+        // Partial copy holding one finished file and one that changed since
+        let make_partial = || {
+            let partial = dir
+                .path()
+                .join(".qbak_temp_AbCdEf0123456789_project-20250101T120000-qbak");
+            fs::create_dir_all(&partial).unwrap();
+            fs::copy(source_dir.join("done.txt"), partial.join("done.txt")).unwrap();
+            fs::write(partial.join("stale.txt"), "old").unwrap();
+            let mtime = FileTime::from_unix_time(1_700_000_000, 0);
+            for name in ["done.txt", "stale.txt"] {
+                filetime::set_file_mtime(source_dir.join(name), mtime).unwrap();
+                filetime::set_file_mtime(partial.join(name), mtime).unwrap();
+            }
+            partial
+        };
+
+        let partial = make_partial();
+        let result =
+            resume_directory_backup(&partial, &source_dir, &default_config(), false, true).unwrap();
+        assert_eq!(
+            result.backup_path,
+            dir.path().join("project-20250101T120000-qbak")
+        );
+        assert!(!partial.exists());
+        // Size and mtime match for the stale file, so only a checksum catches it
+        assert_eq!(result.files_linked, 2);
+        assert_eq!(result.files_copied, 1);
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("sub").join("missing.txt")).unwrap(),
+            "not copied yet"
+        );
+
+        // With verify, files are matched by checksum
+        fs::remove_dir_all(&result.backup_path).unwrap();
+        let partial = make_partial();
+        let config = Config {
+            verify: true,
+            ..default_config()
+        };
+        let result = resume_directory_backup(&partial, &source_dir, &config, false, true).unwrap();
+        assert_eq!(result.files_linked, 1);
+        assert_eq!(result.files_copied, 2);
+        assert_eq!(result.files_verified, 3);
+        assert_eq!(
+            fs::read_to_string(result.backup_path.join("stale.txt")).unwrap(),
+            "new"
+        );
+
+        // Anything but a qbak staging directory is refused
+        assert!(resume_directory_backup(&source_dir, &source_dir, &config, false, true).is_err());
+    }
+
     #[test]
     fn test_interrupt_during_file_copy_with_chunks() {
-        use std::sync::atomic::{AtomicBool, Ordering};
-        use std::sync::Arc;
+        use std::sync::atomic::Ordering;
         use std::time::Duration;
 
         // Reset global state for test isolation
@@ -1571,8 +6740,8 @@ mod tests {
         fs::write(&source_file, content).unwrap();
 
         // Set up interrupt flag
-        let interrupt_flag = Arc::new(AtomicBool::new(false));
-        crate::signal::set_interrupt_flag(interrupt_flag.clone());
+        let context = crate::signal::BackupContext::new();
+        let interrupt_flag = context.interrupt_flag();
 
         // Start copying, then interrupt partway through
         let source_clone = source_file.clone();
@@ -1587,7 +6756,9 @@ mod tests {
                 interrupt_clone.store(true, Ordering::SeqCst);
             });
 
-            copy_file_with_interrupt_check(&source_clone, &dest_clone)
+            context.scope(|| {
+                copy_file_with_interrupt_check(&source_clone, &dest_clone, &default_config(), None)
+            })
         });
 
         let result = copy_thread.join().unwrap();
@@ -1,20 +1,378 @@
 use crate::error::QbakError;
-use crate::progress::ProgressConfig;
+use crate::progress::{ProgressConfig, ProgressFormat};
+use crate::utils::{format_size_in, parse_size, MAX_SPACE_BUFFER_PERCENT};
 use crate::Result;
 use configparser::ini::Ini;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub timestamp_format: String,
     pub backup_suffix: String,
+    pub collision_strategy: CollisionStrategy,
     pub preserve_permissions: bool,
+    /// Mode for created backup directories when not preserving permissions (Unix only)
+    pub dir_mode: Option<u32>,
+    /// Mode for created backup files when not preserving permissions (Unix only)
+    pub file_mode: Option<u32>,
     pub follow_symlinks: bool,
+    /// Follow symlinks passed as targets even when `follow_symlinks` is off
+    pub dereference_top_level: bool,
+    pub symlink_broken_policy: BrokenSymlinkPolicy,
     pub include_hidden: bool,
+    pub allow_outside_cwd: bool,
     pub max_filename_length: usize,
+    /// Shorten the stem of backup names longer than `max_filename_length` instead of failing
+    pub truncate_long_names: bool,
     pub max_symlink_depth: usize,
+    pub max_depth: Option<usize>,
+    pub max_depth_mode: DepthLimitMode,
+    pub max_file_size: Option<u64>,
+    pub newer_than: Option<SystemTime>,
+    pub prune_empty_dirs: bool,
+    /// Copy directories while walking them instead of counting them first; trades the
+    /// progress percentage and ETA for one walk instead of two. Network filesystems
+    /// always get a single pass.
+    pub single_pass: bool,
+    pub include_patterns: Vec<String>,
+    pub exclude_patterns: Vec<String>,
+    pub use_reflink: ReflinkMode,
+    pub compression: CompressionMode,
+    pub archive: ArchiveMode,
+    pub zip_compression_level: u32,
+    pub rate_limit: Option<u64>,
+    pub copy_buffer_size: usize,
+    /// How often a copy is resumed after a transient I/O error before giving up
+    pub io_retries: u32,
+    /// Wait before the first retry; doubled for each further retry
+    pub io_retry_delay: Duration,
+    /// Copy a file again if it changes while being copied, instead of only warning
+    pub recopy_changed_files: bool,
+    pub verify: bool,
+    pub incremental: bool,
+    pub dedup: bool,
+    pub preserve_hardlinks: bool,
+    pub preserve_acls: bool,
+    pub preserve_sparse: bool,
+    pub check_space: bool,
+    pub space_buffer_percent: u32,
+    pub space_buffer_min: u64,
+    pub min_free_space: u64,
+    pub fsync: bool,
+    /// Leave incomplete backups in place when interrupted instead of deleting them; takes
+    /// effect through `BackupContext::set_keep_partial`
+    pub keep_partial_on_interrupt: bool,
+    /// Directory backups are written into instead of next to their source
+    pub output_dir: Option<PathBuf>,
+    pub into_dir: Option<PathBuf>,
+    /// Directory named after the run's timestamp that backups keep their original names in
+    pub dated_dir: Option<PathBuf>,
+    /// Replace an existing backup with the same name instead of resolving the collision
+    pub overwrite: bool,
+    pub record_entries: bool,
+    pub write_manifest: bool,
+    pub manifest_checksums: bool,
+    pub size_units: SizeUnits,
+    pub show_bytes: bool,
+    pub keep: Option<usize>,
+    pub verbose: bool,
     pub progress: ProgressConfig,
+    pub sources: ConfigSources,
+}
+
+/// Whether to attempt copy-on-write clones (reflinks) when copying files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReflinkMode {
+    /// Try a reflink and fall back to a regular copy if unsupported
+    Auto,
+    /// Require a reflink and fail if the filesystem cannot provide one
+    Always,
+    /// Always perform a regular copy
+    Never,
+}
+
+impl ReflinkMode {
+    /// Parse a reflink mode from a config or command line value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "auto" => Some(ReflinkMode::Auto),
+            "always" | "true" | "yes" | "1" | "on" => Some(ReflinkMode::Always),
+            "never" | "false" | "no" | "0" | "off" => Some(ReflinkMode::Never),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReflinkMode::Auto => "auto",
+            ReflinkMode::Always => "always",
+            ReflinkMode::Never => "never",
+        }
+    }
+}
+
+/// How backup data is compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMode {
+    /// Store an exact copy
+    None,
+    /// Gzip single-file backups and tar archives, appending `.gz` to the backup name
+    Gzip,
+}
+
+impl CompressionMode {
+    /// Parse a compression mode from a config value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "none" | "off" | "false" => Some(CompressionMode::None),
+            "gzip" | "gz" => Some(CompressionMode::Gzip),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompressionMode::None => "none",
+            CompressionMode::Gzip => "gzip",
+        }
+    }
+}
+
+/// Whether directory backups are written as a tree or a single archive file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveMode {
+    /// Recreate the directory tree
+    None,
+    /// Stream the tree into a `.tar` file (`.tar.gz` with gzip compression)
+    Tar,
+    /// Write the tree into a `.zip` file
+    Zip,
+}
+
+impl ArchiveMode {
+    /// Parse an archive mode from a config value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "none" | "off" | "false" => Some(ArchiveMode::None),
+            "tar" => Some(ArchiveMode::Tar),
+            "zip" => Some(ArchiveMode::Zip),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveMode::None => "none",
+            ArchiveMode::Tar => "tar",
+            ArchiveMode::Zip => "zip",
+        }
+    }
+}
+
+/// Default chunk size for copying file data
+pub const DEFAULT_COPY_BUFFER_SIZE: usize = 64 * 1024;
+/// Smallest accepted `copy_buffer_size`
+pub const MIN_COPY_BUFFER_SIZE: usize = 4 * 1024;
+/// Largest accepted `copy_buffer_size`; interrupts are only noticed between chunks
+pub const MAX_COPY_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+/// Largest accepted `io_retries`
+pub const MAX_IO_RETRIES: u32 = 10;
+/// Longest wait before retrying a copy; caps `io_retry_delay_ms` and the doubled delays
+pub const MAX_IO_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Which units human-readable sizes are displayed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1024 labelled KB/MB/GB
+    Binary,
+    /// Powers of 1000 labelled kB/MB/GB, as used by disk vendors
+    Si,
+    /// Powers of 1024 labelled KiB/MiB/GiB
+    Iec,
+}
+
+impl SizeUnits {
+    /// Parse a size unit mode from a config value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "binary" => Some(SizeUnits::Binary),
+            "si" | "decimal" => Some(SizeUnits::Si),
+            "iec" => Some(SizeUnits::Iec),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SizeUnits::Binary => "binary",
+            SizeUnits::Si => "si",
+            SizeUnits::Iec => "iec",
+        }
+    }
+}
+
+/// Where the value of a setting came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// Detected from the terminal or environment at startup
+    Detected,
+    /// Set in the config file
+    ConfigFile,
+    /// Overridden by a command line flag
+    CliFlag,
+}
+
+impl ConfigSource {
+    /// Name used when displaying the source
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::Detected => "detected",
+            ConfigSource::ConfigFile => "config file",
+            ConfigSource::CliFlag => "CLI flag",
+        }
+    }
+}
+
+/// Where each non-default setting of a `Config` came from, keyed by config file name
+///
+/// Settings from the `[progress]` section are keyed as `progress.<name>`.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    sources: HashMap<String, ConfigSource>,
+}
+
+impl ConfigSources {
+    /// Sources of a fresh default config, where terminal properties are detected at startup
+    fn detected() -> Self {
+        let mut sources = Self::default();
+        for key in [
+            "progress.supports_ansi",
+            "progress.terminal_width",
+            "progress.is_interactive",
+        ] {
+            sources.set(key, ConfigSource::Detected);
+        }
+        sources
+    }
+
+    /// Record that `key` was set by `source`
+    pub fn set(&mut self, key: &str, source: ConfigSource) {
+        self.sources.insert(key.to_string(), source);
+    }
+
+    /// Where `key` came from; settings nobody set are defaults
+    pub fn get(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .copied()
+            .unwrap_or(ConfigSource::Default)
+    }
+}
+
+/// What to do when the generated backup name is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionStrategy {
+    /// Append a counter (`-1`, `-2`, ...) to the name
+    Counter,
+    /// Wait for the next second and generate a fresh timestamp
+    Timestamp,
+    /// Fail with `BackupExists`
+    Error,
+}
+
+impl CollisionStrategy {
+    /// Parse a collision strategy from a config value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "counter" => Some(CollisionStrategy::Counter),
+            "timestamp" => Some(CollisionStrategy::Timestamp),
+            "error" => Some(CollisionStrategy::Error),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the strategy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollisionStrategy::Counter => "counter",
+            CollisionStrategy::Timestamp => "timestamp",
+            CollisionStrategy::Error => "error",
+        }
+    }
+}
+
+/// What to do with a followed symlink whose target doesn't exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokenSymlinkPolicy {
+    /// Leave the link out of the backup silently
+    Skip,
+    /// Leave the link out of the backup and print a warning
+    Warn,
+    /// Recreate the dangling link in the backup
+    PreserveAsLink,
+    /// Fail the backup
+    Error,
+}
+
+impl BrokenSymlinkPolicy {
+    /// Parse a broken symlink policy from a config value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "skip" => Some(BrokenSymlinkPolicy::Skip),
+            "warn" => Some(BrokenSymlinkPolicy::Warn),
+            "preserve-as-link" => Some(BrokenSymlinkPolicy::PreserveAsLink),
+            "error" => Some(BrokenSymlinkPolicy::Error),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the policy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BrokenSymlinkPolicy::Skip => "skip",
+            BrokenSymlinkPolicy::Warn => "warn",
+            BrokenSymlinkPolicy::PreserveAsLink => "preserve-as-link",
+            BrokenSymlinkPolicy::Error => "error",
+        }
+    }
+}
+
+/// How directories at the `max_depth` limit are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthLimitMode {
+    /// Create the directory in the backup but leave it empty
+    Create,
+    /// Leave the directory out of the backup
+    Skip,
+}
+
+impl DepthLimitMode {
+    /// Parse a depth limit mode from a config value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "create" => Some(DepthLimitMode::Create),
+            "skip" => Some(DepthLimitMode::Skip),
+            _ => None,
+        }
+    }
+
+    /// Name used when displaying the mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepthLimitMode::Create => "create",
+            DepthLimitMode::Skip => "skip",
+        }
+    }
 }
 
 impl Default for Config {
@@ -22,26 +380,176 @@ impl Default for Config {
         Config {
             timestamp_format: "YYYYMMDDTHHMMSS".to_string(),
             backup_suffix: "qbak".to_string(),
+            collision_strategy: CollisionStrategy::Counter,
             preserve_permissions: true,
+            dir_mode: None,
+            file_mode: None,
             follow_symlinks: true,
+            dereference_top_level: false,
+            symlink_broken_policy: BrokenSymlinkPolicy::Warn,
             include_hidden: true,
+            allow_outside_cwd: true,
             max_filename_length: 255,
+            truncate_long_names: false,
             max_symlink_depth: 32, // Reasonable default to prevent excessive traversal
+            max_depth: None,
+            max_depth_mode: DepthLimitMode::Create,
+            max_file_size: None,
+            newer_than: None,
+            prune_empty_dirs: false,
+            single_pass: false,
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            use_reflink: ReflinkMode::Auto,
+            compression: CompressionMode::None,
+            archive: ArchiveMode::None,
+            zip_compression_level: 6,
+            rate_limit: None,
+            copy_buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+            io_retries: 3,
+            io_retry_delay: Duration::from_millis(200),
+            recopy_changed_files: false,
+            verify: false,
+            incremental: false,
+            dedup: false,
+            preserve_hardlinks: false,
+            preserve_acls: false,
+            preserve_sparse: true,
+            check_space: true,
+            space_buffer_percent: 10,
+            space_buffer_min: 0,
+            min_free_space: 0,
+            fsync: true,
+            keep_partial_on_interrupt: false,
+            output_dir: None,
+            into_dir: None,
+            dated_dir: None,
+            overwrite: false,
+            record_entries: false,
+            write_manifest: false,
+            manifest_checksums: false,
+            size_units: SizeUnits::Binary,
+            show_bytes: false,
+            keep: None,
+            verbose: false,
             progress: ProgressConfig::auto_detect(),
+            sources: ConfigSources::detected(),
         }
     }
 }
 
+/// Shortest `max_filename_length` accepted; leaves room for the timestamp and suffix
+pub const MIN_FILENAME_LENGTH: usize = 32;
+
+/// Longest `max_filename_length` accepted
+pub const MAX_FILENAME_LENGTH: usize = 1024;
+
+impl Config {
+    /// Check settings that would otherwise only fail, or misbehave, once a backup runs
+    ///
+    /// Returns a configuration error listing every problem found.
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(QbakError::config(problems.join("; ")))
+        }
+    }
+
+    /// Describe each invalid setting, in config file order
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !crate::naming::is_supported_timestamp_format(&self.timestamp_format) {
+            problems.push(format!(
+                "timestamp_format '{}' is not supported (expected YYYYMMDDTHHMMSS or a strftime pattern with a date, e.g. %Y-%m-%d_%H%M%S)",
+                self.timestamp_format
+            ));
+        }
+
+        let suffix = &self.backup_suffix;
+        if suffix.trim().is_empty() {
+            problems.push("backup_suffix must not be empty".to_string());
+        } else if suffix.contains(['/', '\\']) || suffix.starts_with('.') {
+            problems.push(format!("backup_suffix '{suffix}' must not contain a path"));
+        } else if let Err(e) = crate::naming::validate_filesystem_chars(suffix) {
+            problems.push(format!(
+                "backup_suffix '{suffix}' is not filesystem-safe: {e}"
+            ));
+        }
+
+        if !(MIN_FILENAME_LENGTH..=MAX_FILENAME_LENGTH).contains(&self.max_filename_length) {
+            problems.push(format!(
+                "max_filename_length {} is out of range ({MIN_FILENAME_LENGTH}-{MAX_FILENAME_LENGTH})",
+                self.max_filename_length
+            ));
+        }
+
+        problems
+    }
+}
+
 /// Get default configuration
 pub fn default_config() -> Config {
     Config::default()
 }
 
 /// Load configuration from file, falling back to defaults
+///
+/// A file named by `QBAK_CONFIG` must exist; the default location is optional. Settings that
+/// parse but fail `Config::validate` are reported as a configuration error.
 pub fn load_config() -> Result<Config> {
-    let config_path = get_config_path()?;
+    let config = read_config()?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Validate the config file and print a pass/fail report
+///
+/// Returns whether the configuration is valid. Errors only if the config location can't be
+/// determined.
+pub fn check_config() -> Result<bool> {
+    let (config_path, source) = get_config_path()?;
+    if config_path.exists() {
+        println!("Config file: {} (via {source})", config_path.display());
+    } else {
+        println!(
+            "Config file: {} (not found, via {source}, using defaults)",
+            config_path.display()
+        );
+    }
+
+    let problems = match read_config() {
+        Ok(config) => config.problems(),
+        Err(e) => vec![e.to_string()],
+    };
+    if problems.is_empty() {
+        println!("Config OK");
+        return Ok(true);
+    }
+    for problem in &problems {
+        println!("  - {problem}");
+    }
+    let count = problems.len();
+    println!(
+        "Config invalid: {count} problem{}",
+        if count == 1 { "" } else { "s" }
+    );
+    Ok(false)
+}
+
+/// Parse the config file without validating the values against each other
+fn read_config() -> Result<Config> {
+    let (config_path, source) = get_config_path()?;
 
     if !config_path.exists() {
+        if source == CONFIG_PATH_ENV {
+            return Err(QbakError::config(format!(
+                "Config file from {CONFIG_PATH_ENV} not found: {}",
+                config_path.display()
+            )));
+        }
         return Ok(default_config());
     }
 
@@ -51,6 +559,20 @@ pub fn load_config() -> Result<Config> {
 
     let mut config = default_config();
 
+    // Remember which settings the file provides, for `--dump-config`
+    if let Some(sections) = conf.get_map() {
+        for (section, keys) in sections {
+            for key in keys.keys() {
+                let key = if section == "qbak" {
+                    key.clone()
+                } else {
+                    format!("{section}.{key}")
+                };
+                config.sources.set(&key, ConfigSource::ConfigFile);
+            }
+        }
+    }
+
     // Load string values
     if let Some(value) = conf.get("qbak", "timestamp_format") {
         config.timestamp_format = value;
@@ -58,17 +580,139 @@ pub fn load_config() -> Result<Config> {
     if let Some(value) = conf.get("qbak", "backup_suffix") {
         config.backup_suffix = value;
     }
+    if let Some(value) = conf.get("qbak", "output_dir") {
+        config.output_dir = Some(resolve_home_relative(&value)?);
+    }
+    if let Some(value) = conf.get("qbak", "collision_strategy") {
+        config.collision_strategy = CollisionStrategy::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid collision_strategy: {value}")))?;
+    }
 
     // Load boolean values
     if let Some(value) = conf.get("qbak", "preserve_permissions") {
         config.preserve_permissions = parse_bool(&value).unwrap_or(config.preserve_permissions);
     }
+    if let Some(value) = conf.get("qbak", "dir_mode") {
+        config.dir_mode = Some(parse_mode(&value).ok_or_else(|| {
+            QbakError::config(format!(
+                "Invalid dir_mode: {value} (expected octal, e.g. 0750)"
+            ))
+        })?);
+    }
+    if let Some(value) = conf.get("qbak", "file_mode") {
+        config.file_mode = Some(parse_mode(&value).ok_or_else(|| {
+            QbakError::config(format!(
+                "Invalid file_mode: {value} (expected octal, e.g. 0640)"
+            ))
+        })?);
+    }
     if let Some(value) = conf.get("qbak", "follow_symlinks") {
         config.follow_symlinks = parse_bool(&value).unwrap_or(config.follow_symlinks);
     }
+    if let Some(value) = conf.get("qbak", "dereference_top_level") {
+        config.dereference_top_level = parse_bool(&value).unwrap_or(config.dereference_top_level);
+    }
     if let Some(value) = conf.get("qbak", "include_hidden") {
         config.include_hidden = parse_bool(&value).unwrap_or(config.include_hidden);
     }
+    if let Some(value) = conf.get("qbak", "allow_outside_cwd") {
+        config.allow_outside_cwd = parse_bool(&value).unwrap_or(config.allow_outside_cwd);
+    }
+    if let Some(value) = conf.get("qbak", "verify") {
+        config.verify = parse_bool(&value).unwrap_or(config.verify);
+    }
+    if let Some(value) = conf.get("qbak", "preserve_acls") {
+        config.preserve_acls = parse_bool(&value).unwrap_or(config.preserve_acls);
+    }
+    if let Some(value) = conf.get("qbak", "preserve_sparse") {
+        config.preserve_sparse = parse_bool(&value).unwrap_or(config.preserve_sparse);
+    }
+    if let Some(value) = conf.get("qbak", "check_space") {
+        config.check_space = parse_bool(&value).unwrap_or(config.check_space);
+    }
+    if let Some(value) = conf.get("qbak", "space_buffer_percent") {
+        let percent: u32 = value
+            .parse()
+            .map_err(|_| QbakError::config(format!("Invalid space_buffer_percent: {value}")))?;
+        config.space_buffer_percent = percent.min(MAX_SPACE_BUFFER_PERCENT);
+    }
+    if let Some(value) = conf.get("qbak", "space_buffer_min") {
+        config.space_buffer_min = parse_size(&value)
+            .map_err(|_| QbakError::config(format!("Invalid space_buffer_min: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "min_free_space") {
+        config.min_free_space = parse_size(&value)
+            .map_err(|_| QbakError::config(format!("Invalid min_free_space: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "prune_empty_dirs") {
+        config.prune_empty_dirs = parse_bool(&value).unwrap_or(config.prune_empty_dirs);
+    }
+    if let Some(value) = conf.get("qbak", "single_pass") {
+        config.single_pass = parse_bool(&value).unwrap_or(config.single_pass);
+    }
+    if let Some(value) = conf.get("qbak", "incremental") {
+        config.incremental = parse_bool(&value).unwrap_or(config.incremental);
+    }
+    if let Some(value) = conf.get("qbak", "dedup") {
+        config.dedup = parse_bool(&value).unwrap_or(config.dedup);
+    }
+    if let Some(value) = conf.get("qbak", "preserve_hardlinks") {
+        config.preserve_hardlinks = parse_bool(&value).unwrap_or(config.preserve_hardlinks);
+    }
+    if let Some(value) = conf.get("qbak", "fsync") {
+        config.fsync = parse_bool(&value).unwrap_or(config.fsync);
+    }
+    if let Some(value) = conf.get("qbak", "keep_partial_on_interrupt") {
+        config.keep_partial_on_interrupt =
+            parse_bool(&value).unwrap_or(config.keep_partial_on_interrupt);
+    }
+    if let Some(value) = conf.get("qbak", "record_entries") {
+        config.record_entries = parse_bool(&value).unwrap_or(config.record_entries);
+    }
+    if let Some(value) = conf.get("qbak", "write_manifest") {
+        config.write_manifest = parse_bool(&value).unwrap_or(config.write_manifest);
+    }
+    if let Some(value) = conf.get("qbak", "manifest_checksums") {
+        config.manifest_checksums = parse_bool(&value).unwrap_or(config.manifest_checksums);
+    }
+
+    // Load enum values
+    if let Some(value) = conf.get("qbak", "use_reflink") {
+        config.use_reflink = ReflinkMode::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid use_reflink: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "compression") {
+        config.compression = CompressionMode::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid compression: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "archive") {
+        config.archive = ArchiveMode::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid archive: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "size_units") {
+        config.size_units = SizeUnits::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid size_units: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "zip_compression_level") {
+        config.zip_compression_level =
+            value
+                .parse()
+                .ok()
+                .filter(|level| *level <= 9)
+                .ok_or_else(|| {
+                    QbakError::config(format!(
+                        "Invalid zip_compression_level: {value} (expected 0-9)"
+                    ))
+                })?;
+    }
+    if let Some(value) = conf.get("qbak", "max_depth_mode") {
+        config.max_depth_mode = DepthLimitMode::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid max_depth_mode: {value}")))?;
+    }
+    if let Some(value) = conf.get("qbak", "symlink_broken_policy") {
+        config.symlink_broken_policy = BrokenSymlinkPolicy::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid symlink_broken_policy: {value}")))?;
+    }
 
     // Load numeric values
     if let Some(value) = conf.get("qbak", "max_filename_length") {
@@ -76,11 +720,80 @@ pub fn load_config() -> Result<Config> {
             .parse()
             .map_err(|_| QbakError::config(format!("Invalid max_filename_length: {value}")))?;
     }
+    if let Some(value) = conf.get("qbak", "truncate_long_names") {
+        config.truncate_long_names = parse_bool(&value).unwrap_or(config.truncate_long_names);
+    }
     if let Some(value) = conf.get("qbak", "max_symlink_depth") {
         config.max_symlink_depth = value
             .parse()
             .map_err(|_| QbakError::config(format!("Invalid max_symlink_depth: {value}")))?;
     }
+    if let Some(value) = conf.get("qbak", "max_file_size") {
+        config.max_file_size = match value.to_lowercase().as_str() {
+            "" | "unlimited" | "none" => None,
+            _ => Some(
+                parse_size(&value)
+                    .map_err(|_| QbakError::config(format!("Invalid max_file_size: {value}")))?,
+            ),
+        };
+    }
+    if let Some(value) = conf.get("qbak", "rate_limit") {
+        config.rate_limit = match value.to_lowercase().as_str() {
+            "" | "unlimited" | "none" => None,
+            _ => Some(
+                parse_size(&value)
+                    .map_err(|_| QbakError::config(format!("Invalid rate_limit: {value}")))?,
+            ),
+        };
+    }
+    if let Some(value) = conf.get("qbak", "copy_buffer_size") {
+        config.copy_buffer_size = parse_size(&value)
+            .ok()
+            .and_then(|size| usize::try_from(size).ok())
+            .filter(|size| (MIN_COPY_BUFFER_SIZE..=MAX_COPY_BUFFER_SIZE).contains(size))
+            .ok_or_else(|| {
+                QbakError::config(format!(
+                    "Invalid copy_buffer_size: {value} (expected 4KiB-16MiB)"
+                ))
+            })?;
+    }
+    if let Some(value) = conf.get("qbak", "io_retries") {
+        config.io_retries = value
+            .parse()
+            .ok()
+            .filter(|retries| *retries <= MAX_IO_RETRIES)
+            .ok_or_else(|| {
+                QbakError::config(format!(
+                    "Invalid io_retries: {value} (expected 0-{MAX_IO_RETRIES})"
+                ))
+            })?;
+    }
+    if let Some(value) = conf.get("qbak", "io_retry_delay_ms") {
+        config.io_retry_delay = value
+            .parse()
+            .ok()
+            .map(Duration::from_millis)
+            .filter(|delay| *delay <= MAX_IO_RETRY_DELAY)
+            .ok_or_else(|| {
+                QbakError::config(format!(
+                    "Invalid io_retry_delay_ms: {value} (expected 0-{})",
+                    MAX_IO_RETRY_DELAY.as_millis()
+                ))
+            })?;
+    }
+    if let Some(value) = conf.get("qbak", "recopy_changed_files") {
+        config.recopy_changed_files = parse_bool(&value).unwrap_or(config.recopy_changed_files);
+    }
+    if let Some(value) = conf.get("qbak", "max_depth") {
+        config.max_depth = match value.to_lowercase().as_str() {
+            "" | "unlimited" | "none" => None,
+            _ => Some(
+                value
+                    .parse()
+                    .map_err(|_| QbakError::config(format!("Invalid max_depth: {value}")))?,
+            ),
+        };
+    }
 
     // Load progress configuration
     if let Some(value) = conf.get("progress", "enabled") {
@@ -106,32 +819,92 @@ pub fn load_config() -> Result<Config> {
             .map_err(|_| QbakError::config(format!("Invalid min_duration_seconds: {value}")))?;
         config.progress.min_duration_threshold = Duration::from_secs(seconds);
     }
+    if let Some(value) = conf.get("progress", "scan_interval") {
+        config.progress.scan_update_interval = value
+            .parse()
+            .ok()
+            .filter(|interval| *interval > 0)
+            .ok_or_else(|| QbakError::config(format!("Invalid scan_interval: {value}")))?;
+    }
+    if let Some(value) = conf.get("progress", "format") {
+        config.progress.format = ProgressFormat::parse(&value)
+            .ok_or_else(|| QbakError::config(format!("Invalid progress format: {value}")))?;
+    }
 
     Ok(config)
 }
 
-/// Get the configuration file path for the current platform
-fn get_config_path() -> Result<PathBuf> {
+/// Environment variable naming a config file, overriding the platform default location
+pub const CONFIG_PATH_ENV: &str = "QBAK_CONFIG";
+
+/// Get the configuration file path for the current platform, along with the environment
+/// variable it was derived from
+fn get_config_path() -> Result<(PathBuf, &'static str)> {
+    if let Some(path) = std::env::var_os(CONFIG_PATH_ENV).filter(|path| !path.is_empty()) {
+        return Ok((PathBuf::from(path), CONFIG_PATH_ENV));
+    }
+
     #[cfg(target_os = "windows")]
     {
         if let Some(appdata) = std::env::var_os("APPDATA") {
-            return Ok(PathBuf::from(appdata).join("qbak").join("config.ini"));
+            return Ok((
+                PathBuf::from(appdata).join("qbak").join("config.ini"),
+                "APPDATA",
+            ));
         }
     }
 
     // Unix-like systems (Linux, macOS, etc.)
     if let Some(config_dir) = std::env::var_os("XDG_CONFIG_HOME") {
-        Ok(PathBuf::from(config_dir).join("qbak").join("config.ini"))
+        Ok((
+            PathBuf::from(config_dir).join("qbak").join("config.ini"),
+            "XDG_CONFIG_HOME",
+        ))
     } else if let Some(home) = std::env::var_os("HOME") {
-        Ok(PathBuf::from(home)
-            .join(".config")
-            .join("qbak")
-            .join("config.ini"))
+        Ok((
+            PathBuf::from(home)
+                .join(".config")
+                .join("qbak")
+                .join("config.ini"),
+            "HOME",
+        ))
     } else {
         Err(QbakError::config("Could not determine config directory"))
     }
 }
 
+/// Resolve a path from the config file: `~` and relative paths are taken relative to the
+/// home directory, so they don't depend on where qbak is run from
+fn resolve_home_relative(value: &str) -> Result<PathBuf> {
+    let path = Path::new(value.trim());
+    if path.as_os_str().is_empty() {
+        return Err(QbakError::config("Invalid output_dir: must not be empty"));
+    }
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            QbakError::config(format!(
+                "Invalid output_dir: {value} is relative and the home directory is unknown"
+            ))
+        })?;
+    let relative = path.strip_prefix("~").unwrap_or(path);
+    Ok(home.join(relative))
+}
+
+/// Parse an octal Unix mode such as `0750` or `0o750`
+fn parse_mode(value: &str) -> Option<u32> {
+    let digits = value.trim();
+    let digits = digits.strip_prefix("0o").unwrap_or(digits);
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+}
+
 /// Parse a boolean value from INI string
 fn parse_bool(value: &str) -> Option<bool> {
     match value.to_lowercase().as_str() {
@@ -144,27 +917,152 @@ fn parse_bool(value: &str) -> Option<bool> {
 /// Create a sample configuration file
 pub fn create_sample_config() -> String {
     r#"[qbak]
-# Timestamp format for backup names (ISO-8601 basic format)
+# Timestamp format for backup names: YYYYMMDDTHHMMSS (ISO-8601 basic format) or a
+# strftime pattern with at least a date, e.g. %Y-%m-%d_%H%M%S
 timestamp_format = YYYYMMDDTHHMMSS
 
 # Suffix added to backup filenames  
 backup_suffix = qbak
 
+# What to do when a backup name is taken (counter = append -1, -2, ...;
+# timestamp = wait for the next second; error = fail)
+collision_strategy = counter
+
+# Directory to write all backups into instead of next to the source; relative paths
+# and ~ are resolved against your home directory (--output overrides it)
+# output_dir = ~/backups
+
 # Preserve original file permissions and timestamps (true/false)
 preserve_permissions = true
 
+# Unix modes (octal) for backup directories and files when preserve_permissions is
+# false; unset means the process umask decides
+# dir_mode = 0750
+# file_mode = 0640
+
+# Preserve POSIX ACLs on Linux (requires the `acl` build feature; true/false)
+preserve_acls = false
+
+# Keep holes in sparse files instead of writing out zeros (true/false)
+preserve_sparse = true
+
 # Follow symbolic links (copy target) or preserve as symlinks
 follow_symlinks = true
 
+# Always follow symlinks given as targets on the command line, even with
+# follow_symlinks = false, which then still applies inside directories (like cp -H)
+dereference_top_level = false
+
+# What to do with followed symlinks whose target is missing (skip/warn/preserve-as-link/error)
+symlink_broken_policy = warn
+
 # Include hidden files when backing up directories  
 include_hidden = true
 
-# Maximum filename length before showing error
+# Allow backing up sources that resolve outside the current directory (true/false)
+allow_outside_cwd = true
+
+# Maximum filename length before showing error (32-1024)
 max_filename_length = 255
 
+# Shorten names that would exceed max_filename_length, keeping the extension and adding
+# a short hash of the original name, instead of failing (true/false)
+truncate_long_names = false
+
 # Maximum symlink depth to follow (security feature)
 max_symlink_depth = 32
 
+# Maximum directory depth to descend into (0 = only the immediate children; unlimited by default)
+# max_depth = 2
+
+# What to do with directories at the max_depth limit (create = empty directory, skip = leave out)
+max_depth_mode = create
+
+# Skip files larger than this in directory backups (e.g. 100M, 2G; unlimited by default)
+# max_file_size = 100M
+
+# Remove directories left empty by --include or --newer-than from directory backups (true/false)
+prune_empty_dirs = false
+
+# Copy directories while walking them instead of counting them first, halving directory
+# listings at the cost of the progress percentage and ETA; always on for network
+# filesystems (true/false)
+single_pass = false
+
+# Use copy-on-write clones on btrfs/XFS (auto/always/never)
+use_reflink = auto
+
+# Compress single-file backups and tar archives (none/gzip); gzip appends .gz to the backup name
+compression = none
+
+# Write directory backups as a single archive file instead of a tree (none/tar/zip)
+archive = none
+
+# Deflate level for zip archives (0 stores files uncompressed, 1-9)
+zip_compression_level = 6
+
+# Limit copy throughput in bytes per second (e.g. 10M; unlimited by default)
+# rate_limit = 10M
+
+# Chunk size for copying file data (4KiB-16MiB); larger chunks can speed up copies on fast
+# disks, but interrupts are only noticed between chunks
+copy_buffer_size = 64KiB
+
+# Retry reads and writes that fail with a transient error (EIO, timeouts) this many times
+# per file (0-10), waiting io_retry_delay_ms (0-5000) before the first retry and twice as
+# long before each further one, at most 5 seconds; missing files and permission errors are
+# never retried
+io_retries = 3
+io_retry_delay_ms = 200
+
+# Copy a file again (up to 3 times) if it changes while it is being backed up, instead of
+# only warning that its backup may not match it (true/false)
+recopy_changed_files = false
+
+# Verify backups against the source with a SHA-256 checksum (true/false)
+verify = false
+
+# Hardlink unchanged files from the newest previous directory backup (true/false)
+incremental = false
+
+# Hardlink identical files within a directory backup instead of copying each (true/false)
+dedup = false
+
+# Keep files that are hardlinked together in the source hardlinked in directory backups,
+# instead of copying each link separately (true/false)
+preserve_hardlinks = false
+
+# Check for enough free disk space before starting a backup (true/false)
+check_space = true
+
+# Extra free space required on top of the backup size, in percent (0-100)
+space_buffer_percent = 10
+
+# Minimum safety buffer regardless of backup size (e.g. 100M)
+space_buffer_min = 0
+
+# Free space that must remain on the destination after the backup (e.g. 1G; 0 = no floor)
+min_free_space = 0
+
+# Sync backups to disk before reporting success; false is faster but less crash-safe (true/false)
+fsync = true
+
+# Keep incomplete backups when interrupted instead of deleting them, so they can be
+# inspected or removed by hand (true/false)
+keep_partial_on_interrupt = false
+
+# Record the outcome of every file in directory backup results (true/false)
+record_entries = false
+
+# Write a <backup>.qbak.json manifest next to each backup (true/false)
+write_manifest = false
+
+# Include SHA-256 checksums of the backed up files in the manifest (true/false)
+manifest_checksums = false
+
+# Units for displayed sizes (binary = 1024-based KB/MB, si = 1000-based kB/MB, iec = KiB/MiB)
+size_units = binary
+
 [progress]
 # Enable/disable progress indication (can be overridden by command line flags)
 enabled = true
@@ -176,65 +1074,176 @@ force_enabled = false
 min_files = 50
 min_size_mb = 10
 min_duration_seconds = 2
+
+# Progress output format: bar (interactive bar) or json (NDJSON events on stderr)
+format = bar
+
+# Update the scanning spinner every N files (1 or more)
+scan_interval = 10
 "#
     .to_string()
 }
 
 /// Display the current configuration in a user-friendly format
 pub fn dump_config(config: &Config) -> Result<()> {
-    let config_path = get_config_path()?;
+    let (config_path, source) = get_config_path()?;
 
     println!("qbak Configuration");
     println!("==================");
     println!();
 
-    // Show config file path and status
+    // Show config file path, where it came from and status
     if config_path.exists() {
         let path_display = config_path.display();
-        println!("Config file: {path_display} (found)");
+        println!("Config file: {path_display} (found, via {source})");
     } else {
         println!(
-            "Config file: {} (not found, using defaults)",
+            "Config file: {} (not found, via {source}, using defaults)",
             config_path.display()
         );
     }
     println!();
 
-    // Show current settings
+    // Show current settings, each with where its value came from
     println!("Current Settings:");
     println!("----------------");
-    let timestamp_format = &config.timestamp_format;
-    let backup_suffix = &config.backup_suffix;
-    let preserve_permissions = config.preserve_permissions;
-    let follow_symlinks = config.follow_symlinks;
-    let include_hidden = config.include_hidden;
-    let max_filename_length = config.max_filename_length;
-    println!("timestamp_format     = {timestamp_format}");
-    println!("backup_suffix        = {backup_suffix}");
-    println!("preserve_permissions = {preserve_permissions}");
-    println!("follow_symlinks      = {follow_symlinks}");
-    println!("include_hidden       = {include_hidden}");
-    println!("max_filename_length  = {max_filename_length}");
+    let show = |key: &str, value: &dyn std::fmt::Display| {
+        println!(
+            "{key:<20} = {value}  ({})",
+            config.sources.get(key).as_str()
+        );
+    };
+    let unlimited_bytes = |value: Option<u64>, unit: &str| {
+        value.map_or_else(
+            || "unlimited".to_string(),
+            |value| format!("{value} {unit}"),
+        )
+    };
+    show("timestamp_format", &config.timestamp_format);
+    show("backup_suffix", &config.backup_suffix);
+    show("collision_strategy", &config.collision_strategy.as_str());
+    show("preserve_permissions", &config.preserve_permissions);
+    let mode = |mode: Option<u32>| match mode {
+        Some(mode) => format!("{mode:04o}"),
+        None => "umask".to_string(),
+    };
+    show("dir_mode", &mode(config.dir_mode));
+    show("file_mode", &mode(config.file_mode));
+    show("preserve_acls", &config.preserve_acls);
+    show("preserve_sparse", &config.preserve_sparse);
+    show("follow_symlinks", &config.follow_symlinks);
+    show("dereference_top_level", &config.dereference_top_level);
+    show(
+        "symlink_broken_policy",
+        &config.symlink_broken_policy.as_str(),
+    );
+    show("include_hidden", &config.include_hidden);
+    show("allow_outside_cwd", &config.allow_outside_cwd);
+    show("max_filename_length", &config.max_filename_length);
+    show("truncate_long_names", &config.truncate_long_names);
+    let max_depth = config
+        .max_depth
+        .map_or_else(|| "unlimited".to_string(), |depth| depth.to_string());
+    show("max_depth", &max_depth);
+    show("max_depth_mode", &config.max_depth_mode.as_str());
+    show(
+        "max_file_size",
+        &unlimited_bytes(config.max_file_size, "bytes"),
+    );
+    show("prune_empty_dirs", &config.prune_empty_dirs);
+    show("single_pass", &config.single_pass);
+    show("use_reflink", &config.use_reflink.as_str());
+    show("compression", &config.compression.as_str());
+    show("archive", &config.archive.as_str());
+    show("zip_compression_level", &config.zip_compression_level);
+    show("rate_limit", &unlimited_bytes(config.rate_limit, "bytes/s"));
+    show(
+        "copy_buffer_size",
+        &format!("{} bytes", config.copy_buffer_size),
+    );
+    show("io_retries", &config.io_retries);
+    show(
+        "io_retry_delay_ms",
+        &format!("{} ms", config.io_retry_delay.as_millis()),
+    );
+    show("recopy_changed_files", &config.recopy_changed_files);
+    show("verify", &config.verify);
+    show("incremental", &config.incremental);
+    show("dedup", &config.dedup);
+    show("preserve_hardlinks", &config.preserve_hardlinks);
+    show("check_space", &config.check_space);
+    show("space_buffer_percent", &config.space_buffer_percent);
+    show(
+        "space_buffer_min",
+        &format!("{} bytes", config.space_buffer_min),
+    );
+    show(
+        "min_free_space",
+        &format!("{} bytes", config.min_free_space),
+    );
+    show("fsync", &config.fsync);
+    show(
+        "keep_partial_on_interrupt",
+        &config.keep_partial_on_interrupt,
+    );
+    show("record_entries", &config.record_entries);
+    show("write_manifest", &config.write_manifest);
+    show("manifest_checksums", &config.manifest_checksums);
+    show("size_units", &config.size_units.as_str());
+    if let Some(ref output_dir) = config.output_dir {
+        show("output_dir", &output_dir.display());
+    }
+    if let Some(ref into_dir) = config.into_dir {
+        show("into_dir", &into_dir.display());
+    }
+    if let Some(ref dated_dir) = config.dated_dir {
+        show("dated_dir", &dated_dir.display());
+    }
+    if config.overwrite {
+        show("overwrite", &config.overwrite);
+    }
+    if !config.include_patterns.is_empty() {
+        show("include_patterns", &config.include_patterns.join(", "));
+    }
+    if !config.exclude_patterns.is_empty() {
+        show("exclude_patterns", &config.exclude_patterns.join(", "));
+    }
     println!();
 
     // Show progress settings
     println!("Progress Settings:");
     println!("-----------------");
     let progress = &config.progress;
-    println!("enabled              = {}", progress.enabled);
-    println!("force_enabled        = {}", progress.force_enabled);
-    println!("min_files_threshold  = {}", progress.min_files_threshold);
-    println!(
-        "min_size_threshold   = {} MB",
-        progress.min_size_threshold / (1024 * 1024)
+    let show = |label: &str, key: &str, value: &dyn std::fmt::Display| {
+        let source = config.sources.get(&format!("progress.{key}"));
+        println!("{label:<20} = {value}  ({})", source.as_str());
+    };
+    show("enabled", "enabled", &progress.enabled);
+    show("force_enabled", "force_enabled", &progress.force_enabled);
+    show(
+        "min_files_threshold",
+        "min_files",
+        &progress.min_files_threshold,
     );
-    println!(
-        "min_duration_threshold = {} seconds",
-        progress.min_duration_threshold.as_secs()
+    show(
+        "min_size_threshold",
+        "min_size_mb",
+        &format_size_in(progress.min_size_threshold, config.size_units),
+    );
+    show(
+        "min_duration_threshold",
+        "min_duration_seconds",
+        &format!("{} seconds", progress.min_duration_threshold.as_secs()),
+    );
+    show("supports_ansi", "supports_ansi", &progress.supports_ansi);
+    show("terminal_width", "terminal_width", &progress.terminal_width);
+    show("is_interactive", "is_interactive", &progress.is_interactive);
+    show("format", "format", &progress.format.as_str());
+    show(
+        "scan_update_interval",
+        "scan_interval",
+        &format!("{} files", progress.scan_update_interval),
     );
-    println!("supports_ansi        = {}", progress.supports_ansi);
-    println!("terminal_width       = {}", progress.terminal_width);
-    println!("is_interactive       = {}", progress.is_interactive);
     println!();
 
     // Show example usage
@@ -281,10 +1290,143 @@ mod tests {
         let config = default_config();
         assert_eq!(config.timestamp_format, "YYYYMMDDTHHMMSS");
         assert_eq!(config.backup_suffix, "qbak");
+        assert_eq!(config.collision_strategy, CollisionStrategy::Counter);
         assert!(config.preserve_permissions);
+        assert_eq!(config.dir_mode, None);
+        assert_eq!(config.file_mode, None);
         assert!(config.follow_symlinks);
+        assert!(!config.dereference_top_level);
+        assert_eq!(config.symlink_broken_policy, BrokenSymlinkPolicy::Warn);
         assert!(config.include_hidden);
+        assert!(config.allow_outside_cwd);
         assert_eq!(config.max_filename_length, 255);
+        assert!(!config.truncate_long_names);
+        assert_eq!(config.max_depth, None);
+        assert_eq!(config.max_depth_mode, DepthLimitMode::Create);
+        assert_eq!(config.max_file_size, None);
+        assert!(config.newer_than.is_none());
+        assert!(config.keep.is_none());
+        assert!(!config.record_entries);
+        assert!(!config.write_manifest);
+        assert!(!config.manifest_checksums);
+        assert!(!config.prune_empty_dirs);
+        assert!(!config.single_pass);
+        assert!(!config.verbose);
+        assert_eq!(config.use_reflink, ReflinkMode::Auto);
+        assert_eq!(config.compression, CompressionMode::None);
+        assert_eq!(config.archive, ArchiveMode::None);
+        assert_eq!(config.size_units, SizeUnits::Binary);
+        assert!(!config.show_bytes);
+        assert_eq!(config.zip_compression_level, 6);
+        assert_eq!(config.rate_limit, None);
+        assert_eq!(config.copy_buffer_size, 64 * 1024);
+        assert_eq!(config.io_retries, 3);
+        assert_eq!(config.io_retry_delay, Duration::from_millis(200));
+        assert!(!config.recopy_changed_files);
+        assert!(!config.verify);
+        assert!(!config.incremental);
+        assert!(!config.dedup);
+        assert!(!config.preserve_hardlinks);
+        assert!(!config.preserve_acls);
+        assert!(config.preserve_sparse);
+        assert!(config.check_space);
+        assert_eq!(config.space_buffer_percent, 10);
+        assert_eq!(config.space_buffer_min, 0);
+        assert_eq!(config.min_free_space, 0);
+        assert_eq!(config.sources.get("min_free_space"), ConfigSource::Default);
+        assert_eq!(
+            config.sources.get("progress.terminal_width"),
+            ConfigSource::Detected
+        );
+        assert!(config.fsync);
+        assert!(!config.keep_partial_on_interrupt);
+        assert!(config.output_dir.is_none());
+        assert!(config.into_dir.is_none());
+        assert!(config.dated_dir.is_none());
+        assert!(!config.overwrite);
+    }
+
+    #[test]
+    fn test_reflink_mode_parse() {
+        assert_eq!(ReflinkMode::parse("auto"), Some(ReflinkMode::Auto));
+        assert_eq!(ReflinkMode::parse("ALWAYS"), Some(ReflinkMode::Always));
+        assert_eq!(ReflinkMode::parse("never"), Some(ReflinkMode::Never));
+        assert_eq!(ReflinkMode::parse("true"), Some(ReflinkMode::Always));
+        assert_eq!(ReflinkMode::parse("off"), Some(ReflinkMode::Never));
+        assert_eq!(ReflinkMode::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_compression_mode_parse() {
+        assert_eq!(CompressionMode::parse("none"), Some(CompressionMode::None));
+        assert_eq!(CompressionMode::parse("GZIP"), Some(CompressionMode::Gzip));
+        assert_eq!(CompressionMode::parse("gz"), Some(CompressionMode::Gzip));
+        assert_eq!(CompressionMode::parse("zstd"), None);
+    }
+
+    #[test]
+    fn test_archive_mode_parse() {
+        assert_eq!(ArchiveMode::parse("none"), Some(ArchiveMode::None));
+        assert_eq!(ArchiveMode::parse("TAR"), Some(ArchiveMode::Tar));
+        assert_eq!(ArchiveMode::parse("zip"), Some(ArchiveMode::Zip));
+        assert_eq!(ArchiveMode::parse("cpio"), None);
+    }
+
+    #[test]
+    fn test_size_units_parse() {
+        assert_eq!(SizeUnits::parse("binary"), Some(SizeUnits::Binary));
+        assert_eq!(SizeUnits::parse("SI"), Some(SizeUnits::Si));
+        assert_eq!(SizeUnits::parse("iec"), Some(SizeUnits::Iec));
+        assert_eq!(SizeUnits::parse("metric"), None);
+    }
+
+    #[test]
+    fn test_collision_strategy_parse() {
+        assert_eq!(
+            CollisionStrategy::parse("counter"),
+            Some(CollisionStrategy::Counter)
+        );
+        assert_eq!(
+            CollisionStrategy::parse("Timestamp"),
+            Some(CollisionStrategy::Timestamp)
+        );
+        assert_eq!(
+            CollisionStrategy::parse("error"),
+            Some(CollisionStrategy::Error)
+        );
+        assert_eq!(CollisionStrategy::parse("overwrite"), None);
+    }
+
+    #[test]
+    fn test_broken_symlink_policy_parse() {
+        assert_eq!(
+            BrokenSymlinkPolicy::parse("skip"),
+            Some(BrokenSymlinkPolicy::Skip)
+        );
+        assert_eq!(
+            BrokenSymlinkPolicy::parse("WARN"),
+            Some(BrokenSymlinkPolicy::Warn)
+        );
+        assert_eq!(
+            BrokenSymlinkPolicy::parse("preserve-as-link"),
+            Some(BrokenSymlinkPolicy::PreserveAsLink)
+        );
+        assert_eq!(
+            BrokenSymlinkPolicy::parse("error"),
+            Some(BrokenSymlinkPolicy::Error)
+        );
+        assert_eq!(BrokenSymlinkPolicy::parse("ignore"), None);
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0750"), Some(0o750));
+        assert_eq!(parse_mode("640"), Some(0o640));
+        assert_eq!(parse_mode("0o2775"), Some(0o2775));
+        assert_eq!(parse_mode("0800"), None);
+        assert_eq!(parse_mode("17777"), None);
+        assert_eq!(parse_mode("rwx"), None);
+        assert_eq!(parse_mode(""), None);
     }
 
     #[test]
@@ -377,9 +1519,11 @@ mod tests {
 
         // Create a test config file
         let config_content = r#"[qbak]
-timestamp_format = TEST_FORMAT
+timestamp_format = YYYYMMDDTHHMMSS
 backup_suffix = test-suffix
 preserve_permissions = false
+dir_mode = 0750
+file_mode = 0o640
 follow_symlinks = false
 include_hidden = false
 max_filename_length = 100
@@ -397,12 +1541,19 @@ max_filename_length = 100
 
         let config = load_config().unwrap();
 
-        assert_eq!(config.timestamp_format, "TEST_FORMAT");
+        assert_eq!(config.timestamp_format, "YYYYMMDDTHHMMSS");
         assert_eq!(config.backup_suffix, "test-suffix");
         assert!(!config.preserve_permissions);
+        assert_eq!(config.dir_mode, Some(0o750));
+        assert_eq!(config.file_mode, Some(0o640));
         assert!(!config.follow_symlinks);
         assert!(!config.include_hidden);
         assert_eq!(config.max_filename_length, 100);
+        assert_eq!(
+            config.sources.get("include_hidden"),
+            ConfigSource::ConfigFile
+        );
+        assert_eq!(config.sources.get("verify"), ConfigSource::Default);
 
         // Restore original environment
         #[cfg(not(target_os = "windows"))]
@@ -449,6 +1600,10 @@ max_filename_length = 100
         // Create a config file with only some values
         let config_content = r#"[qbak]
 backup_suffix = custom
+space_buffer_percent = 250
+space_buffer_min = 1M
+min_free_space = 2G
+copy_buffer_size = 1MiB
 "#;
         fs::write(&config_path, config_content).unwrap();
 
@@ -466,6 +1621,14 @@ backup_suffix = custom
 
         // Overridden values
         assert_eq!(config.backup_suffix, "custom");
+        assert_eq!(config.space_buffer_percent, 100); // clamped
+        assert_eq!(config.space_buffer_min, 1_000_000);
+        assert_eq!(config.min_free_space, 2_000_000_000);
+        assert_eq!(
+            config.sources.get("min_free_space"),
+            ConfigSource::ConfigFile
+        );
+        assert_eq!(config.copy_buffer_size, 1024 * 1024);
 
         // Default values should remain
         assert_eq!(config.timestamp_format, default.timestamp_format);
@@ -563,6 +1726,32 @@ include_hidden = not_a_boolean
         }
     }
 
+    #[test]
+    fn test_config_validate() {
+        assert!(default_config().validate().is_ok());
+
+        let config = Config {
+            timestamp_format: "%Y".to_string(),
+            backup_suffix: " ".to_string(),
+            max_filename_length: 0,
+            ..default_config()
+        };
+        let problems = config.problems();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("timestamp_format"));
+        assert!(problems[1].starts_with("backup_suffix"));
+        assert!(problems[2].starts_with("max_filename_length 0"));
+        assert!(config.validate().is_err());
+
+        for suffix in ["", "\t", "../up", "a/b", "x*y", ".hidden"] {
+            let config = Config {
+                backup_suffix: suffix.to_string(),
+                ..default_config()
+            };
+            assert!(config.validate().is_err(), "suffix {suffix:?} accepted");
+        }
+    }
+
     #[test]
     fn test_config_invalid_numeric() {
         let _guard = ENV_MUTEX.lock().unwrap(); // Serialize environment access
@@ -598,6 +1787,28 @@ max_filename_length = not_a_number
         let result = load_config();
         assert!(result.is_err()); // Should fail to parse invalid numbers
 
+        // Copy buffer sizes outside the accepted range are rejected
+        for size in ["1K", "64MiB"] {
+            fs::write(&config_path, format!("[qbak]\ncopy_buffer_size = {size}\n")).unwrap();
+            assert!(load_config().is_err(), "copy_buffer_size {size} accepted");
+        }
+
+        // So are retry counts and delays that would stall a copy for minutes
+        for setting in ["io_retries = 11", "io_retry_delay_ms = 5001"] {
+            fs::write(&config_path, format!("[qbak]\n{setting}\n")).unwrap();
+            assert!(load_config().is_err(), "{setting} accepted");
+        }
+
+        // Values that parse but fail validation are rejected too
+        for setting in [
+            "max_filename_length = 0",
+            "backup_suffix = ",
+            "backup_suffix = ../up",
+        ] {
+            fs::write(&config_path, format!("[qbak]\n{setting}\n")).unwrap();
+            assert!(load_config().is_err(), "{setting} accepted");
+        }
+
         // Restore original environment
         #[cfg(not(target_os = "windows"))]
         {
@@ -796,7 +2007,7 @@ some_key = some_value
             let dir = tempdir().unwrap();
             std::env::set_var("XDG_CONFIG_HOME", dir.path());
 
-            let config_path = get_config_path().unwrap();
+            let config_path = get_config_path().unwrap().0;
             let expected = dir.path().join("qbak").join("config.ini");
 
             assert_eq!(config_path, expected);
@@ -818,7 +2029,7 @@ some_key = some_value
             let dir = tempdir().unwrap();
             std::env::set_var("APPDATA", dir.path());
 
-            let config_path = get_config_path().unwrap();
+            let config_path = get_config_path().unwrap().0;
             let expected = dir.path().join("qbak").join("config.ini");
 
             assert_eq!(config_path, expected);
@@ -849,7 +2060,7 @@ some_key = some_value
             if let Some(home) = original_home.clone() {
                 std::env::set_var("HOME", &home);
 
-                let config_path = get_config_path().unwrap();
+                let config_path = get_config_path().unwrap().0;
                 let expected = PathBuf::from(home)
                     .join(".config")
                     .join("qbak")
@@ -880,6 +2091,85 @@ some_key = some_value
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_config_path_env_override() {
+        let _guard = ENV_MUTEX.lock().unwrap(); // Serialize environment access
+        let original = std::env::var_os(CONFIG_PATH_ENV);
+
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("custom.ini");
+        fs::write(&config_path, "[qbak]\nbackup_suffix = envcfg\n").unwrap();
+
+        std::env::set_var(CONFIG_PATH_ENV, &config_path);
+        assert_eq!(
+            get_config_path().unwrap(),
+            (config_path.clone(), CONFIG_PATH_ENV)
+        );
+        assert_eq!(load_config().unwrap().backup_suffix, "envcfg");
+
+        // An explicitly named file must exist
+        std::env::set_var(CONFIG_PATH_ENV, dir.path().join("missing.ini"));
+        assert!(matches!(load_config(), Err(QbakError::Config { .. })));
+
+        // An empty value falls back to the platform default
+        std::env::set_var(CONFIG_PATH_ENV, "");
+        assert_ne!(
+            get_config_path().map(|(_, source)| source).ok(),
+            Some(CONFIG_PATH_ENV)
+        );
+
+        match original {
+            Some(value) => std::env::set_var(CONFIG_PATH_ENV, value),
+            None => std::env::remove_var(CONFIG_PATH_ENV),
+        }
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_config_output_dir() {
+        let _guard = ENV_MUTEX.lock().unwrap(); // Serialize environment access
+        let original = std::env::var_os(CONFIG_PATH_ENV);
+        let original_home = std::env::var_os("HOME");
+
+        let dir = tempdir().unwrap();
+        let home = dir.path().join("home");
+        let config_path = dir.path().join("custom.ini");
+        std::env::set_var(CONFIG_PATH_ENV, &config_path);
+        std::env::set_var("HOME", &home);
+
+        let output_dir_for = |value: &str| {
+            fs::write(&config_path, format!("[qbak]\noutput_dir = {value}\n")).unwrap();
+            load_config().map(|config| config.output_dir)
+        };
+        assert_eq!(
+            output_dir_for("/mnt/backups").unwrap(),
+            Some(PathBuf::from("/mnt/backups"))
+        );
+        // Relative paths don't depend on the current directory
+        assert_eq!(
+            output_dir_for("backups").unwrap(),
+            Some(home.join("backups"))
+        );
+        assert_eq!(
+            output_dir_for("~/backups").unwrap(),
+            Some(home.join("backups"))
+        );
+        assert!(output_dir_for("").is_err());
+
+        // Not set means next to the source
+        fs::write(&config_path, "[qbak]\n").unwrap();
+        assert_eq!(load_config().unwrap().output_dir, None);
+
+        match original {
+            Some(value) => std::env::set_var(CONFIG_PATH_ENV, value),
+            None => std::env::remove_var(CONFIG_PATH_ENV),
+        }
+        match original_home {
+            Some(value) => std::env::set_var("HOME", value),
+            None => std::env::remove_var("HOME"),
+        }
+    }
+
     #[test]
     fn test_get_config_path_no_env() {
         let _guard = ENV_MUTEX.lock().unwrap(); // Serialize environment access
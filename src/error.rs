@@ -9,8 +9,14 @@ pub enum QbakError {
     #[error("Backup filename too long: {length} chars (max: {max})")]
     FilenameTooLong { length: usize, max: usize },
 
-    #[error("Insufficient disk space: need {needed} bytes, have {available}")]
-    InsufficientSpace { needed: u64, available: u64 },
+    #[error(
+        "Insufficient disk space: need {needed} bytes (including a {buffer} byte safety buffer), have {available}"
+    )]
+    InsufficientSpace {
+        needed: u64,
+        buffer: u64,
+        available: u64,
+    },
 
     #[error("Permission denied: {path}")]
     PermissionDenied { path: PathBuf },
@@ -38,6 +44,15 @@ pub enum QbakError {
 
     #[error("Validation error: {message}")]
     Validation { message: String },
+
+    #[error("Backup verification failed: {path}")]
+    VerificationFailed { path: PathBuf },
+
+    #[error("Unsupported file type ({kind}): {path}")]
+    UnsupportedFileType { path: PathBuf, kind: String },
+
+    #[error("Cannot move backup across filesystems: {from} -> {to}")]
+    CrossDevice { from: PathBuf, to: PathBuf },
 }
 
 impl QbakError {
@@ -62,19 +77,47 @@ impl QbakError {
             QbakError::SourceNotFound { .. }
                 | QbakError::PermissionDenied { .. }
                 | QbakError::Validation { .. }
+                | QbakError::UnsupportedFileType { .. }
         )
     }
 
+    /// Exit code for an interrupted run whose partial backups could not all be removed
+    ///
+    /// Used instead of 130 so monitoring can tell that leftover partials need attention.
+    pub const EXIT_CLEANUP_INCOMPLETE: i32 = 4;
+
     /// Get the exit code for this error
     pub fn exit_code(&self) -> i32 {
         match self {
             QbakError::Interrupted => 130,
             QbakError::Validation { .. } => 2,
             QbakError::Config { .. } => 2,
+            QbakError::CrossDevice { .. } => 3,
             _ => 1,
         }
     }
 
+    /// Name of the error variant, used as a stable key in machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QbakError::SourceNotFound { .. } => "SourceNotFound",
+            QbakError::FilenameTooLong { .. } => "FilenameTooLong",
+            QbakError::InsufficientSpace { .. } => "InsufficientSpace",
+            QbakError::PermissionDenied { .. } => "PermissionDenied",
+            QbakError::InvalidFilesystemChars { .. } => "InvalidFilesystemChars",
+            QbakError::SymlinkLoop { .. } => "SymlinkLoop",
+            QbakError::BackupExists { .. } => "BackupExists",
+            QbakError::PathTraversal { .. } => "PathTraversal",
+            QbakError::Io(_) => "Io",
+            QbakError::Config { .. } => "Config",
+            QbakError::Interrupted => "Interrupted",
+            QbakError::Validation { .. } => "Validation",
+            QbakError::VerificationFailed { .. } => "VerificationFailed",
+            QbakError::UnsupportedFileType { .. } => "UnsupportedFileType",
+            QbakError::CrossDevice { .. } => "CrossDevice",
+        }
+    }
+
     /// Provide helpful suggestions for resolving the error
     pub fn suggestions(&self) -> Vec<String> {
         match self {
@@ -87,16 +130,46 @@ impl QbakError {
                 format!("Rename file to remove problematic characters: {chars}"),
                 "Use a different filesystem that supports these characters".to_string(),
             ],
-            QbakError::InsufficientSpace { .. } => vec![
-                "Free up disk space".to_string(),
-                "Choose a different backup location".to_string(),
-                "Remove old backup files".to_string(),
-            ],
+            QbakError::InsufficientSpace {
+                needed,
+                buffer,
+                available,
+            } => {
+                let mut suggestions = vec![
+                    "Free up disk space".to_string(),
+                    "Choose a different backup location".to_string(),
+                    "Remove old backup files".to_string(),
+                ];
+                // The data itself would fit; only the safety buffer doesn't
+                if needed - buffer <= *available {
+                    suggestions.push(
+                        "Lower space_buffer_percent, space_buffer_min or min_free_space in the config"
+                            .to_string(),
+                    );
+                }
+                suggestions
+            }
             QbakError::PermissionDenied { .. } => vec![
                 "Check file permissions".to_string(),
                 "Run with appropriate privileges".to_string(),
                 "Ensure parent directory is writable".to_string(),
             ],
+            QbakError::VerificationFailed { .. } => vec![
+                "Check the storage device for errors".to_string(),
+                "Make sure the source is not modified during the backup".to_string(),
+                "Run the backup again".to_string(),
+            ],
+            QbakError::UnsupportedFileType { kind, .. } => vec![
+                format!("qbak only backs up regular files, directories and symlinks, not a {kind}"),
+                "Check that the path points at the file you meant to back up".to_string(),
+            ],
+            QbakError::Config { .. } => vec![
+                "Run qbak --check-config to list every problem in the config file".to_string(),
+                "Set QBAK_CONFIG to use a different config file".to_string(),
+            ],
+            QbakError::CrossDevice { .. } => vec![
+                "Use --output with a directory on the same filesystem as the source".to_string(),
+            ],
             _ => vec![],
         }
     }
@@ -123,6 +196,7 @@ mod tests {
 
         let insufficient_space = QbakError::InsufficientSpace {
             needed: 1000,
+            buffer: 100,
             available: 500,
         };
         assert!(format!("{insufficient_space}").contains("1000"));
@@ -152,6 +226,11 @@ mod tests {
         assert!(QbakError::SourceNotFound { path: path.clone() }.is_recoverable());
         assert!(QbakError::PermissionDenied { path: path.clone() }.is_recoverable());
         assert!(QbakError::validation("test").is_recoverable());
+        assert!(QbakError::UnsupportedFileType {
+            path: path.clone(),
+            kind: "FIFO".to_string()
+        }
+        .is_recoverable());
 
         // Non-recoverable errors
         assert!(!QbakError::Interrupted.is_recoverable());
@@ -163,6 +242,7 @@ mod tests {
         .is_recoverable());
         assert!(!QbakError::InsufficientSpace {
             needed: 1000,
+            buffer: 100,
             available: 500
         }
         .is_recoverable());
@@ -173,6 +253,14 @@ mod tests {
         assert_eq!(QbakError::Interrupted.exit_code(), 130);
         assert_eq!(QbakError::validation("test").exit_code(), 2);
         assert_eq!(QbakError::config("test").exit_code(), 2);
+        assert_eq!(
+            QbakError::CrossDevice {
+                from: PathBuf::from("/a"),
+                to: PathBuf::from("/b"),
+            }
+            .exit_code(),
+            3
+        );
 
         let path = PathBuf::from("/test");
         assert_eq!(QbakError::SourceNotFound { path }.exit_code(), 1);
@@ -208,11 +296,25 @@ mod tests {
         // InsufficientSpace suggestions
         let space_error = QbakError::InsufficientSpace {
             needed: 1000,
+            buffer: 100,
             available: 500,
         };
         let suggestions = space_error.suggestions();
         assert!(!suggestions.is_empty());
         assert!(suggestions.iter().any(|s| s.contains("disk space")));
+        assert!(!suggestions.iter().any(|s| s.contains("space_buffer")));
+
+        // Only the safety buffer is missing
+        let buffer_error = QbakError::InsufficientSpace {
+            needed: 1100,
+            buffer: 100,
+            available: 1050,
+        };
+        assert!(format!("{buffer_error}").contains("100 byte safety buffer"));
+        assert!(buffer_error
+            .suggestions()
+            .iter()
+            .any(|s| s.contains("space_buffer_percent")));
 
         // PermissionDenied suggestions
         let path = PathBuf::from("/test");
@@ -221,11 +323,47 @@ mod tests {
         assert!(!suggestions.is_empty());
         assert!(suggestions.iter().any(|s| s.contains("permission")));
 
+        // UnsupportedFileType suggestions
+        let type_error = QbakError::UnsupportedFileType {
+            path: PathBuf::from("/tmp/pipe"),
+            kind: "FIFO".to_string(),
+        };
+        assert!(format!("{type_error}").contains("FIFO"));
+        assert!(type_error.suggestions().iter().any(|s| s.contains("FIFO")));
+
+        // CrossDevice suggestions
+        let cross_device_error = QbakError::CrossDevice {
+            from: PathBuf::from("/tmp/.qbak-temp"),
+            to: PathBuf::from("/mnt/backup"),
+        };
+        assert!(format!("{cross_device_error}").contains("/mnt/backup"));
+        assert!(cross_device_error
+            .suggestions()
+            .iter()
+            .any(|s| s.contains("--output")));
+
+        assert!(QbakError::config("bad suffix")
+            .suggestions()
+            .iter()
+            .any(|s| s.contains("--check-config")));
+
         // Error with no suggestions
         let no_suggestions_error = QbakError::Interrupted;
         assert!(no_suggestions_error.suggestions().is_empty());
     }
 
+    #[test]
+    fn test_kind() {
+        let path = PathBuf::from("/test");
+        assert_eq!(QbakError::SourceNotFound { path }.kind(), "SourceNotFound");
+        assert_eq!(QbakError::Interrupted.kind(), "Interrupted");
+        assert_eq!(QbakError::validation("test").kind(), "Validation");
+        assert_eq!(
+            QbakError::from(io::Error::new(io::ErrorKind::Other, "test")).kind(),
+            "Io"
+        );
+    }
+
     #[test]
     fn test_io_error_conversion() {
         let io_error = io::Error::new(io::ErrorKind::NotFound, "File not found");
@@ -249,6 +387,7 @@ mod tests {
             },
             QbakError::InsufficientSpace {
                 needed: 1000,
+                buffer: 100,
                 available: 500,
             },
             QbakError::PermissionDenied { path: path.clone() },
@@ -257,10 +396,11 @@ mod tests {
             },
             QbakError::SymlinkLoop { path: path.clone() },
             QbakError::BackupExists { path: path.clone() },
-            QbakError::PathTraversal { path },
+            QbakError::PathTraversal { path: path.clone() },
             QbakError::config("Config test"),
             QbakError::Interrupted,
             QbakError::validation("Validation test"),
+            QbakError::VerificationFailed { path },
         ];
 
         for error in errors {
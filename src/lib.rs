@@ -1,3 +1,17 @@
+//! Library behind the `qbak` command-line tool.
+//!
+//! The items re-exported at the crate root are the stable API for embedders:
+//!
+//! - Backups: [`backup_file`], [`backup_directory`] and their progress-reporting variants
+//! - Naming: [`generate_backup_name`] builds the `name-TIMESTAMP-suffix.ext` path qbak
+//!   would use for a source, [`parse_backup_name`] reverses it, and [`split_filename`]
+//!   splits a filename into stem and extension the same way
+//! - Sizes: [`format_size`] and [`format_size_in`] render byte counts as shown by the CLI
+//! - Configuration: [`Config`], [`load_config`] and [`default_config`]
+//!
+//! Items only reachable through module paths (e.g. `qbak::utils::...`) are
+//! implementation details and may change between minor releases.
+
 pub mod backup;
 pub mod config;
 pub mod error;
@@ -7,14 +21,31 @@ pub mod signal;
 pub mod utils;
 
 pub use backup::{
-    backup_directory, backup_directory_with_progress, backup_file, count_files_and_size,
-    count_files_and_size_with_progress, BackupResult,
+    backup_combined, backup_directory, backup_directory_with_callback,
+    backup_directory_with_progress, backup_directory_with_shared_progress, backup_file,
+    backup_file_to_writer, backup_file_with_progress, backup_file_with_shared_progress,
+    backup_into, count_files_and_size, count_files_and_size_with_progress, prune_old_backups,
+    restore_backup, resume_directory_backup, scan_directory, uses_single_pass,
+    verify_against_source, BackupResult, Discrepancy, ScanTotals, SkipReason, VerifyReport,
+};
+pub use config::{
+    default_config, dump_config, load_config, ArchiveMode, BrokenSymlinkPolicy, CollisionStrategy,
+    CompressionMode, Config, ConfigSource, ConfigSources, DepthLimitMode, ReflinkMode, SizeUnits,
 };
-pub use config::{default_config, dump_config, load_config, Config};
 pub use error::QbakError;
-pub use naming::{generate_backup_name, resolve_collision};
-pub use progress::{create_progress_bar, should_show_progress, BackupProgress, ProgressConfig};
-pub use utils::{calculate_size, check_available_space, validate_backup_filename, validate_source};
+pub use naming::{
+    find_backups, generate_backup_name, generate_combined_backup_name, generate_dated_dir,
+    parse_backup_name, resolve_collision, resolve_collision_with, resolve_combined_collision,
+    split_filename, BackupInfo, ParsedBackupName,
+};
+pub use progress::{
+    create_progress_bar, should_show_progress, BackupProgress, ProgressConfig, ProgressEvent,
+    ProgressFormat, ProgressHandle,
+};
+pub use utils::{
+    calculate_size, check_available_space, format_size, format_size_in, validate_backup_filename,
+    validate_source,
+};
 
 /// Main library result type
 pub type Result<T> = std::result::Result<T, QbakError>;
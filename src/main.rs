@@ -1,14 +1,26 @@
 use clap::{Arg, ArgAction, Command};
-use qbak::{backup_file, dump_config, load_config, QbakError};
-use std::path::Path;
+use qbak::{backup_file_with_progress, dump_config, load_config, ConfigSource, QbakError};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::{Mutex, OnceLock};
+
+/// The `--log` file, which gets one timestamped JSON line per backup, error and summary
+static RUN_LOG: OnceLock<Mutex<std::fs::File>> = OnceLock::new();
 
 fn main() {
-    let result = run();
+    // One context per invocation: interrupts and partial backups of this run are tracked in it
+    let context = qbak::signal::BackupContext::new();
+    let result = context.scope(|| run(std::env::args_os(), &context));
     match result {
         Ok(exit_code) => process::exit(exit_code),
         Err(error) => {
             eprintln!("Error: {error}");
+            log_event(serde_json::json!({
+                "ok": false,
+                "error": error.kind(),
+                "message": error.to_string(),
+            }));
 
             // Show suggestions if available
             let suggestions = error.suggestions();
@@ -20,8 +32,17 @@ fn main() {
             }
 
             // If this was an interrupted operation, clean up partial backups before exit
-            if matches!(error, QbakError::Interrupted) {
-                qbak::signal::cleanup_active_operations();
+            let mut exit_code = error.exit_code();
+            if matches!(error, QbakError::Interrupted) && context.keeps_partial() {
+                if !context.get_active_operations().is_empty() {
+                    context.cleanup_active_operations();
+                    eprintln!("Incomplete backups were kept; inspect or delete them manually");
+                }
+            } else if matches!(error, QbakError::Interrupted) {
+                if !context.cleanup_active_operations() {
+                    eprintln!("Some incomplete backups could not be removed; delete them manually");
+                    exit_code = QbakError::EXIT_CLEANUP_INCOMPLETE;
+                }
 
                 // Also clean up any temporary files
                 if let Ok(current_dir) = std::env::current_dir() {
@@ -29,12 +50,16 @@ fn main() {
                 }
             }
 
-            process::exit(error.exit_code());
+            process::exit(exit_code);
         }
     }
 }
 
-fn run() -> Result<i32, QbakError> {
+fn run<I, T>(args: I, context: &qbak::signal::BackupContext) -> Result<i32, QbakError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
     let matches = Command::new("qbak")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Andreas Glaser <andreas.glaser@pm.me>")
@@ -43,9 +68,15 @@ fn run() -> Result<i32, QbakError> {
             "qbak creates timestamped backup copies of files and directories.\n\
              Example: qbak example.txt → example-20250603T145231-qbak.txt",
         )
+        .after_help(
+            "Settings are applied in this order, later ones taking precedence:\n  \
+             1. built-in defaults\n  \
+             2. the config file ($QBAK_CONFIG, or the default location)\n  \
+             3. command-line flags",
+        )
         .arg(
             Arg::new("targets")
-                .help("Files or directories to back up")
+                .help("Files or directories to back up (use - to read paths from stdin)")
                 .required(false)
                 .num_args(1..)
                 .value_name("TARGET"),
@@ -72,6 +103,13 @@ fn run() -> Result<i32, QbakError> {
                 .action(ArgAction::SetTrue)
                 .conflicts_with_all(["verbose", "progress"]),
         )
+        .arg(
+            Arg::new("summary-only")
+                .long("summary-only")
+                .help("Print only the final backup summary, not per-target output")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["verbose", "progress", "dry-run", "list"]),
+        )
         .arg(
             Arg::new("progress")
                 .long("progress")
@@ -79,6 +117,12 @@ fn run() -> Result<i32, QbakError> {
                 .action(ArgAction::SetTrue)
                 .conflicts_with("no-progress"),
         )
+        .arg(
+            Arg::new("no-prescan")
+                .long("no-prescan")
+                .help("Copy directories without counting them first (no percentage or ETA)")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("no-progress")
                 .long("no-progress")
@@ -86,37 +130,581 @@ fn run() -> Result<i32, QbakError> {
                 .action(ArgAction::SetTrue)
                 .conflicts_with("progress"),
         )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .help("Restore a qbak backup over its original name")
+                .value_name("BACKUP")
+                .conflicts_with("targets"),
+        )
+        .arg(
+            Arg::new("verify-only")
+                .long("verify-only")
+                .help("Check an existing backup against its source instead of backing up")
+                .num_args(2)
+                .value_names(["BACKUP", "SOURCE"])
+                .conflicts_with_all(["targets", "restore", "list", "dry-run"]),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help("Finish an interrupted directory backup from its kept partial copy")
+                .num_args(2)
+                .value_names(["PARTIAL", "SOURCE"])
+                .conflicts_with_all([
+                    "targets",
+                    "restore",
+                    "verify-only",
+                    "list",
+                    "dry-run",
+                    "stdout",
+                    "combine",
+                    "interactive",
+                ]),
+        )
+        .arg(
+            Arg::new("checksums")
+                .long("checksums")
+                .help("With --verify-only, also compare SHA-256 checksums of same-sized files")
+                .action(ArgAction::SetTrue)
+                .requires("verify-only"),
+        )
+        .arg(
+            Arg::new("stdout")
+                .long("stdout")
+                .help("Write a backup of a single file to stdout instead of creating a file")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "restore", "list", "dry-run", "json", "output", "keep", "tar", "zip",
+                ]),
+        )
+        .arg(
+            Arg::new("list")
+                .short('l')
+                .long("list")
+                .help("List existing backups of each target, newest first, instead of backing up")
+                .conflicts_with_all(["restore", "dry-run"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("interactive")
+                .short('i')
+                .long("interactive")
+                .help("Show what will happen to each target and ask before doing it")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["stdout", "list", "json", "combine", "dry-run"]),
+        )
+        .arg(
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Allow --restore to overwrite an existing file or directory")
+                .action(ArgAction::SetTrue)
+                .requires("restore"),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .help("Verify each backed up file against its source with a SHA-256 checksum")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-space-check")
+                .long("no-space-check")
+                .help("Skip the free disk space check before backing up")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-fsync")
+                .long("no-fsync")
+                .help("Don't sync backups to disk before reporting success (faster, less crash-safe)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("keep-partial")
+                .long("keep-partial")
+                .help("Keep incomplete backups when interrupted instead of deleting them")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("recopy-changed")
+                .long("recopy-changed")
+                .help("Copy a file again if it changes while being backed up")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("paranoid")
+                .long("paranoid")
+                .help("Sync to disk, verify checksums and keep incomplete backups when interrupted")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("no-fsync"),
+        )
+        .arg(
+            Arg::new("no-preserve-permissions")
+                .long("no-preserve-permissions")
+                .help("Don't copy permissions to the backup (overrides preserve_permissions)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("follow-symlinks")
+                .long("follow-symlinks")
+                .help("Copy the targets of symlinks instead of the links (overrides follow_symlinks)")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-follow-symlinks"),
+        )
+        .arg(
+            Arg::new("no-follow-symlinks")
+                .long("no-follow-symlinks")
+                .help("Preserve symlinks as links (overrides follow_symlinks)")
+                .action(ArgAction::SetTrue)
+                .overrides_with("follow-symlinks"),
+        )
+        .arg(
+            Arg::new("dereference-top-level")
+                .short('H')
+                .long("dereference-top-level")
+                .help("Follow symlinks given as targets, but not those inside directories")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .help("Include hidden files and directories (overrides include_hidden)")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-hidden"),
+        )
+        .arg(
+            Arg::new("no-hidden")
+                .long("no-hidden")
+                .help("Skip hidden files and directories (overrides include_hidden)")
+                .action(ArgAction::SetTrue)
+                .overrides_with("hidden"),
+        )
+        .arg(
+            Arg::new("gzip")
+                .long("gzip")
+                .help("Gzip-compress single-file backups and tar archives (adds .gz)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tar")
+                .long("tar")
+                .help("Write directory backups as a single .tar archive (.tar.gz with --gzip)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("zip")
+                .long("zip")
+                .help("Write directory backups as a single .zip archive")
+                .conflicts_with("tar")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("incremental")
+                .long("incremental")
+                .help("Hardlink files unchanged since the newest previous directory backup")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .help("Hardlink identical files within a directory backup instead of copying each")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("from-file")
+                .long("from-file")
+                .help("Read newline-separated targets from a file (# starts a comment)")
+                .value_name("PATH")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Write backups into this directory instead of next to the source (overrides output_dir)")
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::new("into")
+                .long("into")
+                .help("Back up directory contents into DIR as one timestamped backup per file")
+                .value_name("DIR")
+                .conflicts_with_all(["output", "tar", "zip", "incremental", "stdout", "keep"]),
+        )
+        .arg(
+            Arg::new("timestamp-format")
+                .long("timestamp-format")
+                .help("Timestamp format for this run (YYYYMMDDTHHMMSS or a strftime pattern)")
+                .value_name("FMT"),
+        )
+        .arg(
+            Arg::new("dated-dir")
+                .long("dated-dir")
+                .help("Collect this run's backups in BASE/TIMESTAMP under their original names")
+                .value_name("BASE")
+                .conflicts_with_all(["output", "into", "incremental", "stdout", "list", "keep"]),
+        )
+        .arg(
+            Arg::new("suffix")
+                .long("suffix")
+                .help("Backup name suffix for this run, e.g. daily (overrides backup_suffix)")
+                .value_name("STR"),
+        )
+        .arg(
+            Arg::new("combine")
+                .long("combine")
+                .help("Put all targets into one archive named backup-TIMESTAMP (needs --tar or --zip)")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["into", "incremental", "stdout", "list", "restore", "keep"]),
+        )
+        .arg(
+            Arg::new("overwrite")
+                .long("overwrite")
+                .help("Replace an existing backup with the same name instead of adding a counter")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["stdout", "list", "restore"]),
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .help("After backing up, delete all but the newest N backups of each target")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .help("Descend at most N directory levels (0 = only the immediate children)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max-file-size")
+                .long("max-file-size")
+                .help("Skip files larger than SIZE in directory backups (e.g. 100M, 2G)")
+                .value_name("SIZE"),
+        )
+        .arg(
+            Arg::new("bytes")
+                .long("bytes")
+                .help("Show exact byte counts next to human-readable sizes")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rate-limit")
+                .long("rate-limit")
+                .help("Limit copy throughput to RATE bytes per second (e.g. 10M)")
+                .value_name("RATE"),
+        )
+        .arg(
+            Arg::new("newer-than")
+                .long("newer-than")
+                .help("Only back up files modified after a date (2025-06-01) or within a duration (24h)")
+                .value_name("DATE|DURATION"),
+        )
+        .arg(
+            Arg::new("prune-empty")
+                .long("prune-empty")
+                .help("Leave out directories that --include or --newer-than leave empty")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .help("Only back up files matching this glob pattern (repeatable)")
+                .value_name("GLOB")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .help("Skip files and directories matching this glob pattern (repeatable, wins over --include)")
+                .value_name("GLOB")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude-from")
+                .long("exclude-from")
+                .help("Read gitignore-style exclude patterns from a file (# comments, !negation)")
+                .value_name("FILE")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("progress-format")
+                .long("progress-format")
+                .help("Progress output format: bar, or json for NDJSON events on stderr")
+                .value_name("FORMAT")
+                .value_parser(["bar", "json"]),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .help("Colorize output: auto (only on a terminal, honoring NO_COLOR), always, or never")
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("log")
+                .long("log")
+                .help("Append a timestamped JSON line per backup, error and summary to FILE")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print one JSON object per target and a JSON summary instead of text")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("summary-json")
+                .long("summary-json")
+                .help("End with a QBAK_SUMMARY line holding the summary as JSON for log scrapers")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["json", "combine", "stdout", "list"]),
+        )
+        .arg(
+            Arg::new("check-config")
+                .long("check-config")
+                .help("Validate the configuration file, report any problems and exit")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("dump-config")
                 .long("dump-config")
                 .help("Display current configuration settings and exit")
                 .action(ArgAction::SetTrue),
         )
-        .get_matches();
+        .get_matches_from(args);
+
+    if let Some(path) = matches.get_one::<String>("log") {
+        open_run_log(Path::new(path))?;
+    }
 
     // Parse command line flags
     let dump_config_flag = matches.get_flag("dump-config");
     let dry_run = matches.get_flag("dry-run");
     let verbose = matches.get_flag("verbose");
-    let quiet = matches.get_flag("quiet");
+    let summary_only = matches.get_flag("summary-only");
+    // Per-target output is suppressed exactly as in quiet mode
+    let quiet = matches.get_flag("quiet") || summary_only;
     let force_progress = matches.get_flag("progress");
     let no_progress = matches.get_flag("no-progress");
+    let json = matches.get_flag("json");
+    let interactive = matches.get_flag("interactive");
+    let color = matches
+        .get_one::<String>("color")
+        .map_or("auto", String::as_str);
+    setup_colors(color, json);
+
+    if matches.get_flag("check-config") {
+        return Ok(if qbak::config::check_config()? { 0 } else { 1 });
+    }
 
-    // Load configuration
-    let mut config = load_config()
-        .map_err(|e| {
-            if verbose {
-                eprintln!("Warning: Could not load config, using defaults: {e}");
-            }
-            e
-        })
-        .unwrap_or_else(|_| qbak::default_config());
+    // Load configuration; an unreadable or invalid config file stops the run instead of
+    // silently backing up with the defaults
+    let mut config = load_config()?;
+
+    // An explicit --color choice also decides whether progress bars are colored
+    if color != "auto" {
+        config.progress.supports_ansi = color == "always";
+        config
+            .sources
+            .set("progress.supports_ansi", ConfigSource::CliFlag);
+    }
 
     // Apply command line progress flags (they override config)
     if quiet || no_progress {
         config.progress.enabled = false;
+        config
+            .sources
+            .set("progress.enabled", ConfigSource::CliFlag);
     } else if force_progress {
         config.progress.force_enabled = true;
+        config
+            .sources
+            .set("progress.force_enabled", ConfigSource::CliFlag);
+    }
+
+    if let Some(format) = matches.get_one::<String>("progress-format") {
+        if let Some(format) = qbak::ProgressFormat::parse(format) {
+            config.progress.format = format;
+            config.sources.set("progress.format", ConfigSource::CliFlag);
+        }
+    }
+
+    config.verbose = verbose;
+    if matches.get_flag("verify") {
+        config.verify = true;
+        config.sources.set("verify", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("gzip") {
+        config.compression = qbak::CompressionMode::Gzip;
+        config.sources.set("compression", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("tar") {
+        config.archive = qbak::ArchiveMode::Tar;
+        config.sources.set("archive", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("zip") {
+        config.archive = qbak::ArchiveMode::Zip;
+        config.sources.set("archive", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("incremental") {
+        config.incremental = true;
+        config.sources.set("incremental", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("dedup") {
+        config.dedup = true;
+        config.sources.set("dedup", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("no-space-check") {
+        config.check_space = false;
+        config.sources.set("check_space", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("no-fsync") {
+        config.fsync = false;
+        config.sources.set("fsync", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("paranoid") {
+        config.fsync = true;
+        config.verify = true;
+        config.keep_partial_on_interrupt = true;
+        for key in ["fsync", "verify", "keep_partial_on_interrupt"] {
+            config.sources.set(key, ConfigSource::CliFlag);
+        }
+    }
+    if matches.get_flag("keep-partial") {
+        config.keep_partial_on_interrupt = true;
+        config
+            .sources
+            .set("keep_partial_on_interrupt", ConfigSource::CliFlag);
+    }
+    context.set_keep_partial(config.keep_partial_on_interrupt);
+    if matches.get_flag("recopy-changed") {
+        config.recopy_changed_files = true;
+        config
+            .sources
+            .set("recopy_changed_files", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("no-preserve-permissions") {
+        config.preserve_permissions = false;
+        config
+            .sources
+            .set("preserve_permissions", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("follow-symlinks") {
+        config.follow_symlinks = true;
+        config.sources.set("follow_symlinks", ConfigSource::CliFlag);
+    } else if matches.get_flag("no-follow-symlinks") {
+        config.follow_symlinks = false;
+        config.sources.set("follow_symlinks", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("dereference-top-level") {
+        config.dereference_top_level = true;
+        config
+            .sources
+            .set("dereference_top_level", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("hidden") {
+        config.include_hidden = true;
+        config.sources.set("include_hidden", ConfigSource::CliFlag);
+    } else if matches.get_flag("no-hidden") {
+        config.include_hidden = false;
+        config.sources.set("include_hidden", ConfigSource::CliFlag);
+    }
+    if let Some(size) = matches.get_one::<String>("max-file-size") {
+        config.max_file_size = Some(qbak::utils::parse_size(size)?);
+        config.sources.set("max_file_size", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("bytes") {
+        config.show_bytes = true;
+    }
+    if let Some(rate) = matches.get_one::<String>("rate-limit") {
+        config.rate_limit = Some(qbak::utils::parse_size(rate)?);
+        config.sources.set("rate_limit", ConfigSource::CliFlag);
+    }
+    if let Some(cutoff) = matches.get_one::<String>("newer-than") {
+        config.newer_than = Some(qbak::utils::parse_cutoff_time(cutoff)?);
+    }
+    if matches.get_flag("no-prescan") {
+        config.single_pass = true;
+        config.sources.set("single_pass", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("prune-empty") {
+        config.prune_empty_dirs = true;
+        config
+            .sources
+            .set("prune_empty_dirs", ConfigSource::CliFlag);
+    }
+    if let Some(keep) = matches.get_one::<usize>("keep") {
+        if *keep == 0 {
+            return Err(QbakError::validation("--keep must be at least 1"));
+        }
+        config.keep = Some(*keep);
+    }
+    if let Some(max_depth) = matches.get_one::<usize>("max-depth") {
+        config.max_depth = Some(*max_depth);
+        config.sources.set("max_depth", ConfigSource::CliFlag);
+    }
+    if let Some(output) = matches.get_one::<String>("output") {
+        config.output_dir = Some(absolute_dir(output)?);
+        config.sources.set("output_dir", ConfigSource::CliFlag);
+    }
+    if let Some(into) = matches.get_one::<String>("into") {
+        config.into_dir = Some(absolute_dir(into)?);
+        config.sources.set("into_dir", ConfigSource::CliFlag);
+    }
+    if let Some(format) = matches.get_one::<String>("timestamp-format") {
+        config.timestamp_format = format.clone();
+        config
+            .sources
+            .set("timestamp_format", ConfigSource::CliFlag);
+    }
+    if matches.get_flag("overwrite") {
+        config.overwrite = true;
+        config.sources.set("overwrite", ConfigSource::CliFlag);
+    }
+    if let Some(suffix) = matches.get_one::<String>("suffix") {
+        config.backup_suffix = suffix.clone();
+        config.sources.set("backup_suffix", ConfigSource::CliFlag);
+    }
+
+    // Apply include/exclude patterns from the command line
+    if let Some(patterns) = matches.get_many::<String>("include") {
+        config.include_patterns.extend(patterns.cloned());
+        config
+            .sources
+            .set("include_patterns", ConfigSource::CliFlag);
+    }
+    if let Some(patterns) = matches.get_many::<String>("exclude") {
+        config.exclude_patterns.extend(patterns.cloned());
+        config
+            .sources
+            .set("exclude_patterns", ConfigSource::CliFlag);
+    }
+    if let Some(files) = matches.get_many::<String>("exclude-from") {
+        for file in files {
+            let patterns = qbak::utils::read_exclude_file(Path::new(file))?;
+            config.exclude_patterns.extend(patterns);
+            config
+                .sources
+                .set("exclude_patterns", ConfigSource::CliFlag);
+        }
+    }
+    qbak::utils::validate_patterns(&config.include_patterns)?;
+    qbak::utils::validate_patterns(&config.exclude_patterns)?;
+
+    // Settings given as flags get the same checks as the config file
+    config.validate()?;
+    if let Some(base) = matches.get_one::<String>("dated-dir") {
+        let base = absolute_dir(base)?;
+        config.dated_dir = Some(qbak::generate_dated_dir(&base, &config));
+        config.sources.set("dated_dir", ConfigSource::CliFlag);
+    }
+    if verbose && !json && matches.contains_id("suffix") {
+        println!("Using backup suffix: {} (--suffix)", config.backup_suffix);
     }
 
     // Handle dump-config flag early
@@ -125,160 +713,1273 @@ fn run() -> Result<i32, QbakError> {
         return Ok(0);
     }
 
-    // Parse targets (only needed if not dumping config)
-    let targets: Vec<&str> = if let Some(target_values) = matches.get_many::<String>("targets") {
-        target_values.map(|s| s.as_str()).collect()
+    // Handle restore mode
+    if let Some(backup) = matches.get_one::<String>("restore") {
+        setup_signal_handlers(context);
+        let force = matches.get_flag("force");
+        if interactive {
+            process_restore(Path::new(backup), &config, true, force, quiet)?;
+            let question = format!("Restore {backup}?");
+            if !matches!(
+                confirm_on_stdin(&question),
+                Confirmation::Yes | Confirmation::All
+            ) {
+                return Ok(0);
+            }
+        }
+        process_restore(Path::new(backup), &config, dry_run, force, quiet)?;
+        return Ok(0);
+    }
+
+    // Check an existing backup against its source
+    if let Some(mut paths) = matches.get_many::<String>("verify-only") {
+        let (Some(backup), Some(source)) = (paths.next(), paths.next()) else {
+            return Err(QbakError::validation(
+                "--verify-only needs a BACKUP and a SOURCE",
+            ));
+        };
+        setup_signal_handlers(context);
+        let checksums = matches.get_flag("checksums");
+        let report =
+            qbak::verify_against_source(Path::new(backup), Path::new(source), &config, checksums)?;
+        print_verify_report(&report, json, quiet);
+        return Ok(if report.is_clean() { 0 } else { 1 });
+    }
+
+    // Finish a directory backup left incomplete by an interrupt
+    if let Some(mut paths) = matches.get_many::<String>("resume") {
+        let (Some(partial), Some(source)) = (paths.next(), paths.next()) else {
+            return Err(QbakError::validation(
+                "--resume needs a PARTIAL and a SOURCE",
+            ));
+        };
+        setup_signal_handlers(context);
+        let source = Path::new(source);
+        let result = qbak::resume_directory_backup(
+            Path::new(partial),
+            source,
+            &config,
+            force_progress || verbose,
+            quiet,
+        )?;
+        print_result(source, &config, &result, &[], verbose, quiet, json);
+        return Ok(0);
+    }
+
+    // Parse targets (only needed if not dumping config)
+    let mut targets: Vec<String> = Vec::new();
+    if let Some(target_values) = matches.get_many::<String>("targets") {
+        for target in target_values {
+            if target == "-" {
+                // Read newline-separated paths from stdin
+                let stdin = std::io::stdin();
+                targets.extend(read_target_list(stdin.lock(), false)?);
+            } else {
+                targets.push(target.clone());
+            }
+        }
+    }
+    if let Some(list_files) = matches.get_many::<String>("from-file") {
+        for list_file in list_files {
+            let file = std::fs::File::open(list_file).map_err(|e| {
+                QbakError::validation(format!("Could not read target list {list_file}: {e}"))
+            })?;
+            targets.extend(read_target_list(std::io::BufReader::new(file), true)?);
+        }
+    }
+    if !matches.contains_id("targets") && !matches.contains_id("from-file") {
+        return Err(QbakError::validation(
+            "No targets specified. Use --help for usage information.",
+        ));
+    }
+
+    // Stream a single file to stdout, keeping stdout free of any other output
+    if matches.get_flag("stdout") {
+        let [target] = targets.as_slice() else {
+            return Err(QbakError::validation(
+                "--stdout takes exactly one file target",
+            ));
+        };
+        setup_signal_handlers(context);
+        let mut stdout = std::io::stdout().lock();
+        qbak::backup_file_to_writer(
+            Path::new(target),
+            &config,
+            &mut stdout,
+            force_progress || verbose,
+            quiet,
+        )?;
+        return Ok(0);
+    }
+
+    // List existing backups instead of creating new ones
+    if matches.get_flag("list") {
+        for target_str in &targets {
+            process_list(Path::new(target_str), &config, targets.len() > 1, json)?;
+        }
+        return Ok(0);
+    }
+
+    // Set up signal handling for graceful cleanup
+    setup_signal_handlers(context);
+
+    if matches.get_flag("combine") {
+        let targets: Vec<PathBuf> = targets.iter().map(PathBuf::from).collect();
+        let options = (dry_run, verbose, quiet, force_progress, json);
+        process_combined(&targets, &config, options)?;
+        return Ok(0);
+    }
+
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let mut skipped_count = 0;
+    let mut ask_each = interactive;
+    let mut total_size = 0;
+    let start_time = std::time::Instant::now();
+
+    // With several targets, scan them all up front so one bar covers the whole command
+    let mut overall_progress = None;
+    let mut target_sizes = Vec::new();
+    if targets.len() > 1
+        && !dry_run
+        && !quiet
+        && config.progress.enabled
+        && config.into_dir.is_none()
+        && !interactive
+        && !targets
+            .iter()
+            .any(|target| qbak::uses_single_pass(Path::new(target), &config))
+    {
+        let scans = scan_targets(&targets, &config);
+        let file_count = scans.iter().map(|(files, _)| files).sum();
+        let total_size = scans.iter().map(|(_, size)| size).sum();
+        overall_progress = qbak::create_progress_bar(
+            &config.progress,
+            file_count,
+            total_size,
+            force_progress || verbose,
+        );
+        if let Some(ref mut prog) = overall_progress {
+            prog.start_scanning();
+            prog.finish_scanning(file_count, total_size);
+        }
+        target_sizes = scans.into_iter().map(|(_, size)| size).collect();
+    }
+
+    // Process each target
+    for (index, target_str) in targets.iter().enumerate() {
+        // Stop before starting the next target if a signal arrived in between
+        if qbak::signal::is_interrupted() {
+            finish_progress(&mut overall_progress);
+            return Err(QbakError::Interrupted);
+        }
+
+        let target_path = Path::new(target_str);
+
+        // Show what would happen and ask first
+        if ask_each {
+            let preview = process_target(target_path, &config, true, false, false, false, false);
+            let answer =
+                preview.map(|_| confirm_on_stdin(&format!("Back up {}?", target_path.display())));
+            match answer {
+                Ok(Confirmation::Yes) => {}
+                Ok(Confirmation::All) => ask_each = false,
+                Ok(Confirmation::No) => {
+                    skipped_count += 1;
+                    continue;
+                }
+                Ok(Confirmation::Quit) => {
+                    skipped_count += targets.len() - index;
+                    break;
+                }
+                Err(e) => {
+                    if !e.is_recoverable() {
+                        return Err(e);
+                    }
+                    error_count += 1;
+                    report_target_error(target_path, &e, verbose, quiet, false);
+                    continue;
+                }
+            }
+        }
+
+        let outcome = match overall_progress {
+            Some(ref mut prog) => {
+                let total_size = target_sizes.get(index).copied().unwrap_or(0);
+                process_target_with_shared_progress(
+                    target_path,
+                    total_size,
+                    &config,
+                    prog,
+                    verbose,
+                    quiet,
+                    json && !summary_only,
+                )
+            }
+            None => process_target(
+                target_path,
+                &config,
+                dry_run,
+                verbose,
+                quiet,
+                force_progress,
+                json && !summary_only,
+            ),
+        };
+
+        match outcome {
+            Ok(size) => {
+                success_count += 1;
+                total_size += size;
+            }
+            Err(e) => {
+                error_count += 1;
+
+                if !e.is_recoverable() {
+                    // For non-recoverable errors, fail immediately
+                    finish_progress(&mut overall_progress);
+                    if json {
+                        println!("{}", error_json(target_path, &e));
+                    }
+                    return Err(e);
+                }
+
+                // For recoverable errors, show error but continue
+                let report =
+                    || report_target_error(target_path, &e, verbose, quiet, json && !summary_only);
+                match overall_progress {
+                    Some(ref prog) => prog.suspend(report),
+                    None => report(),
+                }
+            }
+        }
+    }
+    finish_progress(&mut overall_progress);
+    let elapsed = start_time.elapsed();
+
+    // Summary
+    let counts = (success_count, error_count, skipped_count);
+    log_event(serde_json::json!({
+        "summary": summary_json(counts, total_size, elapsed, dry_run)
+    }));
+    if json {
+        let summary = summary_json(counts, total_size, elapsed, dry_run);
+        println!("{}", serde_json::json!({ "summary": summary }));
+    } else if summary_only
+        || (!quiet && (success_count > 1 || error_count > 0 || skipped_count > 0))
+    {
+        let mut line = format!("Backup summary: {success_count} succeeded, {error_count} failed");
+        if skipped_count > 0 {
+            line.push_str(&format!(", {skipped_count} skipped"));
+        }
+        if !dry_run {
+            line.push_str(&format!(
+                ", {}",
+                transfer_summary(total_size, elapsed, &config)
+            ));
+        }
+        println!("{line}");
+    }
+    if matches.get_flag("summary-json") {
+        let summary = summary_json(counts, total_size, elapsed, dry_run);
+        println!("QBAK_SUMMARY {summary}");
+    }
+
+    // Return appropriate exit code
+    if error_count > 0 {
+        Ok(1) // Any failures
+    } else {
+        Ok(0) // All succeeded
+    }
+}
+
+/// The final summary as JSON, from (succeeded, failed, skipped) target counts
+fn summary_json(
+    (succeeded, failed, skipped): (usize, usize, usize),
+    total_size: u64,
+    elapsed: std::time::Duration,
+    dry_run: bool,
+) -> serde_json::Value {
+    let mut summary = serde_json::json!({
+        "succeeded": succeeded,
+        "failed": failed,
+    });
+    if skipped > 0 {
+        summary["skipped"] = skipped.into();
+    }
+    if !dry_run {
+        summary["total_size"] = total_size.into();
+        summary["duration_secs"] = elapsed.as_secs_f64().into();
+        summary["bytes_per_sec"] = throughput(total_size, elapsed).into();
+    }
+    summary
+}
+
+/// Average bytes per second over `elapsed`, rounded down
+fn throughput(bytes: u64, elapsed: std::time::Duration) -> u64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        (bytes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
+
+/// Describe a transfer as e.g. `10.0 MB in 1.2s (8.3 MB/s)`
+fn transfer_summary(bytes: u64, elapsed: std::time::Duration, config: &qbak::Config) -> String {
+    let size = qbak::utils::format_size_for(bytes, config);
+    let rate = qbak::utils::format_size_for(throughput(bytes, elapsed), config);
+    format!("{size} in {:.1}s ({rate}/s)", elapsed.as_secs_f64())
+}
+
+/// Resolve a directory given on the command line against the current working directory
+fn absolute_dir(dir: &str) -> Result<PathBuf, QbakError> {
+    let dir = PathBuf::from(dir);
+    Ok(if dir.is_absolute() {
+        dir
+    } else {
+        std::env::current_dir()?.join(dir)
+    })
+}
+
+/// Read newline-separated target paths, skipping blank lines (and `#` comments if requested)
+///
+/// Paths are taken verbatim (no shell-style splitting); only the line ending is removed.
+fn read_target_list(reader: impl BufRead, skip_comments: bool) -> Result<Vec<String>, QbakError> {
+    let mut targets = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let path = line.strip_suffix('\r').unwrap_or(&line);
+        if path.trim().is_empty() || (skip_comments && path.trim_start().starts_with('#')) {
+            continue;
+        }
+        targets.push(path.to_string());
+    }
+    Ok(targets)
+}
+
+/// Back up (or preview) one target, returning the number of bytes backed up
+fn process_target(
+    target: &Path,
+    config: &qbak::Config,
+    dry_run: bool,
+    verbose: bool,
+    quiet: bool,
+    force_progress: bool,
+    json: bool,
+) -> Result<u64, QbakError> {
+    if let Some(ref into) = config.into_dir {
+        return process_target_into(target, into, config, dry_run, verbose, quiet, json);
+    }
+
+    if dry_run {
+        // Dry run mode - show what would be done, checking the destination like a real run
+        let backup_path = qbak::generate_backup_name(target, config)?;
+        let final_path = qbak::resolve_collision_with(target, &backup_path, config)?;
+        let replaces = config.overwrite && final_path.symlink_metadata().is_ok();
+        // A missing output directory would be created by the real run
+        let parent = final_path.parent().unwrap_or(Path::new(""));
+        if parent.as_os_str().is_empty() || parent.exists() {
+            if replaces {
+                qbak::utils::check_backup_dir_writable(&final_path)?;
+            } else {
+                qbak::validate_backup_filename(&final_path)?;
+            }
+        }
+        let collision = final_path != backup_path;
+
+        let totals = if target.is_dir() || target.is_symlink() {
+            // For directories, potentially show scanning progress in dry run; the scan also
+            // knows whether a symlink target would be preserved as a link
+            let should_show_progress =
+                config.progress.should_show_progress(0, 0, force_progress) && !quiet;
+            qbak::scan_directory(target, config, should_show_progress)?
+        } else {
+            qbak::ScanTotals {
+                files: 1,
+                size: qbak::calculate_size(target)?,
+                skipped: 0,
+            }
+        };
+        let pruned = prune_backups(target, config, None, true)?;
+
+        if json {
+            let mut result = qbak::BackupResult::new(target.to_path_buf(), final_path);
+            result.files_processed = totals.files;
+            result.total_size = totals.size;
+            let mut value = result_json(&result, true);
+            if collision {
+                value["collision"] = serde_json::Value::Bool(true);
+            }
+            if replaces {
+                value["overwrites"] = serde_json::Value::Bool(true);
+            }
+            if totals.skipped > 0 {
+                value["skipped"] = totals.skipped.into();
+            }
+            println!("{}", with_pruned(value, &pruned));
+        } else {
+            let size_str = console::style(qbak::utils::format_size_for(totals.size, config)).dim();
+            if target.is_dir() {
+                println!(
+                    "Would create backup: {} ({} files, {size_str})",
+                    final_path.display(),
+                    totals.files
+                );
+            } else {
+                println!("Would create backup: {} ({size_str})", final_path.display());
+            }
+            if collision {
+                println!(
+                    "  {} already exists; a counter would be appended",
+                    backup_path.display()
+                );
+            }
+            if replaces {
+                println!(
+                    "  {} already exists and would be replaced",
+                    final_path.display()
+                );
+            }
+            if totals.skipped > 0 {
+                println!("  {} entries would be skipped", totals.skipped);
+            }
+            print_pruned(&pruned, true);
+        }
+        return Ok(0);
+    }
+
+    // Perform the actual backup
+    let result = if target.is_dir() {
+        qbak::backup_directory_with_progress(target, config, force_progress || verbose, quiet)?
     } else {
-        return Err(QbakError::validation(
-            "No targets specified. Use --help for usage information.",
-        ));
+        backup_file_with_progress(target, config, force_progress || verbose, quiet)?
     };
+    let pruned = prune_backups(target, config, Some(&result.backup_path), false)?;
 
-    // Set up signal handling for graceful cleanup
-    setup_signal_handlers();
-
-    let mut success_count = 0;
-    let mut error_count = 0;
-
-    // Process each target
-    for target_str in targets {
-        let target_path = Path::new(target_str);
+    print_result(target, config, &result, &pruned, verbose, quiet, json);
 
-        match process_target(
-            target_path,
-            &config,
-            dry_run,
-            verbose,
-            quiet,
-            force_progress,
-        ) {
-            Ok(_) => success_count += 1,
-            Err(e) => {
-                error_count += 1;
+    Ok(result.total_size)
+}
 
-                if e.is_recoverable() {
-                    // For recoverable errors, show error but continue
-                    if !quiet {
-                        eprintln!("Error processing {target_str}: {e}");
-
-                        let suggestions = e.suggestions();
-                        if !suggestions.is_empty() && verbose {
-                            eprintln!("Suggestions:");
-                            for suggestion in suggestions {
-                                eprintln!("  - {suggestion}");
-                            }
-                        }
-                    }
-                } else {
-                    // For non-recoverable errors, fail immediately
-                    return Err(e);
-                }
-            }
+/// Back up `target` with `--into`, one timestamped backup per file inside `into`
+fn process_target_into(
+    target: &Path,
+    into: &Path,
+    config: &qbak::Config,
+    dry_run: bool,
+    verbose: bool,
+    quiet: bool,
+    json: bool,
+) -> Result<u64, QbakError> {
+    if dry_run {
+        qbak::utils::validate_backup_location(target, into)?;
+        let totals = qbak::scan_directory(target, config, false)?;
+        if json {
+            let mut result = qbak::BackupResult::new(target.to_path_buf(), into.to_path_buf());
+            result.files_processed = totals.files;
+            result.total_size = totals.size;
+            println!("{}", result_json(&result, true));
+        } else {
+            let size_str = console::style(qbak::utils::format_size_for(totals.size, config)).dim();
+            println!(
+                "Would back up {} files ({size_str}) into {}",
+                totals.files,
+                into.display()
+            );
         }
+        return Ok(0);
     }
 
-    // Summary
-    if !quiet && (success_count > 1 || error_count > 0) {
-        println!("Backup summary: {success_count} succeeded, {error_count} failed");
+    let result = qbak::backup_into(target, into, config)?;
+    print_result(target, config, &result, &[], verbose, quiet, json);
+    Ok(result.total_size)
+}
+
+/// Answer to an `--interactive` prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Confirmation {
+    Yes,
+    No,
+    /// Yes to this and every remaining target
+    All,
+    /// No to this and every remaining target
+    Quit,
+}
+
+/// Ask `question` on stderr and read a y/n/a/q answer from `input`
+///
+/// Anything but a recognized answer, including end of input, counts as no.
+fn confirm(question: &str, input: &mut impl BufRead) -> Confirmation {
+    eprint!("{question} [y/n/a/q] ");
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer).is_err() {
+        return Confirmation::No;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Confirmation::Yes,
+        "a" | "all" => Confirmation::All,
+        "q" | "quit" => Confirmation::Quit,
+        _ => Confirmation::No,
     }
+}
 
-    // Return appropriate exit code
-    if error_count > 0 {
-        Ok(1) // Any failures
-    } else {
-        Ok(0) // All succeeded
+/// Ask on the terminal, answering no without asking when stdin is not a terminal
+fn confirm_on_stdin(question: &str) -> Confirmation {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        eprintln!("{question} no (stdin is not a terminal)");
+        return Confirmation::No;
     }
+    confirm(question, &mut stdin.lock())
 }
 
-fn process_target(
-    target: &Path,
+/// Back up all targets into a single archive with `--combine`
+///
+/// `options` holds the `dry_run`, `verbose`, `quiet`, `force_progress` and `json` flags.
+fn process_combined(
+    targets: &[PathBuf],
     config: &qbak::Config,
-    dry_run: bool,
-    verbose: bool,
-    quiet: bool,
-    force_progress: bool,
+    options: (bool, bool, bool, bool, bool),
 ) -> Result<(), QbakError> {
-    if dry_run {
-        // Dry run mode - just show what would be done
-        let backup_path = qbak::generate_backup_name(target, config)?;
-        let final_path = qbak::resolve_collision(&backup_path)?;
+    let (dry_run, verbose, quiet, force_progress, json) = options;
+    if config.archive == qbak::ArchiveMode::None {
+        return Err(QbakError::validation(
+            "Combining targets needs an archive format (--tar or --zip)",
+        ));
+    }
 
-        if target.is_dir() {
-            // For directories, potentially show scanning progress in dry run
-            let should_show_progress =
-                config.progress.should_show_progress(0, 0, force_progress) && !quiet;
-            let (file_count, total_size) = if should_show_progress {
-                qbak::count_files_and_size_with_progress(target, config)?
-            } else {
-                qbak::count_files_and_size(target, config)?
-            };
-            let size_str = qbak::utils::format_size(total_size);
+    let target_names: Vec<String> = targets.iter().map(|t| t.display().to_string()).collect();
+    let scans = scan_targets(&target_names, config);
+    let file_count = scans.iter().map(|(files, _)| files).sum();
+    let total_size = scans.iter().map(|(_, size)| size).sum();
+
+    if dry_run {
+        let backup_path = qbak::generate_combined_backup_name(config)?;
+        let final_path = qbak::resolve_combined_collision(&backup_path, config)?;
+        if json {
+            let mut result = qbak::BackupResult::new(
+                PathBuf::from(qbak::naming::COMBINED_BACKUP_STEM),
+                final_path,
+            );
+            result.files_processed = file_count;
+            result.total_size = total_size;
+            println!("{}", result_json(&result, true));
+        } else {
+            let size_str = console::style(qbak::utils::format_size_for(total_size, config)).dim();
             println!(
-                "Would create backup: {} ({} files, {size_str})",
+                "Would create backup: {} ({} targets, {file_count} files, {size_str})",
                 final_path.display(),
-                file_count
+                targets.len()
             );
-        } else {
-            let size = qbak::calculate_size(target)?;
-            let size_str = qbak::utils::format_size(size);
-            println!("Would create backup: {} ({size_str})", final_path.display());
         }
         return Ok(());
     }
 
-    // Perform the actual backup
+    let mut progress = if quiet {
+        None
+    } else {
+        qbak::create_progress_bar(
+            &config.progress,
+            file_count,
+            total_size,
+            force_progress || verbose,
+        )
+    };
+    if let Some(ref mut prog) = progress {
+        prog.start_scanning();
+        prog.finish_scanning(file_count, total_size);
+    }
+    let result = qbak::backup_combined(targets, config, progress.as_mut());
+    finish_progress(&mut progress);
+
+    let result = result?;
+    print_result(
+        &result.source_path,
+        config,
+        &result,
+        &[],
+        verbose,
+        quiet,
+        json,
+    );
+    Ok(())
+}
+
+/// Back up one of several targets while `progress` tracks the whole command
+fn process_target_with_shared_progress(
+    target: &Path,
+    total_size: u64,
+    config: &qbak::Config,
+    progress: &mut qbak::BackupProgress,
+    verbose: bool,
+    quiet: bool,
+    json: bool,
+) -> Result<u64, QbakError> {
     let result = if target.is_dir() {
-        qbak::backup_directory_with_progress(target, config, force_progress || verbose, quiet)?
+        qbak::backup_directory_with_shared_progress(target, config, total_size, progress)?
+    } else {
+        qbak::backup_file_with_shared_progress(target, config, progress)?
+    };
+    let pruned = prune_backups(target, config, Some(&result.backup_path), false)?;
+
+    progress.suspend(|| print_result(target, config, &result, &pruned, verbose, quiet, json));
+
+    Ok(result.total_size)
+}
+
+/// Pre-scan every target, returning its file count and total size
+///
+/// Targets that can't be scanned count as empty; the error surfaces when they're processed.
+fn scan_targets(targets: &[String], config: &qbak::Config) -> Vec<(usize, u64)> {
+    targets
+        .iter()
+        .map(|target| {
+            let path = Path::new(target);
+            if path.is_dir() {
+                qbak::count_files_and_size(path, config).unwrap_or((0, 0))
+            } else {
+                qbak::calculate_size(path).map_or((0, 0), |size| (1, size))
+            }
+        })
+        .collect()
+}
+
+fn finish_progress(progress: &mut Option<qbak::BackupProgress>) {
+    if let Some(ref mut prog) = progress {
+        prog.finish();
+    }
+}
+
+/// Enable or disable colored output for `--color`
+///
+/// `auto` colors only when stdout is a terminal and `NO_COLOR` is unset; `--json`
+/// output is never colored.
+fn setup_colors(when: &str, json: bool) {
+    let enabled = !json
+        && match when {
+            "always" => true,
+            "never" => false,
+            _ => {
+                std::env::var_os("NO_COLOR").map_or(true, |value| value.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+        };
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+/// Report a recoverable error for a single target
+fn report_target_error(target: &Path, error: &QbakError, verbose: bool, quiet: bool, json: bool) {
+    log_event(error_json(target, error));
+    if json {
+        println!("{}", error_json(target, error));
+    } else if !quiet {
+        let label = console::style("Error processing").red().for_stderr();
+        eprintln!("{label} {}: {error}", target.display());
+
+        let suggestions = error.suggestions();
+        if !suggestions.is_empty() && verbose {
+            eprintln!("Suggestions:");
+            for suggestion in suggestions {
+                eprintln!("  - {suggestion}");
+            }
+        }
+    }
+}
+
+/// Apply `--keep` to the backups of `target`, returning the paths removed
+///
+/// `created` is the backup just made, which is always kept. In a dry run nothing is deleted;
+/// the backup that would have been created takes one of the kept slots, so the returned paths
+/// are the ones a real run would remove.
+fn prune_backups(
+    target: &Path,
+    config: &qbak::Config,
+    created: Option<&Path>,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>, QbakError> {
+    match config.keep {
+        Some(keep) if dry_run => qbak::prune_old_backups(target, config, keep - 1, None, true),
+        Some(keep) => qbak::prune_old_backups(target, config, keep, created, false),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn print_pruned(pruned: &[PathBuf], dry_run: bool) {
+    let action = if dry_run {
+        "Would remove old backup"
     } else {
-        backup_file(target, config)?
+        "Removed old backup"
     };
+    for path in pruned {
+        println!("{action}: {}", path.display());
+    }
+}
 
-    // Output results based on verbosity
+/// Print the outcome of a successful backup
+fn print_result(
+    target: &Path,
+    config: &qbak::Config,
+    result: &qbak::BackupResult,
+    pruned: &[PathBuf],
+    verbose: bool,
+    quiet: bool,
+    json: bool,
+) {
+    log_event(with_pruned(result_json(result, false), pruned));
+    if json {
+        println!("{}", with_pruned(result_json(result, false), pruned));
+        return;
+    }
     if verbose {
         println!("Processed: {}", target.display());
         println!("  → {}", result.backup_path.display());
         let files = result.files_processed;
-        let size_str = qbak::utils::format_size(result.total_size);
+        let size_str = console::style(result.size_with(config)).dim();
         let duration = result.duration.as_secs_f64();
         println!("  Files: {files}");
         println!("  Size: {size_str}");
+        if config.verify {
+            let verified = result.files_verified;
+            println!("  Verified: {verified}");
+        }
+        if config.incremental || config.dedup || config.preserve_hardlinks {
+            let copied = result.files_copied;
+            let linked = result.files_linked;
+            println!("  Copied: {copied}, Linked: {linked}");
+        }
+        if config.dedup {
+            let saved = qbak::utils::format_size_for(result.bytes_deduplicated, config);
+            println!("  Deduplicated: {saved}");
+        }
+        if config.preserve_hardlinks {
+            let saved = qbak::utils::format_size_for(result.bytes_hardlinked, config);
+            println!("  Hardlinks preserved: {saved}");
+        }
         println!("  Duration: {duration:.2}s");
     } else if !quiet {
-        let summary = result.summary();
+        let summary = result.summary_with(config);
         println!("{summary}");
     }
+    if !quiet {
+        print_pruned(pruned, false);
+        print_skipped(result, verbose);
+        print_changed(result);
+    }
+}
+
+/// Warn about source files that changed while they were being copied
+fn print_changed(result: &qbak::BackupResult) {
+    for path in &result.changed {
+        eprintln!(
+            "Warning: {} changed while it was backed up; the backup may not match it",
+            path.display()
+        );
+    }
+}
+
+/// Warn about entries a directory backup left out, listing each one when verbose
+fn print_skipped(result: &qbak::BackupResult, verbose: bool) {
+    let Some(summary) = result.skipped_summary() else {
+        return;
+    };
+    eprintln!("Warning: {summary}");
+    if verbose {
+        for (path, reason) in &result.skipped {
+            eprintln!("  {} ({})", path.display(), reason.as_str());
+        }
+    }
+}
 
+/// Open the `--log` file for appending, creating it if needed
+fn open_run_log(path: &Path) -> Result<(), QbakError> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| {
+            QbakError::validation(format!("Could not open log file {}: {e}", path.display()))
+        })?;
+    let _ = RUN_LOG.set(Mutex::new(file));
     Ok(())
 }
 
-fn setup_signal_handlers() {
-    // Set up signal handlers for graceful cleanup
-    #[cfg(unix)]
-    {
-        use std::sync::atomic::Ordering;
+/// Append `event` to the `--log` file, if any, stamped with the local time
+///
+/// Events have the same shape as the `--json` output. A failing log never fails the
+/// backup; it is reported on stderr instead.
+fn log_event(event: serde_json::Value) {
+    let Some(log) = RUN_LOG.get() else {
+        return;
+    };
+    let mut file = log.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Err(e) = writeln!(file, "{}", log_line(event)) {
+        eprintln!("Warning: could not write to log file: {e}");
+    }
+}
 
-        // Create a new backup context for this qbak instance
-        let context = qbak::signal::BackupContext::new();
-        let interrupt_flag = context.interrupt_flag();
+/// Format `event` as a `--log` line stamped with the local time
+fn log_line(mut event: serde_json::Value) -> String {
+    event["time"] = chrono::Local::now().to_rfc3339().into();
+    event.to_string()
+}
+
+/// Build the `--json` object describing a backup result
+fn result_json(result: &qbak::BackupResult, dry_run: bool) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "ok": true,
+        "source": result.source_path.display().to_string(),
+        "backup_path": result.backup_path.display().to_string(),
+        "files_processed": result.files_processed,
+        "total_size": result.total_size,
+        "duration_secs": result.duration.as_secs_f64(),
+    });
+    if result.bytes_deduplicated > 0 {
+        value["bytes_deduplicated"] = result.bytes_deduplicated.into();
+    }
+    if result.bytes_hardlinked > 0 {
+        value["bytes_hardlinked"] = result.bytes_hardlinked.into();
+    }
+    if let Some(stored_size) = result.stored_size {
+        value["stored_size"] = stored_size.into();
+    }
+    if !result.skipped.is_empty() {
+        value["skipped"] = result.skipped.len().into();
+    }
+    if !result.changed.is_empty() {
+        value["changed"] = result.changed.len().into();
+    }
+    if dry_run {
+        value["dry_run"] = serde_json::Value::Bool(true);
+    }
+    value
+}
+
+/// Add the backups removed by `--keep` to a `--json` result object
+fn with_pruned(mut value: serde_json::Value, pruned: &[PathBuf]) -> serde_json::Value {
+    if !pruned.is_empty() {
+        let paths: Vec<String> = pruned
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        value["pruned"] = serde_json::json!(paths);
+    }
+    value
+}
+
+/// Build the `--json` object describing a failed target
+fn error_json(target: &Path, error: &QbakError) -> serde_json::Value {
+    serde_json::json!({
+        "ok": false,
+        "source": target.display().to_string(),
+        "error": error.kind(),
+        "message": error.to_string(),
+    })
+}
+
+/// Print the existing backups of `target`, newest first
+fn process_list(
+    target: &Path,
+    config: &qbak::Config,
+    show_header: bool,
+    json: bool,
+) -> Result<(), QbakError> {
+    let backups = qbak::find_backups(target, config)?;
 
-        ctrlc::set_handler(move || {
-            interrupt_flag.store(true, Ordering::SeqCst);
-            eprintln!("\nInterrupted by user.");
+    if json {
+        println!("{}", list_json(target, &backups));
+        return Ok(());
+    }
+
+    if show_header {
+        println!("{}:", target.display());
+    }
+    if backups.is_empty() {
+        println!("No backups found for {}", target.display());
+    }
+    for backup in &backups {
+        let timestamp = backup.timestamp.format("%Y-%m-%d %H:%M:%S");
+        let size = qbak::utils::format_size_for(backup.size, config);
+        let size = console::style(format!("{size:>10}")).dim();
+        println!("{timestamp}  {size}  {}", backup.path.display());
+    }
+    Ok(())
+}
+
+/// Build the `--list --json` object describing the backups of a target
+fn list_json(target: &Path, backups: &[qbak::BackupInfo]) -> serde_json::Value {
+    let entries: Vec<serde_json::Value> = backups
+        .iter()
+        .map(|backup| {
+            serde_json::json!({
+                "path": backup.path.display().to_string(),
+                "timestamp": backup.timestamp.to_rfc3339(),
+                "size": backup.size,
+                "is_dir": backup.is_dir,
+            })
         })
-        .expect("Error setting Ctrl-C handler");
+        .collect();
+    serde_json::json!({
+        "source": target.display().to_string(),
+        "backups": entries,
+    })
+}
+
+fn process_restore(
+    backup: &Path,
+    config: &qbak::Config,
+    dry_run: bool,
+    force: bool,
+    quiet: bool,
+) -> Result<(), QbakError> {
+    if dry_run {
+        let target = qbak::backup::restore_target_path(backup, config)?;
+        let overwrite = if target.exists() {
+            " (overwriting existing)"
+        } else {
+            ""
+        };
+        println!(
+            "Would restore: {} → {}{overwrite}",
+            backup.display(),
+            target.display()
+        );
+        return Ok(());
+    }
+
+    let result = qbak::restore_backup(backup, config, force)?;
+
+    if !quiet {
+        println!(
+            "Restored: {} → {}",
+            result.source_path.display(),
+            result.backup_path.display()
+        );
+        print_changed(&result);
+    }
+
+    Ok(())
+}
 
-        // Set the global context for this qbak instance
-        qbak::signal::set_global_context(context);
+/// Print the outcome of `--verify-only`
+fn print_verify_report(report: &qbak::VerifyReport, json: bool, quiet: bool) {
+    if json {
+        let discrepancies: Vec<String> = report
+            .discrepancies
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let value = serde_json::json!({
+            "ok": report.is_clean(),
+            "files_checked": report.files_checked,
+            "discrepancies": discrepancies,
+        });
+        println!("{value}");
+        return;
+    }
+    for discrepancy in &report.discrepancies {
+        println!("{discrepancy}");
+    }
+    if !quiet {
+        let checked = report.files_checked;
+        match report.discrepancies.len() {
+            0 => println!("Backup matches source ({checked} files checked)"),
+            found => println!("Found {found} discrepancies ({checked} files checked)"),
+        }
     }
 }
 
+fn setup_signal_handlers(context: &qbak::signal::BackupContext) {
+    // Set up signal handlers (SIGINT, SIGTERM, SIGHUP) for graceful cleanup
+    context
+        .install_signal_handlers()
+        .expect("Error setting signal handlers");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use std::fs::{self, File};
     use std::io::Write;
     use tempfile::tempdir;
 
+    static ENV_MUTEX: Mutex<()> = Mutex::new(());
+
+    /// Run qbak with `args` and the config file at `config_path`
+    fn run_with_config(config_path: &Path, args: &[&str]) -> Result<i32, QbakError> {
+        let _guard = ENV_MUTEX.lock().unwrap(); // Serialize environment access
+        let original = std::env::var_os(qbak::config::CONFIG_PATH_ENV);
+        std::env::set_var(qbak::config::CONFIG_PATH_ENV, config_path);
+
+        let context = qbak::signal::BackupContext::new();
+        let result = context.scope(|| {
+            run(
+                std::iter::once("qbak").chain(args.iter().copied()),
+                &context,
+            )
+        });
+
+        match original {
+            Some(value) => std::env::set_var(qbak::config::CONFIG_PATH_ENV, value),
+            None => std::env::remove_var(qbak::config::CONFIG_PATH_ENV),
+        }
+        result
+    }
+
+    #[test]
+    fn test_run_rejects_unsafe_backup_suffix() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("server.log");
+        fs::write(&source, "data").unwrap();
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+
+        for suffix in ["", "\t", "../up"] {
+            let config_path = dir.path().join("config.ini");
+            fs::write(
+                &config_path,
+                format!(
+                    "[qbak]\nbackup_suffix = {suffix}\noutput_dir = {}\n",
+                    out.display()
+                ),
+            )
+            .unwrap();
+
+            let result = run_with_config(&config_path, &[source.to_str().unwrap()]);
+            assert!(
+                matches!(result, Err(QbakError::Config { .. })),
+                "suffix {suffix:?} was accepted: {result:?}"
+            );
+            // Nothing was backed up, neither with the defaults nor into output_dir
+            assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+            assert_eq!(fs::read_dir(&out).unwrap().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_into_inside_source_rejected() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("main.rs"), "fn main() {}").unwrap();
+
+        // `--into src/out src`, with and without `--dry-run`
+        let into = source.join("out");
+        let config = qbak::Config::default();
+        for dry_run in [true, false] {
+            let result = process_target_into(&source, &into, &config, dry_run, false, true, false);
+            assert!(matches!(result, Err(QbakError::Validation { .. })));
+        }
+        assert_eq!(fs::read_dir(&source).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_run_rejects_missing_qbak_config() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("notes.txt");
+        fs::write(&source, "data").unwrap();
+
+        let result = run_with_config(&dir.path().join("missing.ini"), &[source.to_str().unwrap()]);
+        let error = result.unwrap_err();
+        assert!(matches!(error, QbakError::Config { .. }));
+        assert!(error.to_string().contains("missing.ini"));
+        assert_eq!(error.exit_code(), 2);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_read_target_list() {
+        let input = "first.txt\n\nwith spaces.txt\r\n  leading space.txt\n   \nlast.txt";
+        let targets = read_target_list(input.as_bytes(), false).unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                "first.txt",
+                "with spaces.txt",
+                "  leading space.txt",
+                "last.txt"
+            ]
+        );
+
+        // Comments are only skipped for list files
+        let input = "# important files\nconfig.ini\n  # indented comment\n#notes.txt\n";
+        assert_eq!(
+            read_target_list(input.as_bytes(), true).unwrap(),
+            vec!["config.ini"]
+        );
+        assert_eq!(read_target_list(input.as_bytes(), false).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_confirm_answers() {
+        let answer = |input: &str| confirm("Back up notes.txt?", &mut input.as_bytes());
+        assert_eq!(answer("y\n"), Confirmation::Yes);
+        assert_eq!(answer(" YES \n"), Confirmation::Yes);
+        assert_eq!(answer("a\n"), Confirmation::All);
+        assert_eq!(answer("q\n"), Confirmation::Quit);
+        assert_eq!(answer("n\n"), Confirmation::No);
+
+        // Anything unexpected is a safe no
+        assert_eq!(answer("maybe\n"), Confirmation::No);
+        assert_eq!(answer("\n"), Confirmation::No);
+        assert_eq!(answer(""), Confirmation::No);
+    }
+
+    #[test]
+    fn test_shared_progress_across_targets() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("single.txt");
+        let dir = temp_dir.path().join("tree");
+        std::fs::write(&file, "hello").unwrap();
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "aaa").unwrap();
+        std::fs::write(dir.join("b.txt"), "bb").unwrap();
+
+        let config = qbak::Config::default();
+        let targets = vec![
+            file.display().to_string(),
+            dir.display().to_string(),
+            temp_dir.path().join("missing").display().to_string(),
+        ];
+        let scans = scan_targets(&targets, &config);
+        assert_eq!(scans, vec![(1, 5), (2, 5), (0, 0)]);
+
+        let mut progress = qbak::BackupProgress::new(config.progress.clone());
+        progress.start_scanning();
+        progress.finish_scanning(3, 10);
+        process_target_with_shared_progress(&file, 5, &config, &mut progress, false, true, false)
+            .unwrap();
+        process_target_with_shared_progress(&dir, 5, &config, &mut progress, false, true, false)
+            .unwrap();
+        progress.finish();
+    }
+
+    #[test]
+    fn test_transfer_summary() {
+        use std::time::Duration;
+
+        let config = qbak::default_config();
+        assert_eq!(
+            transfer_summary(10 * 1024 * 1024, Duration::from_secs(4), &config),
+            "10.0 MB in 4.0s (2.5 MB/s)"
+        );
+        assert_eq!(throughput(1024, Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn test_summary_json() {
+        use std::time::Duration;
+
+        let summary = summary_json((2, 1, 0), 4096, Duration::from_secs(2), false);
+        assert_eq!(summary["succeeded"], 2);
+        assert_eq!(summary["failed"], 1);
+        assert!(summary.get("skipped").is_none());
+        assert_eq!(summary["total_size"], 4096);
+        assert_eq!(summary["duration_secs"], 2.0);
+        assert_eq!(summary["bytes_per_sec"], 2048);
+
+        // A dry run transfers nothing, so only the counts are reported
+        let summary = summary_json((1, 0, 3), 0, Duration::ZERO, true);
+        assert_eq!(summary["skipped"], 3);
+        assert!(summary.get("total_size").is_none());
+    }
+
+    #[test]
+    fn test_json_output_objects() {
+        let mut result =
+            qbak::BackupResult::new(PathBuf::from("a.txt"), PathBuf::from("a-qbak.txt"));
+        result.files_processed = 1;
+        result.total_size = 42;
+
+        let value = result_json(&result, false);
+        assert_eq!(value["ok"], true);
+        assert_eq!(value["source"], "a.txt");
+        assert_eq!(value["backup_path"], "a-qbak.txt");
+        assert_eq!(value["files_processed"], 1);
+        assert_eq!(value["total_size"], 42);
+        assert!(value["duration_secs"].is_number());
+        assert!(value.get("dry_run").is_none());
+        assert_eq!(result_json(&result, true)["dry_run"], true);
+
+        let error = QbakError::SourceNotFound {
+            path: PathBuf::from("missing.txt"),
+        };
+        let value = error_json(Path::new("missing.txt"), &error);
+        assert_eq!(value["ok"], false);
+        assert_eq!(value["error"], "SourceNotFound");
+        assert_eq!(value["source"], "missing.txt");
+    }
+
+    #[test]
+    fn test_log_line() {
+        let error = QbakError::validation("bad input");
+        let line = log_line(error_json(Path::new("missing.txt"), &error));
+        assert!(!line.contains('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["ok"], false);
+        assert_eq!(value["source"], "missing.txt");
+        let time = value["time"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(time).is_ok());
+    }
+
+    #[test]
+    fn test_prune_backups_keep() {
+        let temp_dir = tempdir().unwrap();
+        let source = temp_dir.path().join("app.log");
+        std::fs::write(&source, "log").unwrap();
+        for name in [
+            "app-20250101T120000-qbak.log",
+            "app-20250201T120000-qbak.log",
+        ] {
+            std::fs::write(temp_dir.path().join(name), "old").unwrap();
+        }
+
+        let mut config = qbak::default_config();
+        assert!(prune_backups(&source, &config, None, false)
+            .unwrap()
+            .is_empty());
+
+        // A dry run counts the backup it would have created towards --keep
+        config.keep = Some(2);
+        let would_remove = prune_backups(&source, &config, None, true).unwrap();
+        assert_eq!(
+            would_remove,
+            vec![temp_dir.path().join("app-20250101T120000-qbak.log")]
+        );
+        assert!(would_remove[0].exists());
+
+        assert!(prune_backups(&source, &config, None, false)
+            .unwrap()
+            .is_empty());
+        config.keep = Some(1);
+        let removed = prune_backups(&source, &config, None, false).unwrap();
+        assert_eq!(removed, would_remove);
+        assert!(!removed[0].exists());
+    }
+
+    #[test]
+    fn test_list_json() {
+        let temp_dir = tempdir().unwrap();
+        let source = temp_dir.path().join("notes.md");
+        let backup = temp_dir.path().join("notes-20250603T145231-qbak.md");
+        std::fs::write(&backup, "# Notes").unwrap();
+
+        let backups = qbak::find_backups(&source, &qbak::default_config()).unwrap();
+        let value = list_json(&source, &backups);
+        assert_eq!(value["source"], source.display().to_string());
+        assert_eq!(value["backups"][0]["path"], backup.display().to_string());
+        assert_eq!(
+            value["backups"][0]["timestamp"],
+            "2025-06-03T14:52:31+00:00"
+        );
+        assert_eq!(value["backups"][0]["size"], 7);
+        assert_eq!(value["backups"][0]["is_dir"], false);
+    }
+
     #[test]
     fn test_process_target_file() {
         let dir = tempdir().unwrap();
@@ -286,7 +1987,7 @@ mod tests {
         File::create(&source_path).unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -297,7 +1998,7 @@ mod tests {
         File::create(&source_path).unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, true, false, false, false);
+        let result = process_target(&source_path, &config, true, false, false, false, false);
         assert!(result.is_ok());
 
         // In dry run mode, no backup should be created
@@ -311,7 +2012,7 @@ mod tests {
         let source_path = dir.path().join("nonexistent.txt");
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
 
         assert!(result.is_err());
         match result.unwrap_err() {
@@ -330,7 +2031,7 @@ mod tests {
         std::fs::write(source_dir.join("file.txt"), "content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_dir, &config, false, false, true, false);
+        let result = process_target(&source_dir, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -344,7 +2045,7 @@ mod tests {
 
         let config = qbak::default_config();
         // Test verbose mode (should not panic or error)
-        let result = process_target(&source_path, &config, false, true, false, false);
+        let result = process_target(&source_path, &config, false, true, false, false, false);
         assert!(result.is_ok());
     }
 
@@ -356,7 +2057,7 @@ mod tests {
         std::fs::write(source_dir.join("file.txt"), "content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_dir, &config, true, false, false, false);
+        let result = process_target(&source_dir, &config, true, false, false, false, false);
         assert!(result.is_ok());
 
         // Verify no backup was actually created
@@ -372,7 +2073,7 @@ mod tests {
 
         let config = qbak::default_config();
         // Test quiet mode
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -386,7 +2087,7 @@ mod tests {
         config.backup_suffix = "custom".to_string();
         config.preserve_permissions = false;
 
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -400,7 +2101,7 @@ mod tests {
         std::fs::write(&source_path, content).unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, true, false, false);
+        let result = process_target(&source_path, &config, false, true, false, false, false);
         assert!(result.is_ok());
     }
 
@@ -411,7 +2112,7 @@ mod tests {
         File::create(&source_path).unwrap(); // Creates empty file
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, false, false);
+        let result = process_target(&source_path, &config, false, false, false, false, false);
         assert!(result.is_ok());
     }
 
@@ -422,7 +2123,7 @@ mod tests {
         std::fs::write(&source_path, "content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -433,10 +2134,32 @@ mod tests {
         std::fs::write(&source_path, "unicode content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_process_target_long_unicode_filename_narrow_terminal() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("данные");
+        std::fs::create_dir(&source_dir).unwrap();
+        let source_path = source_dir.join("очень_длинное_имя_файла.txt");
+        std::fs::write(&source_path, "unicode content").unwrap();
+
+        // Progress messages truncate the name to fit a 40-column terminal (the truncated text
+        // itself is checked in the progress module's tests); the backups must not panic
+        let config = qbak::Config {
+            progress: qbak::ProgressConfig {
+                terminal_width: 40,
+                is_interactive: true,
+                ..qbak::ProgressConfig::default()
+            },
+            ..qbak::default_config()
+        };
+        assert!(process_target(&source_path, &config, false, false, false, true, false).is_ok());
+        assert!(process_target(&source_dir, &config, false, false, false, true, false).is_ok());
+    }
+
     #[test]
     fn test_process_target_no_extension() {
         let dir = tempdir().unwrap();
@@ -444,7 +2167,7 @@ mod tests {
         std::fs::write(&source_path, "readme content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -455,7 +2178,7 @@ mod tests {
         std::fs::write(&source_path, "archive content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
@@ -466,10 +2189,54 @@ mod tests {
         std::fs::write(&source_path, "hidden content").unwrap();
 
         let config = qbak::default_config();
-        let result = process_target(&source_path, &config, false, false, true, false);
+        let result = process_target(&source_path, &config, false, false, true, false, false);
         assert!(result.is_ok());
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_process_target_symlink_arguments() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "notes").unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs").join("a.md"), "a").unwrap();
+        let file_link = dir.path().join("latest.txt");
+        let dir_link = dir.path().join("latest");
+        std::os::unix::fs::symlink("notes.txt", &file_link).unwrap();
+        std::os::unix::fs::symlink("docs", &dir_link).unwrap();
+
+        let backups = |follow_symlinks: bool| {
+            let config = qbak::Config {
+                follow_symlinks,
+                ..qbak::default_config()
+            };
+            for target in [&file_link, &dir_link] {
+                process_target(target, &config, false, false, true, false, false).unwrap();
+            }
+            let mut backups: Vec<PathBuf> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| qbak::parse_backup_name(path, &config).is_ok())
+                .collect();
+            backups.sort();
+            backups
+        };
+
+        // Without following, both links are recreated as links
+        let preserved = backups(false);
+        assert_eq!(preserved.len(), 2);
+        assert!(preserved.iter().all(|path| path.is_symlink()));
+
+        // Following copies the file and the directory behind them
+        let copied: Vec<PathBuf> = backups(true)
+            .into_iter()
+            .filter(|path| !preserved.contains(path))
+            .collect();
+        assert_eq!(copied.len(), 2);
+        assert!(copied.iter().all(|path| !path.is_symlink()));
+        assert!(copied.iter().any(|path| path.is_dir()));
+    }
+
     #[test]
     fn test_process_target_dry_run_verbose() {
         let dir = tempdir().unwrap();
@@ -478,7 +2245,7 @@ mod tests {
 
         let config = qbak::default_config();
         // Test dry run with verbose output
-        let result = process_target(&source_path, &config, true, true, false, false);
+        let result = process_target(&source_path, &config, true, true, false, false, false);
         assert!(result.is_ok());
     }
 
@@ -1,44 +1,245 @@
-use crate::config::Config;
+use crate::config::{ArchiveMode, CollisionStrategy, CompressionMode, Config};
 use crate::error::QbakError;
+use crate::utils::{
+    calculate_size, is_preserved_symlink, is_same_location, validate_backup_location,
+};
 use crate::Result;
-use chrono::{DateTime, Utc};
+use chrono::format::{Item, Parsed, StrftimeItems};
+use chrono::{DateTime, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use std::path::{Path, PathBuf};
 
 /// Generate a backup filename based on the source path and configuration
 pub fn generate_backup_name(source: &Path, config: &Config) -> Result<PathBuf> {
-    let timestamp = Utc::now();
-    let timestamp_str = format_timestamp(&timestamp, &config.timestamp_format);
+    generate_backup_name_in(source, None, config)
+}
 
+/// Generate a backup filename like `generate_backup_name`, placed in `into` instead of the
+/// configured directory if given
+pub(crate) fn generate_backup_name_in(
+    source: &Path,
+    into: Option<&Path>,
+    config: &Config,
+) -> Result<PathBuf> {
     let source_name = source
         .file_name()
         .and_then(|name| name.to_str())
         .ok_or_else(|| QbakError::validation("Invalid source filename"))?;
 
+    // Archived directories and compressed backups get the matching extensions; a preserved
+    // symlink is neither
+    let preserved_link = is_preserved_symlink(source, config);
+    let backup_name = backup_file_name(source_name, source.is_dir(), preserved_link, config)?;
+
+    // Place the backup in the dated or output directory if configured, otherwise next to
+    // the source
+    let configured = config.dated_dir.as_deref().or(config.output_dir.as_deref());
+    let parent = match into.or(configured) {
+        Some(dir) => dir,
+        None => source.parent().unwrap_or(Path::new(".")),
+    };
+    let backup_path = parent.join(&backup_name);
+
+    // Never write a backup into the tree it is a copy of
+    if !preserved_link {
+        validate_backup_location(source, &backup_path)?;
+    }
+
+    Ok(backup_path)
+}
+
+/// Name under which `--combine` archives several targets, before the timestamp and suffix
+pub const COMBINED_BACKUP_STEM: &str = "backup";
+
+/// Generate the name of an archive combining several targets
+///
+/// The archive is named like an archived directory called `backup` (e.g.
+/// `backup-20250603T145231-qbak.tar`) and placed in the output directory if configured,
+/// otherwise in the current directory.
+pub fn generate_combined_backup_name(config: &Config) -> Result<PathBuf> {
+    let backup_name = backup_file_name(COMBINED_BACKUP_STEM, true, false, config)?;
+    let parent = config
+        .dated_dir
+        .as_deref()
+        .or(config.output_dir.as_deref())
+        .unwrap_or(Path::new(""));
+    Ok(parent.join(backup_name))
+}
+
+/// Generate the directory a `--dated-dir` run writes its backups into, e.g.
+/// `base/20250603T145231`
+pub fn generate_dated_dir(base: &Path, config: &Config) -> PathBuf {
+    base.join(format_timestamp(&Utc::now(), &config.timestamp_format))
+}
+
+/// Build and validate the timestamped file name for a backup of `source_name`
+///
+/// With `dated_dir` the directory carries the timestamp, so the original name is kept.
+/// With `truncate_long_names`, a name over `max_filename_length` gets a shortened stem.
+fn backup_file_name(
+    source_name: &str,
+    is_dir: bool,
+    preserved_link: bool,
+    config: &Config,
+) -> Result<String> {
+    let timestamp = Utc::now();
+    let timestamp_str = format_timestamp(&timestamp, &config.timestamp_format);
+
     // Split filename into stem and extension
     let (stem, extension) = split_filename(source_name);
 
     // Create backup filename
-    let backup_name = if extension.is_empty() {
-        let suffix = &config.backup_suffix;
-        format!("{stem}-{timestamp_str}-{suffix}")
-    } else {
-        format!(
-            "{stem}-{timestamp_str}-{}.{extension}",
-            config.backup_suffix
-        )
+    let build_name = |stem: &str| {
+        let mut backup_name = if config.dated_dir.is_some() {
+            // Keep whatever follows the stem, including a trailing dot
+            format!("{stem}{}", &source_name[stem.len()..])
+        } else if extension.is_empty() {
+            let suffix = &config.backup_suffix;
+            format!("{stem}-{timestamp_str}-{suffix}")
+        } else {
+            format!(
+                "{stem}-{timestamp_str}-{}.{extension}",
+                config.backup_suffix
+            )
+        };
+
+        if is_dir && !preserved_link {
+            match config.archive {
+                ArchiveMode::Tar => backup_name.push_str(".tar"),
+                ArchiveMode::Zip => backup_name.push_str(".zip"),
+                ArchiveMode::None => {}
+            }
+        }
+        // Gzip applies to single files and tar archives; zip does its own compression
+        let gzipped = !is_dir || config.archive == ArchiveMode::Tar;
+        if config.compression == CompressionMode::Gzip && gzipped && !preserved_link {
+            backup_name.push_str(".gz");
+        }
+        backup_name
     };
+    let mut backup_name = build_name(stem);
+
+    if config.truncate_long_names && backup_name.len() > config.max_filename_length {
+        let excess = backup_name.len() - config.max_filename_length;
+        if let Some(short_stem) = shorten_stem(stem, excess, source_name) {
+            backup_name = build_name(&short_stem);
+        }
+    }
 
     // Validate the generated filename
     validate_filename_length(&backup_name, config.max_filename_length)?;
     validate_filesystem_chars(&backup_name)?;
 
-    // Get the parent directory
-    let parent = source.parent().unwrap_or(Path::new("."));
-    let backup_path = parent.join(&backup_name);
+    Ok(backup_name)
+}
 
-    Ok(backup_path)
+/// Shorten `stem` by at least `excess` bytes for `truncate_long_names`
+///
+/// The stem is cut at a character boundary and ends in `~` and the first 8 hex digits of
+/// the SHA-256 of `source_name`, so different long names sharing a prefix stay distinct
+/// and the same name always shortens the same way. Returns `None` if the stem is too
+/// short to make room.
+fn shorten_stem(stem: &str, excess: usize, source_name: &str) -> Option<String> {
+    let marker = format!("~{}", name_hash(source_name));
+    let mut keep = stem.len().checked_sub(excess + marker.len())?;
+    while !stem.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    if keep == 0 {
+        return None;
+    }
+    Some(format!("{}{marker}", &stem[..keep]))
+}
+
+/// Whether `stem` is `source_name`'s stem as shortened by `shorten_stem`
+fn is_shortened_stem_of(stem: &str, source_name: &str) -> bool {
+    stem.strip_suffix(&format!("~{}", name_hash(source_name)))
+        .is_some_and(|prefix| !prefix.is_empty() && source_name.starts_with(prefix))
+}
+
+/// First 8 hex digits of the SHA-256 of `name`
+fn name_hash(name: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(name.as_bytes())[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Resolve a collision with an existing backup using the configured `collision_strategy`
+///
+/// `backup_path` is the name generated for `source`. With `timestamp`, waits for the next
+/// second and generates a new name in the same directory, giving up with `BackupExists`
+/// after a few tries (a timestamp format without seconds never changes that quickly).
+pub fn resolve_collision_with(
+    source: &Path,
+    backup_path: &Path,
+    config: &Config,
+) -> Result<PathBuf> {
+    if config.overwrite && is_same_location(source, backup_path)? {
+        return Err(QbakError::validation(format!(
+            "Refusing to overwrite the source {} with its own backup",
+            source.display()
+        )));
+    }
+    resolve_collision_by(backup_path, config, || {
+        let fresh = generate_backup_name(source, config)?;
+        Ok(backup_path.with_file_name(fresh.file_name().unwrap_or_default()))
+    })
+}
+
+/// Resolve a collision for an archive from `generate_combined_backup_name`
+pub fn resolve_combined_collision(backup_path: &Path, config: &Config) -> Result<PathBuf> {
+    resolve_collision_by(backup_path, config, || {
+        generate_combined_backup_name(config)
+    })
+}
+
+/// Resolve a collision with `collision_strategy`, calling `regenerate` for a fresh timestamp
+fn resolve_collision_by(
+    backup_path: &Path,
+    config: &Config,
+    regenerate: impl Fn() -> Result<PathBuf>,
+) -> Result<PathBuf> {
+    // An existing backup is replaced when the new one is finalized
+    if config.overwrite {
+        return Ok(backup_path.to_path_buf());
+    }
+    match config.collision_strategy {
+        CollisionStrategy::Counter => resolve_collision(backup_path),
+        // Names in a dated directory carry no timestamp, so waiting would not free them
+        CollisionStrategy::Timestamp if config.dated_dir.is_some() => {
+            resolve_collision(backup_path)
+        }
+        CollisionStrategy::Error if backup_path.exists() => Err(QbakError::BackupExists {
+            path: backup_path.to_path_buf(),
+        }),
+        CollisionStrategy::Error => Ok(backup_path.to_path_buf()),
+        CollisionStrategy::Timestamp => {
+            let mut candidate = backup_path.to_path_buf();
+            for _ in 0..TIMESTAMP_COLLISION_RETRIES {
+                if !candidate.exists() {
+                    return Ok(candidate);
+                }
+                if crate::signal::is_interrupted() {
+                    return Err(QbakError::Interrupted);
+                }
+                let nanos = Utc::now().timestamp_subsec_nanos();
+                let wait = 1_000_000_000u32.saturating_sub(nanos);
+                std::thread::sleep(std::time::Duration::from_nanos(u64::from(wait)));
+                candidate = regenerate()?;
+            }
+            if candidate.exists() {
+                return Err(QbakError::BackupExists { path: candidate });
+            }
+            Ok(candidate)
+        }
+    }
 }
 
+/// How many times the `timestamp` collision strategy waits for a fresh timestamp
+const TIMESTAMP_COLLISION_RETRIES: usize = 3;
+
 /// Resolve filename collisions by adding a counter
 pub fn resolve_collision(base_path: &Path) -> Result<PathBuf> {
     if !base_path.exists() {
@@ -71,20 +272,239 @@ pub fn resolve_collision(base_path: &Path) -> Result<PathBuf> {
     Err(QbakError::validation("Too many backup collisions (>9999)"))
 }
 
-/// Format timestamp according to the specified format
-fn format_timestamp(timestamp: &DateTime<Utc>, format: &str) -> String {
-    match format {
-        "YYYYMMDDTHHMMSS" => timestamp.format("%Y%m%dT%H%M%S").to_string(),
-        _ => {
-            // For now, we only support the default format
-            // In the future, we could add support for custom formats
-            timestamp.format("%Y%m%dT%H%M%S").to_string()
+/// Components of a qbak-generated backup filename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedBackupName {
+    pub stem: String,
+    pub timestamp: String,
+    pub suffix: String,
+    pub extension: String,
+    pub counter: Option<u32>,
+}
+
+impl ParsedBackupName {
+    /// Reconstruct the original filename the backup was created from
+    pub fn original_name(&self) -> String {
+        if self.extension.is_empty() {
+            self.stem.clone()
+        } else {
+            format!("{}.{}", self.stem, self.extension)
+        }
+    }
+}
+
+/// Parse a qbak-generated backup filename back into its components
+///
+/// Accepts names of the form `stem-TIMESTAMP-suffix[-N][.ext]`, where `-N` is
+/// the optional collision counter added by `resolve_collision`.
+pub fn parse_backup_name(backup: &Path, config: &Config) -> Result<ParsedBackupName> {
+    let filename = backup
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| QbakError::validation("Invalid backup filename"))?;
+
+    let not_a_backup = || {
+        QbakError::validation(format!(
+            "Not a qbak backup name: {filename} (expected name-TIMESTAMP-{}.ext)",
+            config.backup_suffix
+        ))
+    };
+
+    let (name, extension) = split_filename(filename);
+
+    // Strip the optional collision counter
+    let suffix_marker = format!("-{}", config.backup_suffix);
+    let (name, counter) = match name.rsplit_once('-') {
+        Some((rest, digits))
+            if rest.ends_with(&suffix_marker)
+                && !digits.is_empty()
+                && digits.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (rest, digits.parse().ok())
         }
+        _ => (name, None),
+    };
+
+    let name = name.strip_suffix(&suffix_marker).ok_or_else(not_a_backup)?;
+
+    // The timestamp may itself contain dashes, so take the shortest tail that parses
+    let (stem, timestamp) = name
+        .match_indices('-')
+        .rev()
+        .map(|(index, _)| (&name[..index], &name[index + 1..]))
+        .find(|(stem, timestamp)| {
+            !stem.is_empty() && parse_timestamp(timestamp, &config.timestamp_format).is_some()
+        })
+        .ok_or_else(not_a_backup)?;
+
+    Ok(ParsedBackupName {
+        stem: stem.to_string(),
+        timestamp: timestamp.to_string(),
+        suffix: config.backup_suffix.clone(),
+        extension: extension.to_string(),
+        counter,
+    })
+}
+
+/// A backup found next to its source by `find_backups`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub timestamp: DateTime<Utc>,
+    pub counter: Option<u32>,
+    /// Size in bytes; the total of all files for directory backups
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Find existing qbak backups of a source with their timestamps and sizes, newest first
+///
+/// Uses the same matching as `find_existing_backups`; the source itself need not exist.
+pub fn find_backups(source: &Path, config: &Config) -> Result<Vec<BackupInfo>> {
+    matching_backups(source, config)?
+        .into_iter()
+        .map(|(parsed, path)| {
+            let naive = parse_timestamp(&parsed.timestamp, &config.timestamp_format)
+                .ok_or_else(|| QbakError::validation("Invalid backup timestamp"))?;
+            Ok(BackupInfo {
+                timestamp: naive.and_utc(),
+                counter: parsed.counter,
+                size: calculate_size(&path)?,
+                is_dir: path.is_dir(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Find existing qbak backups of a source in its backup directory, newest first
+///
+/// The backup directory is the configured output directory, or the source's parent.
+pub fn find_existing_backups(source: &Path, config: &Config) -> Result<Vec<PathBuf>> {
+    Ok(matching_backups(source, config)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect())
+}
+
+/// Parsed names and paths of the backups of `source`, newest first
+fn matching_backups(source: &Path, config: &Config) -> Result<Vec<(ParsedBackupName, PathBuf)>> {
+    let source_name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| QbakError::validation("Invalid source filename"))?;
+
+    let parent = match (&config.output_dir, source.parent()) {
+        (Some(output_dir), _) => output_dir.as_path(),
+        (None, Some(parent)) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !parent.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(parent)? {
+        let path = entry?.path();
+        if let Some(parsed) = parse_backup_of(&path, source_name, config) {
+            backups.push((parsed, path));
+        }
+    }
+
+    // Custom timestamp formats need not sort lexicographically, so compare the parsed times;
+    // the collision counter breaks ties. A plain and a compressed or archived backup made in
+    // the same second share both, so the modification time decides between them.
+    let sort_key = |parsed: &ParsedBackupName, path: &Path| {
+        let time = parse_timestamp(&parsed.timestamp, &config.timestamp_format);
+        let modified = std::fs::symlink_metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        (time, parsed.counter.unwrap_or(0), modified)
+    };
+    backups.sort_by_cached_key(|(parsed, path)| std::cmp::Reverse(sort_key(parsed, path)));
+
+    Ok(backups)
+}
+
+/// Parse `backup` if it is a qbak backup of the file or directory named `source_name`
+///
+/// Besides plain backups this matches the `.gz`, `.tar`, `.tar.gz` and `.zip` names
+/// produced by the compression and archive modes. Backups of other sources sharing the
+/// same stem (e.g. `data.txt` when looking for `data`) are not matched. Names shortened by
+/// `truncate_long_names` are matched by the hash in their stem.
+fn parse_backup_of(backup: &Path, source_name: &str, config: &Config) -> Option<ParsedBackupName> {
+    let filename = backup.file_name()?.to_str()?;
+    // Archive extensions follow the source's own, e.g. `d-TIMESTAMP-qbak.v2.tar` for `d.v2`
+    let candidates = [
+        Some(filename),
+        filename.strip_suffix(".gz"),
+        filename.strip_suffix(".tar"),
+        filename.strip_suffix(".tar.gz"),
+        filename.strip_suffix(".zip"),
+    ];
+
+    candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|candidate| parse_backup_name(Path::new(candidate), config).ok())
+        .find(|parsed| {
+            parsed.original_name() == source_name || is_shortened_stem_of(&parsed.stem, source_name)
+        })
+}
+
+/// Whether `format` names a timestamp format `generate_backup_name` understands
+///
+/// Besides `YYYYMMDDTHHMMSS`, any strftime pattern (e.g. `%Y-%m-%d_%H%M`) is accepted if
+/// its output is filesystem-safe, has no path separators or `.` (which would be mistaken
+/// for an extension), and can be read back into at least a date, so existing backups are
+/// still found by `--list` and `--keep`.
+pub(crate) fn is_supported_timestamp_format(format: &str) -> bool {
+    let pattern = strftime_pattern(format);
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        return false;
+    }
+
+    let sample = Utc.with_ymd_and_hms(2025, 6, 3, 14, 52, 31).unwrap();
+    let rendered = sample.format(pattern).to_string();
+    !rendered.is_empty()
+        && !rendered.contains(['.', '/', '\\'])
+        && validate_filesystem_chars(&rendered).is_ok()
+        && parse_timestamp(&rendered, format).is_some()
+}
+
+/// The strftime pattern for a `timestamp_format` setting
+fn strftime_pattern(format: &str) -> &str {
+    if format == "YYYYMMDDTHHMMSS" {
+        "%Y%m%dT%H%M%S"
+    } else {
+        format
     }
 }
 
-/// Split filename into stem and extension
-fn split_filename(filename: &str) -> (&str, &str) {
+/// Format timestamp according to the specified format
+fn format_timestamp(timestamp: &DateTime<Utc>, format: &str) -> String {
+    timestamp.format(strftime_pattern(format)).to_string()
+}
+
+/// Read a timestamp written with `format`; formats without a time of day give midnight
+fn parse_timestamp(timestamp: &str, format: &str) -> Option<NaiveDateTime> {
+    let mut parsed = Parsed::new();
+    chrono::format::parse(
+        &mut parsed,
+        timestamp,
+        StrftimeItems::new(strftime_pattern(format)),
+    )
+    .ok()?;
+    let date = parsed.to_naive_date().ok()?;
+    let time = parsed.to_naive_time().unwrap_or(NaiveTime::MIN);
+    Some(date.and_time(time))
+}
+
+/// Split a filename into stem and extension at the last dot
+///
+/// Leading dots (hidden files like `.bashrc`) are part of the stem, and a
+/// trailing dot yields an empty extension.
+pub fn split_filename(filename: &str) -> (&str, &str) {
     if let Some(dot_pos) = filename.rfind('.') {
         // Only split if the dot is not at the beginning or end
         if dot_pos > 0 && dot_pos < filename.len() - 1 {
@@ -109,7 +529,7 @@ fn validate_filename_length(filename: &str, max_length: usize) -> Result<()> {
 }
 
 /// Validate that the filename doesn't contain problematic characters
-fn validate_filesystem_chars(filename: &str) -> Result<()> {
+pub(crate) fn validate_filesystem_chars(filename: &str) -> Result<()> {
     // Characters that are problematic on Windows
     const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
 
@@ -191,6 +611,21 @@ mod tests {
         assert!(backup_name.starts_with("makefile-"));
     }
 
+    #[test]
+    fn test_generate_backup_name_output_dir() {
+        let mut config = default_config();
+        config.output_dir = Some(PathBuf::from("/var/backups"));
+
+        let backup_path = generate_backup_name(Path::new("/etc/app.conf"), &config).unwrap();
+        assert_eq!(backup_path.parent().unwrap(), Path::new("/var/backups"));
+        assert!(backup_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("app-"));
+    }
+
     #[test]
     fn test_resolve_collision() {
         let dir = tempdir().unwrap();
@@ -214,6 +649,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_collision_with_strategies() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("data.txt");
+        File::create(&source).unwrap();
+
+        let mut config = Config {
+            collision_strategy: CollisionStrategy::Error,
+            ..Config::default()
+        };
+        let taken = generate_backup_name(&source, &config).unwrap();
+        assert_eq!(
+            resolve_collision_with(&source, &taken, &config).unwrap(),
+            taken
+        );
+
+        File::create(&taken).unwrap();
+        assert!(matches!(
+            resolve_collision_with(&source, &taken, &config),
+            Err(QbakError::BackupExists { .. })
+        ));
+
+        config.collision_strategy = CollisionStrategy::Counter;
+        let counted = resolve_collision_with(&source, &taken, &config).unwrap();
+        assert!(counted.to_str().unwrap().ends_with("-qbak-1.txt"));
+
+        // A fresh timestamp instead of a counter
+        config.collision_strategy = CollisionStrategy::Timestamp;
+        let bumped = resolve_collision_with(&source, &taken, &config).unwrap();
+        assert_ne!(bumped, taken);
+        assert!(!bumped.exists());
+        assert!(bumped.to_str().unwrap().ends_with("-qbak.txt"));
+
+        // The fresh name stays in the directory the backup was placed in
+        let into = dir.path().join("into");
+        std::fs::create_dir(&into).unwrap();
+        let taken = generate_backup_name_in(&source, Some(&into), &config).unwrap();
+        File::create(&taken).unwrap();
+        let bumped = resolve_collision_with(&source, &taken, &config).unwrap();
+        assert_ne!(bumped, taken);
+        assert_eq!(bumped.parent(), Some(into.as_path()));
+    }
+
+    #[test]
+    fn test_generate_combined_backup_name() {
+        let mut config = Config {
+            archive: ArchiveMode::Tar,
+            compression: CompressionMode::Gzip,
+            ..Config::default()
+        };
+        let name = generate_combined_backup_name(&config).unwrap();
+        let name = name.to_str().unwrap();
+        assert!(name.starts_with("backup-"));
+        assert!(name.ends_with("-qbak.tar.gz"));
+
+        config.archive = ArchiveMode::Zip;
+        config.output_dir = Some(PathBuf::from("/srv/backups"));
+        let name = generate_combined_backup_name(&config).unwrap();
+        assert_eq!(name.parent(), Some(Path::new("/srv/backups")));
+        assert!(name.to_str().unwrap().ends_with("-qbak.zip"));
+    }
+
     #[test]
     fn test_validate_filesystem_chars() {
         // Valid filenames
@@ -239,4 +736,251 @@ mod tests {
         assert!(validate_filename_length(short_name, 255).is_ok());
         assert!(validate_filename_length(&long_name, 255).is_err());
     }
+
+    #[test]
+    fn test_truncate_long_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join(format!("{}.json", "x".repeat(300)));
+        let other = dir.path().join(format!("{}y.json", "x".repeat(299)));
+
+        // Strict by default
+        let config = default_config();
+        assert!(matches!(
+            generate_backup_name(&source, &config),
+            Err(QbakError::FilenameTooLong { .. })
+        ));
+
+        let config = Config {
+            truncate_long_names: true,
+            ..default_config()
+        };
+        let backup = generate_backup_name(&source, &config).unwrap();
+        let name = backup.file_name().unwrap().to_str().unwrap();
+        assert!(name.len() <= config.max_filename_length);
+        assert!(name.ends_with("-qbak.json"));
+        assert!(name.starts_with("xxxx"));
+
+        // Deterministic, and distinct for names sharing the kept prefix
+        let again = generate_backup_name(&source, &config).unwrap();
+        let stem = |path: &Path| parse_backup_name(path, &config).unwrap().stem;
+        assert_eq!(stem(&backup), stem(&again));
+        assert_ne!(
+            stem(&backup),
+            stem(&generate_backup_name(&other, &config).unwrap())
+        );
+
+        // Shortened backups are still found as backups of their source
+        std::fs::write(&backup, "{}").unwrap();
+        assert_eq!(find_existing_backups(&source, &config).unwrap(), [backup]);
+        assert!(find_existing_backups(&other, &config).unwrap().is_empty());
+
+        // Multibyte stems are cut at a character boundary
+        let short = shorten_stem(&"é".repeat(20), 5, "name").unwrap();
+        assert!(short.len() <= 40 - 5);
+        assert!(short.starts_with("éé"));
+        assert!(shorten_stem("abc", 5, "abc").is_none());
+    }
+
+    #[test]
+    fn test_parse_backup_name() {
+        let config = default_config();
+
+        let parsed =
+            parse_backup_name(Path::new("/tmp/example-20250603T145231-qbak.txt"), &config).unwrap();
+        assert_eq!(parsed.stem, "example");
+        assert_eq!(parsed.timestamp, "20250603T145231");
+        assert_eq!(parsed.suffix, "qbak");
+        assert_eq!(parsed.extension, "txt");
+        assert_eq!(parsed.counter, None);
+        assert_eq!(parsed.original_name(), "example.txt");
+
+        // Multiple extensions, hyphenated stems and collision counters
+        let parsed =
+            parse_backup_name(Path::new("data.tar-20250603T145231-qbak-2.gz"), &config).unwrap();
+        assert_eq!(parsed.original_name(), "data.tar.gz");
+        assert_eq!(parsed.counter, Some(2));
+
+        let parsed =
+            parse_backup_name(Path::new("my-project-20250603T145231-qbak"), &config).unwrap();
+        assert_eq!(parsed.original_name(), "my-project");
+    }
+
+    #[test]
+    fn test_parse_backup_name_roundtrip() {
+        let config = default_config();
+        for name in ["report.pdf", "data.tar.gz", "makefile", "my-dir"] {
+            let backup = generate_backup_name(Path::new(name), &config).unwrap();
+            let parsed = parse_backup_name(&backup, &config).unwrap();
+            assert_eq!(parsed.original_name(), name);
+        }
+    }
+
+    #[test]
+    fn test_parse_backup_name_invalid() {
+        let config = default_config();
+        assert!(parse_backup_name(Path::new("example.txt"), &config).is_err());
+        assert!(parse_backup_name(Path::new("example-notatime-qbak.txt"), &config).is_err());
+        assert!(
+            parse_backup_name(Path::new("example-20250603T145231-other.txt"), &config).is_err()
+        );
+        assert!(parse_backup_name(Path::new("-20250603T145231-qbak.txt"), &config).is_err());
+    }
+
+    #[test]
+    fn test_find_backups() {
+        use chrono::TimeZone;
+
+        let dir = tempdir().unwrap();
+        let config = default_config();
+        // The source may already be gone when looking for backups to restore
+        let source = dir.path().join("report.txt");
+
+        std::fs::write(dir.path().join("report-20250101T120000-qbak.txt"), "v1").unwrap();
+        std::fs::write(dir.path().join("report-20250603T145231-qbak.txt"), "v2!").unwrap();
+        std::fs::write(dir.path().join("report-20250603T145231-qbak-1.txt"), "v3!!").unwrap();
+        std::fs::write(dir.path().join("report-final.txt"), "other").unwrap();
+
+        let backups = find_backups(&source, &config).unwrap();
+        assert_eq!(backups.len(), 3);
+        assert_eq!(
+            backups[0].path,
+            dir.path().join("report-20250603T145231-qbak-1.txt")
+        );
+        assert_eq!(backups[0].counter, Some(1));
+        assert_eq!(backups[0].size, 4);
+        assert_eq!(
+            backups[1].timestamp,
+            Utc.with_ymd_and_hms(2025, 6, 3, 14, 52, 31).unwrap()
+        );
+        assert_eq!(backups[2].size, 2);
+        assert!(!backups[2].is_dir);
+    }
+
+    #[test]
+    fn test_find_archive_backups_of_dotted_directory() {
+        let dir = tempdir().unwrap();
+        let config = default_config();
+        let source = dir.path().join("d.v2");
+
+        for name in [
+            "d-20250101T120000-qbak.v2",
+            "d-20250102T120000-qbak.v2.tar",
+            "d-20250103T120000-qbak.v2.tar.gz",
+            "d-20250104T120000-qbak.v2.zip",
+            "d-20250105T120000-qbak.v3.tar",
+            "d-20250106T120000-qbak.tar",
+        ] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        let backups = find_backups(&source, &config).unwrap();
+        let names: Vec<_> = backups
+            .iter()
+            .map(|info| info.path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "d-20250104T120000-qbak.v2.zip",
+                "d-20250103T120000-qbak.v2.tar.gz",
+                "d-20250102T120000-qbak.v2.tar",
+                "d-20250101T120000-qbak.v2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_timestamp_format() {
+        for format in [
+            "YYYYMMDDTHHMMSS",
+            "%Y-%m-%d",
+            "%d-%m-%Y_%H%M%S",
+            "%Y%m%d%H%M",
+        ] {
+            assert!(is_supported_timestamp_format(format), "{format}");
+        }
+        // No date to read back, path separators, dots, or invalid specifiers
+        for format in [
+            "%Y", "%H%M%S", "daily", "%Y/%m/%d", "%Y.%m.%d", "%Y%m%d%Q", "",
+        ] {
+            assert!(!is_supported_timestamp_format(format), "{format}");
+        }
+
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("my-notes.txt");
+        File::create(&source).unwrap();
+        let config = Config {
+            timestamp_format: "%d-%m-%Y_%H%M%S".to_string(),
+            ..Config::default()
+        };
+
+        let backup = generate_backup_name(&source, &config).unwrap();
+        let expected = Utc::now().format("%d-%m-%Y_").to_string();
+        let name = backup.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("my-notes-{expected}")));
+
+        // Dashes inside the timestamp don't confuse parsing
+        let parsed = parse_backup_name(&backup, &config).unwrap();
+        assert_eq!(parsed.original_name(), "my-notes.txt");
+
+        // Backups are ordered by time, not by how the timestamp sorts as text
+        File::create(dir.path().join("my-notes-31-01-2025_120000-qbak.txt")).unwrap();
+        File::create(dir.path().join("my-notes-01-06-2025_120000-qbak.txt")).unwrap();
+        File::create(&backup).unwrap();
+        let backups = find_backups(&source, &config).unwrap();
+        let names: Vec<_> = backups
+            .iter()
+            .map(|info| info.path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names[1..],
+            [
+                "my-notes-01-06-2025_120000-qbak.txt",
+                "my-notes-31-01-2025_120000-qbak.txt"
+            ]
+        );
+        assert_eq!(names[0], name);
+    }
+
+    #[test]
+    fn test_find_existing_backups() {
+        let dir = tempdir().unwrap();
+        let config = default_config();
+        let source = dir.path().join("data");
+        std::fs::create_dir(&source).unwrap();
+
+        for name in [
+            "data-20250101T120000-qbak",
+            "data-20250301T120000-qbak",
+            "data-20250301T120000-qbak-1",
+            "other-20250401T120000-qbak",
+            "data.txt-20250501T120000-qbak",
+        ] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        for name in [
+            "data-20250201T120000-qbak.tar.gz",
+            "data-20250115T120000-qbak.zip",
+            "data-20250601T120000-qbak.txt.gz",
+            "data-20250601T120000-qbak.csv",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let backups = find_existing_backups(&source, &config).unwrap();
+        let names: Vec<_> = backups
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "data-20250301T120000-qbak-1",
+                "data-20250301T120000-qbak",
+                "data-20250201T120000-qbak.tar.gz",
+                "data-20250115T120000-qbak.zip",
+                "data-20250101T120000-qbak",
+            ]
+        );
+    }
 }
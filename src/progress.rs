@@ -2,8 +2,13 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+/// Re-exported so progress users keep a single definition of size formatting
+pub use crate::utils::format_size;
+
 #[derive(Debug, Clone)]
 pub struct ProgressConfig {
     pub enabled: bool,
@@ -14,19 +19,55 @@ pub struct ProgressConfig {
     pub min_files_threshold: usize,
     pub min_size_threshold: u64,
     pub min_duration_threshold: Duration,
+    pub format: ProgressFormat,
+    /// Update the scanning spinner after this many files
+    pub scan_update_interval: usize,
+}
+
+/// How progress is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// Interactive indicatif bars and spinners
+    Bar,
+    /// Newline-delimited JSON events on stderr, for wrappers that draw their own UI
+    Json,
 }
 
+impl ProgressFormat {
+    /// Parse a progress format from a config or command line value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "bar" => Some(Self::Bar),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bar => "bar",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Width assumed when the terminal size can't be detected (e.g. stdout is piped)
+const FALLBACK_TERMINAL_WIDTH: usize = 80;
+
 impl Default for ProgressConfig {
     fn default() -> Self {
+        let is_interactive = std::io::stdout().is_terminal();
         Self {
             enabled: true,
             force_enabled: false,
-            supports_ansi: console::colors_enabled(),
-            terminal_width: console::Term::stdout().size().1 as usize,
-            is_interactive: std::io::stdout().is_terminal(),
+            supports_ansi: detect_ansi_support(),
+            terminal_width: detect_terminal_width(is_interactive),
+            is_interactive,
             min_files_threshold: 50,
             min_size_threshold: 10 * 1024 * 1024, // 10 MB
             min_duration_threshold: Duration::from_secs(2),
+            format: ProgressFormat::Bar,
+            scan_update_interval: 10,
         }
     }
 }
@@ -68,7 +109,75 @@ impl ProgressConfig {
     }
 }
 
-pub struct BackupProgress {
+/// Progress reported to a callback, for library users that draw their own UI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// Counting the files to back up has started
+    ScanStarted,
+    /// Files found so far while scanning
+    ScanUpdate { files: usize },
+    /// Scanning is complete; the totals the backup will report against
+    ScanFinished { files: usize, bytes: u64 },
+    /// Files and bytes backed up so far, and the file just written
+    BackingUpdate {
+        files: usize,
+        bytes: u64,
+        current: PathBuf,
+    },
+    /// The operation has ended, successfully or not
+    Finished,
+}
+
+/// Cloneable handle for reporting progress from several worker threads at once
+///
+/// Workers add what they completed with `record`; the thread that owns the
+/// `BackupProgress` picks the totals up with `BackupProgress::sync`. The totals only
+/// ever grow, so the bar cannot jump backward however the updates interleave.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressHandle {
+    shared: Arc<SharedProgress>,
+}
+
+#[derive(Debug, Default)]
+struct SharedProgress {
+    files: AtomicUsize,
+    bytes: AtomicU64,
+    current_file: Mutex<Option<PathBuf>>,
+}
+
+impl ProgressHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `files` completed files totalling `bytes` bytes, `file` being the latest
+    pub fn record(&self, files: usize, bytes: u64, file: &Path) {
+        // Bytes go first, so a reader that sees the new file count also sees their bytes
+        self.shared.bytes.fetch_add(bytes, Ordering::Release);
+        self.shared.files.fetch_add(files, Ordering::Release);
+        if let Ok(mut current) = self.shared.current_file.lock() {
+            *current = Some(file.to_path_buf());
+        }
+    }
+
+    /// Files and bytes completed so far by all clones of this handle
+    pub fn totals(&self) -> (usize, u64) {
+        let files = self.shared.files.load(Ordering::Acquire);
+        let bytes = self.shared.bytes.load(Ordering::Acquire);
+        (files, bytes)
+    }
+
+    /// The file most recently reported by any worker
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.shared
+            .current_file
+            .lock()
+            .ok()
+            .and_then(|current| current.clone())
+    }
+}
+
+pub struct BackupProgress<'a> {
     phase: ProgressPhase,
     files_total: Option<usize>,
     files_processed: usize,
@@ -78,6 +187,13 @@ pub struct BackupProgress {
     current_file: Option<PathBuf>,
     progress_bar: Option<ProgressBar>,
     config: ProgressConfig,
+    /// Only show the bar once `min_duration_threshold` has elapsed
+    deferred: bool,
+    /// Progress made by earlier targets when one bar spans several targets
+    files_offset: usize,
+    bytes_offset: u64,
+    /// Receives events instead of drawing bars when set
+    callback: Option<Box<dyn FnMut(ProgressEvent) + 'a>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -86,7 +202,7 @@ enum ProgressPhase {
     Backing,
 }
 
-impl BackupProgress {
+impl<'a> BackupProgress<'a> {
     pub fn new(config: ProgressConfig) -> Self {
         Self {
             phase: ProgressPhase::Scanning,
@@ -98,29 +214,110 @@ impl BackupProgress {
             current_file: None,
             progress_bar: None,
             config,
+            deferred: false,
+            files_offset: 0,
+            bytes_offset: 0,
+            callback: None,
         }
     }
 
+    /// Create progress that reports every update to `callback` and never draws bars
+    pub fn with_callback(config: ProgressConfig, callback: impl FnMut(ProgressEvent) + 'a) -> Self {
+        Self {
+            callback: Some(Box::new(callback)),
+            ..Self::new(config)
+        }
+    }
+
+    /// Pass an event to the callback, returning false if there is none
+    fn notify(&mut self, event: ProgressEvent) -> bool {
+        match self.callback {
+            Some(ref mut callback) => {
+                callback(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Create progress that stays hidden until `min_duration_threshold` has elapsed
+    ///
+    /// Used for operations below the file count and size thresholds, so fast backups
+    /// stay quiet while slow ones (e.g. from a network mount) still get a bar.
+    pub fn new_deferred(config: ProgressConfig) -> Self {
+        Self {
+            deferred: true,
+            ..Self::new(config)
+        }
+    }
+
+    /// Whether events are emitted as NDJSON instead of drawing bars
+    fn is_json(&self) -> bool {
+        self.config.format == ProgressFormat::Json
+    }
+
+    /// Write a single NDJSON progress event to stderr
+    fn emit_event(&self, event: serde_json::Value) {
+        eprintln!("{event}");
+    }
+
     pub fn start_scanning(&mut self) {
         self.phase = ProgressPhase::Scanning;
         self.start_time = Instant::now();
 
-        if self.config.is_interactive {
-            let pb = ProgressBar::new_spinner();
-            pb.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} Scanning files... {msg}")
-                    .unwrap(),
-            );
-            pb.set_message("Starting scan...");
-            self.progress_bar = Some(pb);
+        if self.notify(ProgressEvent::ScanStarted) {
+            return;
+        }
+
+        if self.is_json() {
+            self.emit_event(serde_json::json!({ "phase": "scanning", "files": 0 }));
+            return;
+        }
+
+        if self.config.is_interactive && !self.deferred {
+            self.start_scan_spinner();
         }
     }
 
+    fn start_scan_spinner(&mut self) {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template(&self.styled_template("{spinner:.green} Scanning files... {msg}"))
+                .unwrap(),
+        );
+        pb.set_message("Starting scan...");
+        self.progress_bar = Some(pb);
+    }
+
+    /// Whether the scan has found enough new files to report another update
+    pub fn scan_update_due(&self, files_found: usize) -> bool {
+        files_found % self.config.scan_update_interval.max(1) == 0
+    }
+
     pub fn update_scan_progress(&mut self, files_found: usize, current_path: &Path) {
         self.files_processed = files_found;
         self.current_file = Some(current_path.to_path_buf());
 
+        if self.notify(ProgressEvent::ScanUpdate { files: files_found }) {
+            return;
+        }
+
+        if self.is_json() {
+            self.emit_event(serde_json::json!({ "phase": "scanning", "files": files_found }));
+            return;
+        }
+
+        // A deferred spinner appears once the scan itself turns out to be slow
+        if self.deferred
+            && self.progress_bar.is_none()
+            && self.config.is_interactive
+            && matches!(self.phase, ProgressPhase::Scanning)
+            && self.start_time.elapsed() >= self.config.min_duration_threshold
+        {
+            self.start_scan_spinner();
+        }
+
         if let Some(ref pb) = self.progress_bar {
             let filename = current_path
                 .file_name()
@@ -139,6 +336,24 @@ impl BackupProgress {
         self.phase = ProgressPhase::Backing;
         self.files_processed = 0;
         self.bytes_processed = 0;
+        self.files_offset = 0;
+        self.bytes_offset = 0;
+
+        if self.notify(ProgressEvent::ScanFinished {
+            files: total_files,
+            bytes: total_size,
+        }) {
+            return;
+        }
+
+        if self.is_json() {
+            self.emit_event(serde_json::json!({
+                "phase": "scanning",
+                "files": total_files,
+                "bytes": total_size,
+            }));
+            return;
+        }
 
         // Finish scanning spinner
         if let Some(pb) = self.progress_bar.take() {
@@ -147,32 +362,200 @@ impl BackupProgress {
         }
 
         // Start backup progress bar
+        if !self.deferred {
+            self.start_backing_bar();
+        }
+    }
+
+    /// Start backing up without a scan, for `single_pass`
+    ///
+    /// With no totals there is no percentage or ETA; the bar is a spinner with running
+    /// file and byte counts.
+    pub fn start_unscanned(&mut self) {
+        self.files_total = None;
+        self.bytes_total = None;
+        self.phase = ProgressPhase::Backing;
+        self.start_time = Instant::now();
+        self.files_processed = 0;
+        self.bytes_processed = 0;
+        self.files_offset = 0;
+        self.bytes_offset = 0;
+
+        if self.callback.is_some() || self.is_json() {
+            return;
+        }
+        if !self.deferred {
+            self.start_backing_bar();
+        }
+    }
+
+    /// The bar advances by bytes rather than files, so indicatif's exponentially
+    /// weighted rate estimate, and the ETA derived from it, stay steady on trees that
+    /// mix many tiny files with a few huge ones. The file count goes in the message.
+    fn start_backing_bar(&mut self) {
+        if self.files_total.is_none() {
+            if self.config.is_interactive {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(self.get_spinner_style());
+                self.progress_bar = Some(pb);
+            }
+            return;
+        }
+        let total_files = self.files_total.unwrap_or(0);
         if self.config.is_interactive && total_files > 0 {
-            let pb = ProgressBar::new(total_files as u64);
+            let pb = ProgressBar::new(self.bytes_total.unwrap_or(0));
             pb.set_style(self.get_progress_style());
             self.progress_bar = Some(pb);
         }
     }
 
+    /// Begin the next target of a multi-target operation
+    ///
+    /// Later `update_backup_progress` calls report progress within the new target and
+    /// are added on top of everything completed so far.
+    pub fn start_target(&mut self) {
+        self.files_offset = self.files_processed;
+        self.bytes_offset = self.bytes_processed;
+    }
+
     pub fn update_backup_progress(
         &mut self,
         files_completed: usize,
         bytes_completed: u64,
         current_file: &Path,
     ) {
+        let files_completed = self.files_offset + files_completed;
+        let bytes_completed = self.bytes_offset + bytes_completed;
         self.files_processed = files_completed;
         self.bytes_processed = bytes_completed;
         self.current_file = Some(current_file.to_path_buf());
 
+        if self.notify(ProgressEvent::BackingUpdate {
+            files: files_completed,
+            bytes: bytes_completed,
+            current: current_file.to_path_buf(),
+        }) {
+            return;
+        }
+
+        if self.is_json() {
+            self.emit_event(serde_json::json!({
+                "phase": "backing",
+                "files": files_completed,
+                "bytes": bytes_completed,
+                "current": current_file.display().to_string(),
+            }));
+            return;
+        }
+
+        // Show a deferred bar once the operation turns out to be slow
+        if self.deferred
+            && self.progress_bar.is_none()
+            && self.start_time.elapsed() >= self.config.min_duration_threshold
+        {
+            self.deferred = false;
+            self.start_backing_bar();
+        }
+
         if let Some(ref pb) = self.progress_bar {
-            pb.set_position(files_completed as u64);
+            pb.set_position(bytes_completed);
 
-            let message = self.format_progress_message(current_file);
+            let message = self.format_backing_message(files_completed, current_file);
             pb.set_message(message);
         }
     }
 
+    /// Report the totals workers have recorded through `handle` for the current target
+    ///
+    /// Call this from the thread that owns the progress, e.g. in a loop while workers
+    /// run and once more after they finish. Does nothing if nothing changed.
+    pub fn sync(&mut self, handle: &ProgressHandle) {
+        let (files, bytes) = handle.totals();
+        if self.files_offset + files == self.files_processed
+            && self.bytes_offset + bytes == self.bytes_processed
+        {
+            return;
+        }
+        let current = handle.current_file().unwrap_or_default();
+        self.update_backup_progress(files, bytes, &current);
+    }
+
+    /// Start a byte-based bar for copying a single file
+    pub fn start_file_copy(&mut self, total_bytes: u64, file: &Path) {
+        self.phase = ProgressPhase::Backing;
+        self.start_time = Instant::now();
+        self.files_total = Some(1);
+        self.bytes_total = Some(total_bytes);
+        self.bytes_processed = 0;
+        self.current_file = Some(file.to_path_buf());
+
+        if self.is_json() || self.callback.is_some() {
+            return;
+        }
+
+        if self.config.is_interactive && total_bytes > 0 {
+            let pb = ProgressBar::new(total_bytes);
+            pb.set_style(self.get_bytes_progress_style());
+            pb.set_message(self.format_progress_message(file));
+            self.progress_bar = Some(pb);
+        }
+    }
+
+    /// Update a byte-based bar started with `start_file_copy`
+    ///
+    /// When one bar spans several targets (see `start_target`), `bytes_completed` counts
+    /// within the current target and is added on top of the progress made so far.
+    pub fn update_bytes_progress(&mut self, bytes_completed: u64) {
+        let bytes_completed = self.bytes_offset + bytes_completed;
+        self.bytes_processed = bytes_completed;
+
+        let current = self.current_file.clone().unwrap_or_default();
+        if self.notify(ProgressEvent::BackingUpdate {
+            files: self.files_processed,
+            bytes: bytes_completed,
+            current,
+        }) {
+            return;
+        }
+
+        if self.is_json() {
+            let current = self
+                .current_file
+                .as_ref()
+                .map(|file| file.display().to_string())
+                .unwrap_or_default();
+            self.emit_event(serde_json::json!({
+                "phase": "backing",
+                "files": self.files_processed,
+                "bytes": bytes_completed,
+                "current": current,
+            }));
+            return;
+        }
+
+        if let Some(ref pb) = self.progress_bar {
+            pb.set_position(bytes_completed);
+        }
+    }
+
+    /// Hide the bar while `f` runs, so it can print without garbling the display
+    pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        match self.progress_bar {
+            Some(ref pb) => pb.suspend(f),
+            None => f(),
+        }
+    }
+
     pub fn finish(&mut self) {
+        if self.notify(ProgressEvent::Finished) {
+            return;
+        }
+
+        if self.is_json() {
+            self.emit_event(serde_json::json!({ "phase": "done" }));
+            return;
+        }
+
         if let Some(pb) = self.progress_bar.take() {
             pb.finish_and_clear();
         }
@@ -181,24 +564,89 @@ impl BackupProgress {
     fn get_progress_style(&self) -> ProgressStyle {
         let template = if self.config.terminal_width >= 120 {
             // Full display for wide terminals
-            "[{bar:32.cyan/blue}] {pos}/{len} files ({percent}%) • {bytes}/{total_bytes} • {bytes_per_sec} • ETA: {eta} • {msg}"
+            "[{bar:32.cyan/blue}] {msg} ({percent}%) • {bytes}/{total_bytes} • {bytes_per_sec} • ETA: {eta}"
         } else if self.config.terminal_width >= 80 {
             // Compact display for normal terminals
-            "[{bar:24.cyan/blue}] {pos}/{len} ({percent}%) • {bytes_per_sec} • ETA: {eta}"
+            "[{bar:24.cyan/blue}] {msg} ({percent}%) • {bytes_per_sec} • ETA: {eta}"
         } else if self.config.terminal_width >= 60 {
             // Minimal display for narrow terminals
-            "[{bar:16}] {pos}/{len} ({percent}%)"
+            "[{bar:16}] {msg} ({percent}%)"
         } else {
             // Very minimal for very narrow terminals
-            "{pos}/{len} ({percent}%)"
+            "{msg} ({percent}%)"
         };
 
         ProgressStyle::default_bar()
-            .template(template)
+            .template(&self.styled_template(template))
             .unwrap()
             .progress_chars("█▉▊▋▌▍▎▏ ")
     }
 
+    fn get_spinner_style(&self) -> ProgressStyle {
+        let template = if self.config.terminal_width >= 120 {
+            "{spinner:.green} {msg} • {bytes} • {bytes_per_sec} • {elapsed}"
+        } else if self.config.terminal_width >= 80 {
+            "{spinner:.green} {msg} • {bytes} • {bytes_per_sec}"
+        } else {
+            "{spinner:.green} {msg} • {bytes}"
+        };
+
+        ProgressStyle::default_spinner()
+            .template(&self.styled_template(template))
+            .unwrap()
+    }
+
+    fn get_bytes_progress_style(&self) -> ProgressStyle {
+        let template = if self.config.terminal_width >= 120 {
+            "[{bar:32.cyan/blue}] {bytes}/{total_bytes} ({percent}%) • {bytes_per_sec} • ETA: {eta} • {msg}"
+        } else if self.config.terminal_width >= 80 {
+            "[{bar:24.cyan/blue}] {bytes}/{total_bytes} ({percent}%) • {bytes_per_sec} • ETA: {eta}"
+        } else if self.config.terminal_width >= 60 {
+            "[{bar:16}] {bytes}/{total_bytes} ({percent}%)"
+        } else {
+            "{bytes}/{total_bytes} ({percent}%)"
+        };
+
+        ProgressStyle::default_bar()
+            .template(&self.styled_template(template))
+            .unwrap()
+            .progress_chars("█▉▊▋▌▍▎▏ ")
+    }
+
+    /// Drop the color tokens from a template when ANSI styling is unsupported
+    fn styled_template(&self, template: &str) -> String {
+        if self.config.supports_ansi {
+            template.to_string()
+        } else {
+            template.replace(".cyan/blue", "").replace(":.green", "")
+        }
+    }
+
+    /// Message for the directory bar: the file count, plus the current file when there is room
+    fn format_backing_message(&self, files_completed: usize, current_file: &Path) -> String {
+        let Some(files_total) = self.files_total else {
+            // Without a scan there is only a running count
+            return if self.config.terminal_width >= 120 {
+                format!(
+                    "{files_completed} files • {}",
+                    self.format_progress_message(current_file)
+                )
+            } else {
+                format!("{files_completed} files")
+            };
+        };
+        if self.config.terminal_width >= 120 {
+            format!(
+                "{files_completed}/{files_total} files • {}",
+                self.format_progress_message(current_file)
+            )
+        } else if self.config.terminal_width >= 60 {
+            format!("{files_completed}/{files_total} files")
+        } else {
+            format!("{files_completed}/{files_total}")
+        }
+    }
+
     fn format_progress_message(&self, current_file: &Path) -> String {
         let filename = current_file
             .file_name()
@@ -209,9 +657,10 @@ impl BackupProgress {
             format!("Processing: {filename}")
         } else {
             // For narrower terminals, truncate long filenames
+            // Count characters, not bytes, so multi-byte names are never cut mid-character
             let max_len = (self.config.terminal_width / 3).min(30);
-            if filename.len() > max_len {
-                let truncated = &filename[..max_len.saturating_sub(3)];
+            if filename.chars().count() > max_len {
+                let truncated: String = filename.chars().take(max_len.saturating_sub(3)).collect();
                 format!("{truncated}...")
             } else {
                 filename.to_string()
@@ -225,7 +674,7 @@ pub fn create_progress_bar(
     file_count: usize,
     total_size: u64,
     force_progress: bool,
-) -> Option<BackupProgress> {
+) -> Option<BackupProgress<'static>> {
     if config.should_show_progress(file_count, total_size, force_progress) {
         Some(BackupProgress::new(config.clone()))
     } else {
@@ -233,6 +682,15 @@ pub fn create_progress_bar(
     }
 }
 
+/// Create progress for an operation below the thresholds that only appears if it is slow
+pub fn create_deferred_progress_bar(config: &ProgressConfig) -> Option<BackupProgress<'static>> {
+    if config.enabled {
+        Some(BackupProgress::new_deferred(config.clone()))
+    } else {
+        None
+    }
+}
+
 pub fn should_show_progress(
     config: &ProgressConfig,
     file_count: usize,
@@ -242,6 +700,45 @@ pub fn should_show_progress(
     config.should_show_progress(file_count, total_size, force_progress)
 }
 
+/// Terminal width for choosing progress templates
+///
+/// Detection reports 0 columns when stdout isn't a terminal, which would select the most
+/// minimal templates, so fall back to `FALLBACK_TERMINAL_WIDTH` in that case.
+fn detect_terminal_width(is_interactive: bool) -> usize {
+    let width = console::Term::stdout().size().1 as usize;
+    if !is_interactive || width == 0 {
+        FALLBACK_TERMINAL_WIDTH
+    } else {
+        width
+    }
+}
+
+/// Whether progress templates may use color
+///
+/// `NO_COLOR` (any non-empty value) always disables color; otherwise `CLICOLOR_FORCE`
+/// (any value but `0`) enables it even when output is piped.
+fn detect_ansi_support() -> bool {
+    ansi_support_from_env(
+        std::env::var_os("NO_COLOR"),
+        std::env::var_os("CLICOLOR_FORCE"),
+        console::colors_enabled(),
+    )
+}
+
+fn ansi_support_from_env(
+    no_color: Option<std::ffi::OsString>,
+    clicolor_force: Option<std::ffi::OsString>,
+    detected: bool,
+) -> bool {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        false
+    } else if clicolor_force.is_some_and(|value| !value.is_empty() && value != "0") {
+        true
+    } else {
+        detected
+    }
+}
+
 /// Check if we're running in a CI environment
 fn is_ci_environment() -> bool {
     std::env::var("CI").is_ok()
@@ -253,27 +750,6 @@ fn is_ci_environment() -> bool {
         || std::env::var("BUILDKITE").is_ok()
 }
 
-/// Format byte size in human-readable format
-pub fn format_size(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    const THRESHOLD: u64 = 1024;
-
-    if bytes < THRESHOLD {
-        return format!("{bytes} B");
-    }
-
-    let mut size = bytes as f64;
-    let mut unit_index = 0;
-
-    while size >= THRESHOLD as f64 && unit_index < UNITS.len() - 1 {
-        size /= THRESHOLD as f64;
-        unit_index += 1;
-    }
-
-    let unit = UNITS[unit_index];
-    format!("{size:.1} {unit}")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,6 +762,7 @@ mod tests {
         assert_eq!(config.min_files_threshold, 50);
         assert_eq!(config.min_size_threshold, 10 * 1024 * 1024);
         assert_eq!(config.min_duration_threshold, Duration::from_secs(2));
+        assert_eq!(config.scan_update_interval, 10);
     }
 
     #[test]
@@ -342,6 +819,11 @@ mod tests {
         assert_eq!(format_size(1536), "1.5 KB");
         assert_eq!(format_size(1024 * 1024), "1.0 MB");
         assert_eq!(format_size(1024 * 1024 * 1024), "1.0 GB");
+        // Progress output shares the utils implementation and its default units
+        assert_eq!(
+            format_size(1_500_000),
+            crate::utils::format_size_in(1_500_000, crate::config::SizeUnits::Binary)
+        );
     }
 
     #[test]
@@ -471,6 +953,147 @@ mod tests {
         assert_eq!(progress.bytes_processed, 512 * 1024);
     }
 
+    #[test]
+    fn test_backing_bar_advances_by_bytes() {
+        let config = ProgressConfig {
+            enabled: true,
+            is_interactive: true,
+            terminal_width: 80,
+            ..ProgressConfig::default()
+        };
+        let mut progress = BackupProgress::new(config);
+        let test_path = Path::new("/test/huge.img");
+
+        progress.finish_scanning(10, 1000);
+        let pb = progress.progress_bar.clone().unwrap();
+        assert_eq!(pb.length(), Some(1000));
+
+        // A single huge file moves the bar far more than many tiny ones
+        progress.update_backup_progress(1, 900, test_path);
+        assert_eq!(pb.position(), 900);
+        assert_eq!(pb.message(), "1/10 files");
+
+        progress.update_backup_progress(10, 1000, test_path);
+        assert_eq!(pb.position(), 1000);
+        assert_eq!(pb.message(), "10/10 files");
+    }
+
+    #[test]
+    fn test_start_unscanned_shows_running_totals() {
+        let config = ProgressConfig {
+            enabled: true,
+            is_interactive: true,
+            terminal_width: 80,
+            ..ProgressConfig::default()
+        };
+        let mut progress = BackupProgress::new(config);
+        let test_path = Path::new("/test/file.txt");
+
+        progress.start_unscanned();
+        let pb = progress.progress_bar.clone().unwrap();
+        assert_eq!(pb.length(), None);
+
+        progress.update_backup_progress(3, 300, test_path);
+        assert_eq!(pb.position(), 300);
+        assert_eq!(pb.message(), "3 files");
+        progress.finish();
+    }
+
+    #[test]
+    fn test_format_backing_message() {
+        let test_path = Path::new("/test/file.txt");
+        let message_at = |terminal_width| {
+            let config = ProgressConfig {
+                terminal_width,
+                ..ProgressConfig::default()
+            };
+            let mut progress = BackupProgress::new(config);
+            progress.files_total = Some(20);
+            progress.format_backing_message(5, test_path)
+        };
+
+        assert_eq!(message_at(120), "5/20 files • Processing: file.txt");
+        assert_eq!(message_at(80), "5/20 files");
+        assert_eq!(message_at(40), "5/20");
+    }
+
+    #[test]
+    fn test_start_target_accumulates_progress() {
+        let config = ProgressConfig {
+            enabled: true,
+            is_interactive: false,
+            ..ProgressConfig::default()
+        };
+        let mut progress = BackupProgress::new(config);
+        let test_path = Path::new("/test/backup_file.txt");
+
+        progress.finish_scanning(30, 3000);
+
+        progress.start_target();
+        progress.update_backup_progress(10, 1000, test_path);
+        assert_eq!(progress.files_processed, 10);
+
+        // The second target reports its own counts, which add to the first
+        progress.start_target();
+        progress.update_backup_progress(5, 500, test_path);
+        assert_eq!(progress.files_processed, 15);
+        assert_eq!(progress.bytes_processed, 1500);
+
+        assert_eq!(progress.suspend(|| 42), 42);
+    }
+
+    #[test]
+    fn test_progress_handle_concurrent_workers() {
+        const WORKERS: usize = 8;
+        const FILES_PER_WORKER: usize = 1000;
+
+        let mut events = Vec::new();
+        {
+            let mut progress = BackupProgress::with_callback(ProgressConfig::default(), |event| {
+                if let ProgressEvent::BackingUpdate { files, bytes, .. } = event {
+                    events.push((files, bytes));
+                }
+            });
+            let handle = ProgressHandle::new();
+
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..WORKERS)
+                    .map(|worker| {
+                        let handle = handle.clone();
+                        scope.spawn(move || {
+                            let file = PathBuf::from(format!("worker{worker}.txt"));
+                            for _ in 0..FILES_PER_WORKER {
+                                handle.record(1, 10, &file);
+                            }
+                        })
+                    })
+                    .collect();
+                while !workers.iter().all(|worker| worker.is_finished()) {
+                    progress.sync(&handle);
+                }
+            });
+            progress.sync(&handle);
+            assert_eq!(handle.totals(), (WORKERS * FILES_PER_WORKER, 80_000));
+            assert!(handle.current_file().is_some());
+        }
+
+        // Every snapshot is consistent and the totals never go backward
+        assert!(!events.is_empty());
+        for pair in events.windows(2) {
+            assert!(pair[0].0 <= pair[1].0 && pair[0].1 <= pair[1].1);
+        }
+        for &(files, bytes) in &events {
+            assert!(bytes >= files as u64 * 10);
+        }
+        assert_eq!(
+            events.last(),
+            Some(&(
+                WORKERS * FILES_PER_WORKER,
+                (WORKERS * FILES_PER_WORKER) as u64 * 10
+            ))
+        );
+    }
+
     #[test]
     fn test_get_progress_style_different_terminal_widths() {
         // Test that different terminal widths produce valid progress styles
@@ -534,6 +1157,76 @@ mod tests {
         assert!(message.ends_with("..."));
     }
 
+    #[test]
+    fn test_format_progress_message_multibyte_narrow_terminal() {
+        let path = Path::new("/some/path/очень_длинное_имя_файла.txt");
+
+        // Byte-based truncation would split a Cyrillic character at these widths
+        let config = ProgressConfig {
+            terminal_width: 36,
+            ..ProgressConfig::default()
+        };
+        let message = BackupProgress::new(config).format_progress_message(path);
+        assert_eq!(message, "очень_дли...");
+
+        // 40 columns leave room for 13 characters, the last three being the ellipsis
+        let config = ProgressConfig {
+            terminal_width: 40,
+            ..ProgressConfig::default()
+        };
+        let message = BackupProgress::new(config).format_progress_message(path);
+        assert_eq!(message, "очень_длин...");
+        assert_eq!(message.chars().count(), 13);
+
+        let config = ProgressConfig {
+            terminal_width: 1,
+            ..ProgressConfig::default()
+        };
+        let message = BackupProgress::new(config).format_progress_message(path);
+        assert_eq!(message, "...");
+    }
+
+    #[test]
+    fn test_ansi_support_from_env() {
+        use std::ffi::OsString;
+
+        let set = |value: &str| Some(OsString::from(value));
+        assert!(!ansi_support_from_env(set("1"), None, true));
+        assert!(!ansi_support_from_env(set("1"), set("1"), true));
+        assert!(ansi_support_from_env(set(""), None, true));
+        assert!(ansi_support_from_env(None, set("1"), false));
+        assert!(!ansi_support_from_env(None, set("0"), false));
+        assert!(!ansi_support_from_env(None, None, false));
+    }
+
+    #[test]
+    fn test_styled_template_without_ansi() {
+        let plain = BackupProgress::new(ProgressConfig {
+            supports_ansi: false,
+            ..ProgressConfig::default()
+        });
+        assert_eq!(
+            plain.styled_template("{spinner:.green} [{bar:24.cyan/blue}] {msg}"),
+            "{spinner} [{bar:24}] {msg}"
+        );
+
+        let colored = BackupProgress::new(ProgressConfig {
+            supports_ansi: true,
+            ..ProgressConfig::default()
+        });
+        assert_eq!(
+            colored.styled_template("{spinner:.green}"),
+            "{spinner:.green}"
+        );
+    }
+
+    #[test]
+    fn test_detect_terminal_width_fallback() {
+        // Non-interactive output never reports a zero width
+        assert_eq!(detect_terminal_width(false), FALLBACK_TERMINAL_WIDTH);
+        assert!(ProgressConfig::default().terminal_width > 0);
+    }
+
     #[test]
     fn test_format_progress_message_short_filename() {
         let config = ProgressConfig {
@@ -560,6 +1253,172 @@ mod tests {
         assert_eq!(message, "Processing: ...");
     }
 
+    #[test]
+    fn test_deferred_progress_activation() {
+        let config = ProgressConfig {
+            enabled: true,
+            is_interactive: true,
+            min_duration_threshold: Duration::from_secs(3600),
+            ..ProgressConfig::default()
+        };
+        let test_path = Path::new("/test/slow_file.txt");
+
+        // Stays hidden while the operation is fast
+        let mut progress = BackupProgress::new_deferred(config.clone());
+        progress.start_scanning();
+        assert!(progress.progress_bar.is_none());
+        progress.finish_scanning(5, 1024);
+        progress.update_backup_progress(1, 100, test_path);
+        assert!(progress.progress_bar.is_none());
+
+        // Appears once the duration threshold has passed
+        let config = ProgressConfig {
+            min_duration_threshold: Duration::from_secs(0),
+            ..config
+        };
+        let mut progress = BackupProgress::new_deferred(config.clone());
+        progress.start_scanning();
+        progress.finish_scanning(5, 1024);
+        assert!(progress.progress_bar.is_none());
+        progress.update_backup_progress(1, 100, test_path);
+        assert!(progress.progress_bar.is_some());
+        progress.finish();
+
+        // A slow scan shows a spinner before any totals are known
+        let mut progress = BackupProgress::new_deferred(config);
+        progress.start_scanning();
+        assert!(progress.progress_bar.is_none());
+        progress.update_scan_progress(10, test_path);
+        assert!(progress.progress_bar.is_some());
+        progress.finish();
+    }
+
+    #[test]
+    fn test_scan_update_interval() {
+        let config = ProgressConfig {
+            scan_update_interval: 25,
+            ..ProgressConfig::default()
+        };
+        let progress = BackupProgress::new(config);
+        assert!(!progress.scan_update_due(10));
+        assert!(progress.scan_update_due(25));
+        assert!(progress.scan_update_due(50));
+
+        // A zero interval updates on every file instead of dividing by zero
+        let progress = BackupProgress::new(ProgressConfig {
+            scan_update_interval: 0,
+            ..ProgressConfig::default()
+        });
+        assert!(progress.scan_update_due(7));
+    }
+
+    #[test]
+    fn test_create_deferred_progress_bar() {
+        let config = ProgressConfig::default();
+        assert!(create_deferred_progress_bar(&config).is_some());
+
+        let config = ProgressConfig {
+            enabled: false,
+            ..ProgressConfig::default()
+        };
+        assert!(create_deferred_progress_bar(&config).is_none());
+    }
+
+    #[test]
+    fn test_file_copy_progress() {
+        let config = ProgressConfig {
+            enabled: true,
+            is_interactive: true,
+            ..ProgressConfig::default()
+        };
+        let mut progress = BackupProgress::new(config);
+        let test_path = Path::new("/test/disk.img");
+
+        progress.start_file_copy(1024 * 1024, test_path);
+        assert!(matches!(progress.phase, ProgressPhase::Backing));
+        assert_eq!(progress.bytes_total, Some(1024 * 1024));
+        assert!(progress.progress_bar.is_some());
+
+        progress.update_bytes_progress(512 * 1024);
+        assert_eq!(progress.bytes_processed, 512 * 1024);
+        assert_eq!(
+            progress.progress_bar.as_ref().unwrap().position(),
+            512 * 1024
+        );
+
+        progress.finish();
+        assert!(progress.progress_bar.is_none());
+    }
+
+    #[test]
+    fn test_progress_format_parse() {
+        assert_eq!(ProgressFormat::parse("bar"), Some(ProgressFormat::Bar));
+        assert_eq!(ProgressFormat::parse(" JSON "), Some(ProgressFormat::Json));
+        assert_eq!(ProgressFormat::parse("xml"), None);
+        assert_eq!(ProgressFormat::Json.as_str(), "json");
+    }
+
+    #[test]
+    fn test_json_format_never_draws_bars() {
+        let config = ProgressConfig {
+            enabled: true,
+            is_interactive: true,
+            format: ProgressFormat::Json,
+            ..ProgressConfig::default()
+        };
+        let mut progress = BackupProgress::new(config);
+        let test_path = Path::new("/test/file.txt");
+
+        progress.start_scanning();
+        progress.update_scan_progress(3, test_path);
+        progress.finish_scanning(3, 300);
+        progress.update_backup_progress(1, 100, test_path);
+        assert!(progress.progress_bar.is_none());
+        assert_eq!(progress.files_processed, 1);
+
+        progress.start_file_copy(1024, test_path);
+        progress.update_bytes_progress(512);
+        assert!(progress.progress_bar.is_none());
+        assert_eq!(progress.bytes_processed, 512);
+        progress.finish();
+    }
+
+    #[test]
+    fn test_callback_receives_events() {
+        let mut events = Vec::new();
+        let config = ProgressConfig {
+            is_interactive: true,
+            ..Default::default()
+        };
+
+        let mut progress = BackupProgress::with_callback(config, |event| events.push(event));
+        progress.start_scanning();
+        progress.update_scan_progress(1, Path::new("a.txt"));
+        progress.finish_scanning(1, 10);
+        progress.update_backup_progress(1, 10, Path::new("a.txt"));
+        assert!(progress.progress_bar.is_none());
+        progress.finish();
+        drop(progress);
+
+        assert_eq!(
+            events,
+            vec![
+                ProgressEvent::ScanStarted,
+                ProgressEvent::ScanUpdate { files: 1 },
+                ProgressEvent::ScanFinished {
+                    files: 1,
+                    bytes: 10
+                },
+                ProgressEvent::BackingUpdate {
+                    files: 1,
+                    bytes: 10,
+                    current: PathBuf::from("a.txt"),
+                },
+                ProgressEvent::Finished,
+            ]
+        );
+    }
+
     #[test]
     fn test_finish_clears_progress_bar() {
         let config = ProgressConfig {
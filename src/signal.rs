@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -8,6 +9,7 @@ use std::sync::{Arc, Mutex};
 pub struct BackupContext {
     interrupt_flag: Arc<AtomicBool>,
     active_operations: Arc<Mutex<HashSet<PathBuf>>>,
+    keep_partial: Arc<AtomicBool>,
 }
 
 impl BackupContext {
@@ -16,6 +18,7 @@ impl BackupContext {
         Self {
             interrupt_flag: Arc::new(AtomicBool::new(false)),
             active_operations: Arc::new(Mutex::new(HashSet::new())),
+            keep_partial: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -34,6 +37,51 @@ impl BackupContext {
         self.interrupt_flag.store(interrupted, Ordering::SeqCst);
     }
 
+    /// Keep incomplete backups on interrupt instead of deleting them (`keep_partial_on_interrupt`)
+    pub fn set_keep_partial(&self, keep: bool) {
+        self.keep_partial.store(keep, Ordering::SeqCst);
+    }
+
+    /// Whether incomplete backups are kept on interrupt
+    pub fn keeps_partial(&self) -> bool {
+        self.keep_partial.load(Ordering::SeqCst)
+    }
+
+    /// Install process signal handlers that set this context's interrupt flag
+    ///
+    /// Handles SIGINT (Ctrl-C), SIGTERM and SIGHUP on Unix, and Ctrl-C/Ctrl-Break on Windows,
+    /// so the copy loops stop and incomplete backups get cleaned up. Can only be called once
+    /// per process.
+    pub fn install_signal_handlers(&self) -> crate::Result<()> {
+        ctrlc::set_handler(self.interrupt_handler()).map_err(|e| {
+            crate::error::QbakError::Io(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Could not install signal handlers: {e}"),
+            ))
+        })
+    }
+
+    /// The callback run on SIGINT, SIGTERM or SIGHUP: flags this context as interrupted
+    fn interrupt_handler(&self) -> impl Fn() + Send + 'static {
+        let interrupt_flag = self.interrupt_flag();
+        move || {
+            interrupt_flag.store(true, Ordering::SeqCst);
+            eprintln!("\nInterrupted, cleaning up...");
+        }
+    }
+
+    /// Run `f` with this context as the current one on this thread
+    ///
+    /// Library calls made inside `f` check this context's interrupt flag and register their
+    /// partial backups with it, so backups running concurrently on separate threads each keep
+    /// their own interrupt state and cleanup registry. Scopes nest; the previous context is
+    /// restored when `f` returns or panics.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_CONTEXT.with(|current| current.replace(Some(self.clone())));
+        let _restore = ScopeRestore(previous);
+        f()
+    }
+
     /// Register a backup operation for cleanup tracking
     pub fn register_operation(&self, backup_path: PathBuf) -> BackupOperationGuard {
         if let Ok(mut operations) = self.active_operations.lock() {
@@ -51,21 +99,32 @@ impl BackupContext {
     }
 
     /// Clean up all active backup operations
-    pub fn cleanup_active_operations(&self) {
-        self.cleanup_active_operations_with_mode(false);
+    ///
+    /// Returns `false` if any partial backup could not be removed.
+    pub fn cleanup_active_operations(&self) -> bool {
+        self.cleanup_active_operations_with_mode(false)
     }
 
     /// Clean up all active backup operations with optional silent mode
-    pub fn cleanup_active_operations_with_mode(&self, silent: bool) {
+    ///
+    /// Returns `false` if any partial backup could not be removed. With `set_keep_partial`,
+    /// nothing is removed; the incomplete backups are listed instead.
+    pub fn cleanup_active_operations_with_mode(&self, silent: bool) -> bool {
         let active_ops = self.get_active_operations();
+        let mut all_removed = true;
 
         for backup_path in active_ops {
-            if backup_path.exists() {
+            if self.keeps_partial() {
+                if !silent && backup_path.exists() {
+                    eprintln!("Kept incomplete backup: {}", backup_path.display());
+                }
+            } else if backup_path.exists() {
                 let cleanup_result = if backup_path.is_dir() {
                     std::fs::remove_dir_all(&backup_path)
                 } else {
                     std::fs::remove_file(&backup_path)
                 };
+                all_removed &= cleanup_result.is_ok();
 
                 // Log cleanup attempt (unless silent)
                 if !silent {
@@ -85,6 +144,8 @@ impl BackupContext {
         if let Ok(mut operations) = self.active_operations.lock() {
             operations.clear();
         }
+
+        all_removed
     }
 
     /// Remove an operation from tracking (internal use)
@@ -101,11 +162,31 @@ impl Default for BackupContext {
     }
 }
 
-// Global context for backward compatibility (will be removed in favor of instance-based)
+thread_local! {
+    /// Context entered with `BackupContext::scope` on this thread
+    static CURRENT_CONTEXT: RefCell<Option<BackupContext>> = const { RefCell::new(None) };
+}
+
+/// Restores the previously current context when a scope ends
+struct ScopeRestore(Option<BackupContext>);
+
+impl Drop for ScopeRestore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT_CONTEXT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+// Process-wide fallback for callers that don't enter a context
 static GLOBAL_CONTEXT: Mutex<Option<BackupContext>> = Mutex::new(None);
 
 /// Set the global backup context
+#[deprecated(note = "concurrent backups share the global context; use `BackupContext::scope`")]
 pub fn set_global_context(context: BackupContext) {
+    store_global_context(context);
+}
+
+fn store_global_context(context: BackupContext) {
     if let Ok(mut global) = GLOBAL_CONTEXT.lock() {
         *global = Some(context);
     }
@@ -116,22 +197,40 @@ fn get_global_context() -> Option<BackupContext> {
     GLOBAL_CONTEXT.lock().ok().and_then(|global| global.clone())
 }
 
+/// Get the context library calls on this thread use
+///
+/// This is the context entered with `BackupContext::scope`, falling back to the global one.
+pub fn current_context() -> Option<BackupContext> {
+    CURRENT_CONTEXT
+        .with(|current| current.borrow().clone())
+        .or_else(get_global_context)
+}
+
 /// Set the global interrupt flag
+#[deprecated(note = "concurrent backups share the global context; use `BackupContext::scope`")]
 pub fn set_interrupt_flag(flag: Arc<AtomicBool>) {
     let context = BackupContext {
         interrupt_flag: flag,
         active_operations: Arc::new(Mutex::new(HashSet::new())),
+        keep_partial: Arc::new(AtomicBool::new(false)),
     };
-    set_global_context(context);
+    store_global_context(context);
 }
 
-/// Check if an interrupt has been requested
+/// Check if an interrupt has been requested in the current context
 pub fn is_interrupted() -> bool {
-    get_global_context()
+    current_context()
         .map(|ctx| ctx.is_interrupted())
         .unwrap_or(false)
 }
 
+/// Whether incomplete backups in the current context are kept on interrupt
+pub fn keeps_partial() -> bool {
+    current_context()
+        .map(|ctx| ctx.keeps_partial())
+        .unwrap_or(false)
+}
+
 /// Reset global state for testing
 #[cfg(test)]
 pub fn reset_for_testing() {
@@ -168,6 +267,8 @@ impl BackupOperationGuard {
 }
 
 impl Drop for BackupOperationGuard {
+    /// Leaves an interrupted operation registered, so `cleanup_active_operations` removes it
+    /// or, with `keep_partial_on_interrupt`, lists it as kept
     fn drop(&mut self) {
         if self.registered && !self.completed {
             // If not interrupted, remove from tracking (normal failure/panic)
@@ -180,33 +281,37 @@ impl Drop for BackupOperationGuard {
     }
 }
 
-/// Get a snapshot of currently active operations
+/// Get a snapshot of active operations in the current context
 pub fn get_active_operations() -> Vec<PathBuf> {
-    get_global_context()
+    current_context()
         .map(|ctx| ctx.get_active_operations())
         .unwrap_or_default()
 }
 
-/// Clean up all active backup operations
-pub fn cleanup_active_operations() {
-    cleanup_active_operations_with_mode(false);
+/// Clean up all active backup operations in the current context
+///
+/// Returns `false` if any partial backup could not be removed.
+pub fn cleanup_active_operations() -> bool {
+    cleanup_active_operations_with_mode(false)
 }
 
-/// Clean up all active backup operations with optional silent mode
-pub fn cleanup_active_operations_with_mode(silent: bool) {
-    if let Some(context) = get_global_context() {
-        context.cleanup_active_operations_with_mode(silent);
-    }
+/// Clean up all active backup operations in the current context with optional silent mode
+///
+/// Returns `false` if any partial backup could not be removed.
+pub fn cleanup_active_operations_with_mode(silent: bool) -> bool {
+    current_context()
+        .map(|context| context.cleanup_active_operations_with_mode(silent))
+        .unwrap_or(true)
 }
 
-/// Create a BackupOperationGuard using the global context
+/// Create a BackupOperationGuard using the current context
 pub fn create_backup_guard(backup_path: PathBuf) -> BackupOperationGuard {
-    if let Some(context) = get_global_context() {
+    if let Some(context) = current_context() {
         context.register_operation(backup_path)
     } else {
-        // Fallback if no global context is set
+        // Fallback if no context is set
         let context = BackupContext::new();
-        set_global_context(context.clone());
+        store_global_context(context.clone());
         context.register_operation(backup_path)
     }
 }
@@ -217,6 +322,109 @@ mod tests {
     use std::fs;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_interrupt_handler_sets_context_flag() {
+        let context = BackupContext::new();
+        let other = BackupContext::new();
+        let handler = context.interrupt_handler();
+        assert!(!context.is_interrupted());
+
+        handler();
+        assert!(context.is_interrupted());
+        assert!(!other.is_interrupted());
+
+        // Library code running in the context's scope sees the interrupt
+        assert!(context.scope(is_interrupted));
+        assert!(!other.scope(is_interrupted));
+    }
+
+    /// Set in the child process spawned by `test_install_signal_handlers_sigterm`
+    #[cfg(target_os = "linux")]
+    const SIGNAL_CHILD_ENV: &str = "QBAK_SIGNAL_TEST_CHILD";
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_install_signal_handlers_sigterm() {
+        // The handler is process-wide and can only be installed once, so the real signal is
+        // sent in a separate test process
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "signal::tests::sigterm_child",
+                "--test-threads=1",
+            ])
+            .env(SIGNAL_CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 passed"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn sigterm_child() {
+        use std::time::{Duration, Instant};
+
+        if std::env::var_os(SIGNAL_CHILD_ENV).is_none() {
+            return;
+        }
+
+        let context = BackupContext::new();
+        context.install_signal_handlers().unwrap();
+        assert!(!context.is_interrupted());
+
+        // SAFETY: raising a signal we have a handler installed for
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+
+        // The handler runs on a separate thread, so give it a moment
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !context.is_interrupted() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(context.is_interrupted());
+    }
+
+    #[test]
+    fn test_scope_isolates_concurrent_contexts() {
+        let dir = tempdir().unwrap();
+        let first = BackupContext::new();
+        let second = BackupContext::new();
+
+        let handles: Vec<_> = [(first.clone(), "first"), (second.clone(), "second")]
+            .into_iter()
+            .map(|(context, name)| {
+                let backup_path = dir.path().join(name);
+                std::thread::spawn(move || {
+                    context.scope(|| {
+                        let _guard = create_backup_guard(backup_path.clone());
+                        assert_eq!(get_active_operations(), vec![backup_path]);
+                        assert!(!is_interrupted());
+                    })
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Interrupting one context leaves the other untouched
+        first.set_interrupted(true);
+        assert!(first.scope(is_interrupted));
+        assert!(!second.scope(is_interrupted));
+
+        // Scopes nest and restore the outer context
+        second.scope(|| {
+            first.scope(|| assert!(is_interrupted()));
+            assert!(!is_interrupted());
+        });
+    }
+
     #[test]
     fn test_backup_operation_guard_normal_completion() {
         let dir = tempdir().unwrap();
@@ -287,7 +495,7 @@ mod tests {
         assert!(backup_path2.exists());
 
         // Cleanup all active operations
-        context.cleanup_active_operations();
+        assert!(context.cleanup_active_operations());
 
         // Verify they're no longer tracked
         let active_ops = context.get_active_operations();
@@ -299,6 +507,37 @@ mod tests {
         assert!(!backup_path2.exists());
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_cleanup_reports_failed_removal() {
+        // procfs entries can't be removed, even by root
+        let undeletable = PathBuf::from("/proc/version");
+        let context = BackupContext::new();
+        let _guard = context.register_operation(undeletable.clone());
+
+        assert!(!context.cleanup_active_operations_with_mode(true));
+        assert!(undeletable.exists());
+        assert!(context.get_active_operations().is_empty());
+    }
+
+    #[test]
+    fn test_cleanup_keeps_partials_when_configured() {
+        let dir = tempdir().unwrap();
+        let partial = dir.path().join(".qbak_temp_abc_data");
+        fs::create_dir(&partial).unwrap();
+        fs::write(partial.join("file.txt"), "half").unwrap();
+
+        let context = BackupContext::new();
+        context.set_keep_partial(true);
+        let guard = context.register_operation(partial.clone());
+        context.set_interrupted(true);
+        drop(guard);
+
+        assert!(context.cleanup_active_operations_with_mode(true));
+        assert!(partial.join("file.txt").exists());
+        assert!(context.get_active_operations().is_empty());
+    }
+
     #[test]
     fn test_cleanup_nonexistent_operations() {
         let dir = tempdir().unwrap();
@@ -310,7 +549,7 @@ mod tests {
         let _guard = context.register_operation(backup_path.clone());
 
         // Cleanup should handle gracefully
-        context.cleanup_active_operations();
+        assert!(context.cleanup_active_operations());
 
         // Should not panic or error
         let active_ops = context.get_active_operations();
@@ -1,14 +1,20 @@
+use crate::config::{Config, SizeUnits};
 use crate::error::QbakError;
 use crate::Result;
+use filetime::FileTime;
 use fs2::available_space;
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use rand::distr::Alphanumeric;
+use rand::Rng;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 /// Validate that a source path exists and is readable
-pub fn validate_source(path: &Path) -> Result<()> {
+///
+/// With `allow_outside_cwd` off, sources that resolve outside the current directory tree
+/// (via `..` or a symlink) are rejected as path traversal.
+pub fn validate_source(path: &Path, config: &Config) -> Result<()> {
     // Check if path exists
     if !path.exists() {
         return Err(QbakError::SourceNotFound {
@@ -20,8 +26,8 @@ pub fn validate_source(path: &Path) -> Result<()> {
     let canonical_path = match path.canonicalize() {
         Ok(p) => p,
         Err(_) => {
-            // If canonicalization fails, fall back to string check for backwards compatibility
-            if path.to_string_lossy().contains("..") {
+            // If canonicalization fails, fall back to checking the input for `..` components
+            if has_parent_component(path) {
                 return Err(QbakError::PathTraversal {
                     path: path.to_path_buf(),
                 });
@@ -31,28 +37,32 @@ pub fn validate_source(path: &Path) -> Result<()> {
     };
 
     // Ensure the canonical path doesn't contain suspicious patterns
-    let path_str = canonical_path.to_string_lossy();
-    if path_str.contains("..") {
+    if has_parent_component(&canonical_path) {
         return Err(QbakError::PathTraversal {
             path: path.to_path_buf(),
         });
     }
 
-    // Additional check: ensure the canonical path is within reasonable bounds
-    // This prevents attacks using symlinks to escape intended directories
-    let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-    if let Ok(current_canonical) = current_dir.canonicalize() {
-        // Only allow paths that are within or relative to current working directory tree
-        // This is a reasonable security boundary for a backup tool
-        if !canonical_path.starts_with(current_canonical.parent().unwrap_or(&current_canonical)) {
-            // Allow absolute paths but log for security awareness
-            // In a backup tool, users may legitimately want to backup system files
+    // Compare resolved paths, so symlinks that escape the tree are caught as well as `..`
+    if !config.allow_outside_cwd {
+        let current_dir = std::env::current_dir()?.canonicalize()?;
+        if !current_dir.join(&canonical_path).starts_with(&current_dir) {
+            return Err(QbakError::PathTraversal {
+                path: path.to_path_buf(),
+            });
         }
     }
 
     // Try to read metadata to check permissions
     match fs::metadata(path) {
-        Ok(_) => Ok(()),
+        Ok(metadata) => match special_file_kind(&metadata.file_type()) {
+            // Reading a FIFO or device would block or never end
+            Some(kind) => Err(QbakError::UnsupportedFileType {
+                path: path.to_path_buf(),
+                kind: kind.to_string(),
+            }),
+            None => Ok(()),
+        },
         Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
             Err(QbakError::PermissionDenied {
                 path: path.to_path_buf(),
@@ -62,20 +72,62 @@ pub fn validate_source(path: &Path) -> Result<()> {
     }
 }
 
+/// Check for a genuine `..` component, so names like `foo..bar.txt` aren't mistaken for one
+fn has_parent_component(path: &Path) -> bool {
+    path.components()
+        .any(|component| component == std::path::Component::ParentDir)
+}
+
+/// Name the kind of a special file (FIFO, socket or device), or `None` for anything else
+#[cfg(unix)]
+pub fn special_file_kind(file_type: &fs::FileType) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        Some("FIFO")
+    } else if file_type.is_socket() {
+        Some("socket")
+    } else if file_type.is_block_device() {
+        Some("block device")
+    } else if file_type.is_char_device() {
+        Some("character device")
+    } else {
+        None
+    }
+}
+
+/// Name the kind of a special file (FIFO, socket or device), or `None` for anything else
+#[cfg(not(unix))]
+pub fn special_file_kind(_file_type: &fs::FileType) -> Option<&'static str> {
+    None
+}
+
 /// Check if there's enough disk space for the backup operation
-pub fn check_available_space(source: &Path, target_dir: &Path) -> Result<()> {
+pub fn check_available_space(source: &Path, target_dir: &Path, config: &Config) -> Result<()> {
     // Calculate size needed
     let needed_size = calculate_size(source)?;
 
+    check_available_space_for(needed_size, target_dir, config)
+}
+
+/// Check that a target directory has room for `needed_size` bytes plus a safety buffer, and
+/// that at least `min_free_space` bytes stay free afterwards
+pub fn check_available_space_for(
+    needed_size: u64,
+    target_dir: &Path,
+    config: &Config,
+) -> Result<()> {
     // Get available space in target directory
     let available_size = get_available_space(target_dir)?;
 
-    // Add 10% buffer for metadata and safety
-    let needed_with_buffer = needed_size + (needed_size / 10);
+    // Both the buffer and the free-space floor must fit after the backup, so the larger one counts
+    let buffer = space_buffer(needed_size, config).max(config.min_free_space);
+    let needed_with_buffer = needed_size.saturating_add(buffer);
 
     if available_size < needed_with_buffer {
         return Err(QbakError::InsufficientSpace {
             needed: needed_with_buffer,
+            buffer,
             available: available_size,
         });
     }
@@ -83,6 +135,17 @@ pub fn check_available_space(source: &Path, target_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Safety buffer for metadata on top of `needed_size`: `space_buffer_percent` of it, but at
+/// least `space_buffer_min` bytes
+pub fn space_buffer(needed_size: u64, config: &Config) -> u64 {
+    let percent = u64::from(config.space_buffer_percent.min(MAX_SPACE_BUFFER_PERCENT));
+    let proportional = (u128::from(needed_size) * u128::from(percent) / 100) as u64;
+    proportional.max(config.space_buffer_min)
+}
+
+/// Largest accepted `space_buffer_percent`; more than doubling the need is never useful
+pub const MAX_SPACE_BUFFER_PERCENT: u32 = 100;
+
 /// Validate that a backup filename is acceptable
 pub fn validate_backup_filename(path: &Path) -> Result<()> {
     if path.exists() {
@@ -90,9 +153,20 @@ pub fn validate_backup_filename(path: &Path) -> Result<()> {
             path: path.to_path_buf(),
         });
     }
+    check_backup_dir_writable(path)
+}
 
-    // Check if parent directory is writable
-    if let Some(parent) = path.parent() {
+/// Check that the directory a backup would be written to accepts new files
+pub fn check_backup_dir_writable(path: &Path) -> Result<()> {
+    // Check if parent directory is writable (a bare file name lives in the current directory)
+    let parent = path.parent().map(|parent| {
+        if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        }
+    });
+    if let Some(parent) = parent {
         if parent.exists() && fs::metadata(parent).is_ok() {
             // Try to create a temporary file to test write permissions
             let process_id = std::process::id();
@@ -122,15 +196,107 @@ pub fn validate_backup_filename(path: &Path) -> Result<()> {
     }
 }
 
+/// Reject a backup path that is the source itself or lies inside a source directory
+///
+/// A backup written into the tree being copied would include itself. Both sides are
+/// canonicalized; the backup's directory may not exist yet, so it is resolved through its
+/// nearest existing ancestor, and the backup name itself is never followed (an existing
+/// backup may be a preserved symlink). A source that can't be resolved is left to
+/// `validate_source`.
+pub fn validate_backup_location(source: &Path, backup_path: &Path) -> Result<()> {
+    let Ok(canonical_source) = source.canonicalize() else {
+        return Ok(());
+    };
+    let canonical_backup = match (backup_path.parent(), backup_path.file_name()) {
+        (Some(parent), Some(name)) => canonicalize_partial(parent)?.join(name),
+        _ => canonicalize_partial(backup_path)?,
+    };
+
+    let nested = canonical_source.is_dir() && canonical_backup.starts_with(&canonical_source);
+    if canonical_backup == canonical_source || nested {
+        return Err(QbakError::validation(format!(
+            "Backup {} would be inside the source {}; choose an output directory outside it",
+            backup_path.display(),
+            source.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Whether two paths name the same directory entry, without following the entries themselves
+pub(crate) fn is_same_location(a: &Path, b: &Path) -> Result<bool> {
+    let resolve = |path: &Path| match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => canonicalize_partial(parent).map(|dir| dir.join(name)),
+        _ => canonicalize_partial(path),
+    };
+    Ok(resolve(a)? == resolve(b)?)
+}
+
+/// Canonicalize the longest existing prefix of `path` and append the rest unchanged
+fn canonicalize_partial(path: &Path) -> Result<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return Ok(missing
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name)));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return Ok(absolute),
+        }
+    }
+}
+
 /// Generate a cryptographically secure random string for temporary file names
 pub fn generate_secure_random_string(length: usize) -> String {
-    thread_rng()
+    rand::rng()
         .sample_iter(&Alphanumeric)
         .take(length)
         .map(char::from)
         .collect()
 }
 
+/// Inodes of hardlinked files seen so far, so their data is only counted once
+///
+/// Only files with more than one link are tracked. Without inode information (on
+/// non-Unix platforms) every file counts as a first link.
+#[derive(Default)]
+pub(crate) struct InodeSet {
+    seen: HashSet<(u64, u64)>,
+}
+
+impl InodeSet {
+    /// Whether `metadata` belongs to the first link seen to its inode
+    pub(crate) fn first_link(&mut self, metadata: &fs::Metadata) -> bool {
+        inode_key(metadata).map_or(true, |inode| self.seen.insert(inode))
+    }
+}
+
+/// Device and inode number of a file with more than one hardlink
+#[cfg(unix)]
+pub(crate) fn inode_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+/// Device and inode number of a file with more than one hardlink
+#[cfg(not(unix))]
+pub(crate) fn inode_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
 /// Calculate the total size of a file or directory
 pub fn calculate_size(path: &Path) -> Result<u64> {
     let metadata = fs::metadata(path)?;
@@ -146,11 +312,18 @@ pub fn calculate_size(path: &Path) -> Result<u64> {
 }
 
 /// Calculate the total size of a directory recursively
+///
+/// Files hardlinked more than once within the directory count once.
 fn calculate_directory_size(dir: &Path) -> Result<u64> {
     let mut total_size = 0;
     let mut visited = HashSet::new();
 
-    calculate_directory_size_recursive(dir, &mut total_size, &mut visited)?;
+    calculate_directory_size_recursive(
+        dir,
+        &mut total_size,
+        &mut visited,
+        &mut InodeSet::default(),
+    )?;
 
     Ok(total_size)
 }
@@ -160,6 +333,7 @@ fn calculate_directory_size_recursive(
     dir: &Path,
     total_size: &mut u64,
     visited: &mut HashSet<PathBuf>,
+    inodes: &mut InodeSet,
 ) -> Result<()> {
     // Resolve symlinks to detect cycles
     let canonical = match dir.canonicalize() {
@@ -181,9 +355,11 @@ fn calculate_directory_size_recursive(
         let metadata = entry.metadata()?;
 
         if metadata.is_file() {
-            *total_size += metadata.len();
+            if inodes.first_link(&metadata) {
+                *total_size += metadata.len();
+            }
         } else if metadata.is_dir() {
-            calculate_directory_size_recursive(&path, total_size, visited)?;
+            calculate_directory_size_recursive(&path, total_size, visited, inodes)?;
         } else {
             // Symlink or special file
             *total_size += metadata.len();
@@ -242,47 +418,452 @@ pub fn copy_permissions(source: &Path, dest: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Copy timestamps from source to destination
-pub fn copy_timestamps(source: &Path, _dest: &Path) -> Result<()> {
-    let _metadata = fs::metadata(source)?;
-
-    // On Unix systems, we can set access and modification times
+/// Set the Unix mode of `path`, as for `dir_mode` and `file_mode`; does nothing elsewhere
+pub fn set_mode(path: &Path, mode: u32) -> Result<()> {
     #[cfg(unix)]
     {
-        use std::os::unix::fs::MetadataExt;
-        use std::time::{Duration, UNIX_EPOCH};
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+/// Create a directory and any missing parents accessible to the owner only (on Unix)
+///
+/// For directories whose final mode is set once their contents are written, so nothing
+/// is readable by others in the meantime.
+pub(crate) fn create_private_dir_all(path: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)?;
+    Ok(())
+}
+
+/// Copy timestamps from source to destination
+pub fn copy_timestamps(source: &Path, dest: &Path) -> Result<()> {
+    let metadata = fs::metadata(source)?;
+
+    let atime = FileTime::from_last_access_time(&metadata);
+    let mtime = FileTime::from_last_modification_time(&metadata);
+    filetime::set_file_times(dest, atime, mtime)?;
+
+    Ok(())
+}
+
+/// Modification time in nanoseconds since the Unix epoch
+///
+/// Unlike `MetadataExt::mtime`, this distinguishes writes made within the same second.
+#[cfg(unix)]
+pub fn mtime_nanos(metadata: &fs::Metadata) -> i128 {
+    use std::os::unix::fs::MetadataExt;
+
+    i128::from(metadata.mtime()) * 1_000_000_000 + i128::from(metadata.mtime_nsec())
+}
+
+/// Modification time in nanoseconds since the Unix epoch
+#[cfg(not(unix))]
+pub fn mtime_nanos(metadata: &fs::Metadata) -> i128 {
+    let mtime = FileTime::from_last_modification_time(metadata);
+    i128::from(mtime.unix_seconds()) * 1_000_000_000 + i128::from(mtime.nanoseconds())
+}
 
-        let _atime = UNIX_EPOCH + Duration::from_secs(_metadata.atime() as u64);
-        let _mtime = UNIX_EPOCH + Duration::from_secs(_metadata.mtime() as u64);
+/// Whether this build can copy POSIX ACLs (the `acl` feature on Linux)
+pub const ACL_SUPPORT: bool = cfg!(all(feature = "acl", target_os = "linux"));
 
-        // Use utime crate if available, or filetime
-        // For now, we'll skip this in the basic implementation
+/// Copy POSIX ACLs (and default ACLs for directories) from source to destination
+#[cfg(all(feature = "acl", target_os = "linux"))]
+pub fn copy_acls(source: &Path, dest: &Path) -> Result<()> {
+    use posix_acl::PosixACL;
+
+    let acl_error = |e: posix_acl::ACLError| QbakError::validation(e.to_string());
+
+    let mut acl = PosixACL::read_acl(source).map_err(acl_error)?;
+    acl.write_acl(dest).map_err(acl_error)?;
+
+    if source.is_dir() {
+        let mut default_acl = PosixACL::read_default_acl(source).map_err(acl_error)?;
+        default_acl.write_default_acl(dest).map_err(acl_error)?;
     }
 
     Ok(())
 }
 
+/// Copy POSIX ACLs (unavailable without the `acl` feature on Linux)
+#[cfg(not(all(feature = "acl", target_os = "linux")))]
+pub fn copy_acls(_source: &Path, _dest: &Path) -> Result<()> {
+    Err(QbakError::validation(
+        "ACL support is not available in this build (requires the `acl` feature on Linux)",
+    ))
+}
+
+/// Compute the SHA-256 checksum of a file, checking for interrupts between chunks
+pub fn sha256_file(path: &Path) -> Result<[u8; 32]> {
+    sha256_reader(fs::File::open(path)?)
+}
+
+/// Compute the SHA-256 checksum of everything read from `reader`
+pub fn sha256_reader(mut reader: impl std::io::Read) -> Result<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024]; // 64KB buffer
+
+    loop {
+        if crate::signal::is_interrupted() {
+            return Err(QbakError::Interrupted);
+        }
+
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+/// Try to clone a file with a copy-on-write reflink (btrfs, XFS)
+///
+/// Returns `Ok(false)` when the filesystem or platform doesn't support reflinks,
+/// so the caller can fall back to a regular copy.
+#[cfg(target_os = "linux")]
+pub fn try_reflink(source: &Path, dest: &Path) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // FICLONE = _IOW(0x94, 9, int)
+    const FICLONE: libc::c_ulong = 0x4004_9409;
+
+    let source_file = fs::File::open(source)?;
+    let dest_file = fs::File::create(dest)?;
+
+    // SAFETY: both file descriptors are valid for the duration of the call
+    let ret = unsafe { libc::ioctl(dest_file.as_raw_fd(), FICLONE as _, source_file.as_raw_fd()) };
+    if ret == 0 {
+        return Ok(true);
+    }
+
+    let error = std::io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EXDEV)
+        | Some(libc::EOPNOTSUPP)
+        | Some(libc::ENOTTY)
+        | Some(libc::EINVAL)
+        | Some(libc::ENOSYS) => Ok(false),
+        _ => Err(QbakError::Io(error)),
+    }
+}
+
+/// Reflinks are only supported on Linux; always fall back to a regular copy
+#[cfg(not(target_os = "linux"))]
+pub fn try_reflink(_source: &Path, _dest: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// Copy a sparse file by writing only its data segments and seeking over holes
+///
+/// Returns `Ok(false)` without touching `dest` when the source has no holes or
+/// `SEEK_DATA`/`SEEK_HOLE` aren't supported, so the caller can do a dense copy.
+#[cfg(target_os = "linux")]
+pub fn try_sparse_copy(source: &Path, dest: &Path) -> Result<bool> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let mut source_file = fs::File::open(source)?;
+    let metadata = source_file.metadata()?;
+    let length = metadata.len();
+
+    // Files using at least as many blocks as their length have no holes
+    if metadata.blocks() * 512 >= length {
+        return Ok(false);
+    }
+
+    let fd = source_file.as_raw_fd();
+    let seek = |offset: u64, whence: libc::c_int| -> std::io::Result<Option<u64>> {
+        // SAFETY: fd stays valid while source_file is alive
+        let ret = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+        if ret >= 0 {
+            return Ok(Some(ret as u64));
+        }
+        let error = std::io::Error::last_os_error();
+        match error.raw_os_error() {
+            // No more data after offset
+            Some(libc::ENXIO) => Ok(None),
+            _ => Err(error),
+        }
+    };
+
+    // Probe support before creating the destination
+    let first_data = match seek(0, libc::SEEK_DATA) {
+        Ok(offset) => offset,
+        Err(e)
+            if matches!(
+                e.raw_os_error(),
+                Some(libc::EINVAL) | Some(libc::EOPNOTSUPP)
+            ) =>
+        {
+            return Ok(false);
+        }
+        Err(e) => return Err(QbakError::Io(e)),
+    };
+
+    let mut dest_file = fs::File::create(dest)?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut next_data = first_data;
+
+    while let Some(data_start) = next_data {
+        let data_end = seek(data_start, libc::SEEK_HOLE)?.unwrap_or(length);
+
+        source_file.seek(SeekFrom::Start(data_start))?;
+        dest_file.seek(SeekFrom::Start(data_start))?;
+
+        let mut remaining = data_end - data_start;
+        while remaining > 0 {
+            if crate::signal::is_interrupted() {
+                drop(dest_file);
+                let _ = fs::remove_file(dest);
+                return Err(QbakError::Interrupted);
+            }
+
+            let chunk = remaining.min(buffer.len() as u64) as usize;
+            let bytes_read = source_file.read(&mut buffer[..chunk])?;
+            if bytes_read == 0 {
+                break; // File shrank while copying
+            }
+            dest_file.write_all(&buffer[..bytes_read])?;
+            remaining -= bytes_read as u64;
+        }
+
+        next_data = if data_end < length {
+            seek(data_end, libc::SEEK_DATA)?
+        } else {
+            None
+        };
+    }
+
+    // Extend to the apparent size so a trailing hole is preserved
+    dest_file.set_len(length)?;
+    dest_file.flush()?;
+    Ok(true)
+}
+
+/// Sparse-aware copying is only supported on Linux; always fall back to a dense copy
+#[cfg(not(target_os = "linux"))]
+pub fn try_sparse_copy(_source: &Path, _dest: &Path) -> Result<bool> {
+    Ok(false)
+}
+
 /// Format byte size in human-readable format
 pub fn format_size(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    const THRESHOLD: u64 = 1024;
+    format_size_in(bytes, SizeUnits::Binary)
+}
 
-    if bytes < THRESHOLD {
+/// Format byte size in human-readable format using the given units
+pub fn format_size_in(bytes: u64, units: SizeUnits) -> String {
+    let (units, threshold): (&[&str], u64) = match units {
+        SizeUnits::Binary => (&["B", "KB", "MB", "GB", "TB"], 1024),
+        SizeUnits::Si => (&["B", "kB", "MB", "GB", "TB"], 1000),
+        SizeUnits::Iec => (&["B", "KiB", "MiB", "GiB", "TiB"], 1024),
+    };
+
+    if bytes < threshold {
         return format!("{bytes} B");
     }
 
     let mut size = bytes as f64;
     let mut unit_index = 0;
 
-    while size >= THRESHOLD as f64 && unit_index < UNITS.len() - 1 {
-        size /= THRESHOLD as f64;
+    while size >= threshold as f64 && unit_index < units.len() - 1 {
+        size /= threshold as f64;
         unit_index += 1;
     }
 
-    let unit = UNITS[unit_index];
+    let unit = units[unit_index];
     format!("{size:.1} {unit}")
 }
 
+/// Format byte size for display with the configured units, plus the exact byte count
+/// if `show_bytes` is set (e.g. `1.5 MB / 1572864 bytes`)
+pub fn format_size_for(bytes: u64, config: &Config) -> String {
+    let size = format_size_in(bytes, config.size_units);
+    // Small sizes are already shown in bytes
+    if config.show_bytes && size != format!("{bytes} B") {
+        format!("{size} / {bytes} bytes")
+    } else {
+        size
+    }
+}
+
+/// Parse a human-readable size such as `512`, `100M`, `2G` or `1.5GiB` into bytes
+///
+/// `K`/`M`/`G`/`T` are decimal (powers of 1000); add `i` (`Ki`, `Mi`, ...) for binary
+/// units. A trailing `B` is optional, a number without a unit is bytes, and fractional values
+/// are rounded to the nearest byte. Malformed input is a `QbakError::Validation`.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let invalid = || QbakError::validation(format!("Invalid size: {value}"));
+
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier: u64 = match unit.strip_suffix('B').unwrap_or(&unit) {
+        "" => 1,
+        "K" => 1_000,
+        "M" => 1_000_000,
+        "G" => 1_000_000_000,
+        "T" => 1_000_000_000_000,
+        "KI" => 1 << 10,
+        "MI" => 1 << 20,
+        "GI" => 1 << 30,
+        "TI" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+
+    let bytes = (number * multiplier as f64).round();
+    if bytes > u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+/// Parse a human-readable duration such as `90s`, `15m`, `24h` or `1.5d`
+///
+/// Units are `s`, `m`, `h` and `d`; a number without a unit is taken as seconds. Malformed input
+/// is a `QbakError::Validation`.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let invalid = || QbakError::validation(format!("Invalid duration: {value}"));
+
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+
+    let seconds_per_unit = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        _ => return Err(invalid()),
+    };
+
+    Duration::try_from_secs_f64(number * seconds_per_unit).map_err(|_| invalid())
+}
+
+/// Parse a `--newer-than` value into a cutoff time
+///
+/// Accepts a duration relative to now (`24h`, `7d`), a date (`2025-06-01`), a date and
+/// time (`2025-06-01T12:00:00`) or an RFC 3339 timestamp. Dates without an offset are UTC,
+/// like the timestamps in backup names.
+pub fn parse_cutoff_time(value: &str) -> Result<SystemTime> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    if let Ok(age) = parse_duration(value) {
+        return SystemTime::now()
+            .checked_sub(age)
+            .ok_or_else(|| QbakError::validation(format!("Invalid time: {value}")));
+    }
+
+    let value = value.trim();
+    let timestamp = if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        datetime.timestamp_nanos_opt()
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        datetime.and_utc().timestamp_nanos_opt()
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        datetime.and_utc().timestamp_nanos_opt()
+    } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|datetime| datetime.and_utc().timestamp_nanos_opt())
+    } else {
+        None
+    };
+
+    let nanos = timestamp
+        .and_then(|nanos| u64::try_from(nanos).ok())
+        .ok_or_else(|| QbakError::validation(format!("Invalid date or duration: {value}")))?;
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos))
+}
+
+/// Whether a backup target is a symlink that is backed up as a link rather than followed
+///
+/// With `follow_symlinks` off, a symlink passed as the target is recreated as a link named
+/// after it, just like symlinks inside a directory. Only Unix can recreate links; elsewhere
+/// the target is always followed. `dereference_top_level` follows targets regardless, leaving
+/// `follow_symlinks` to decide only for links found inside directories.
+pub fn is_preserved_symlink(path: &Path, config: &Config) -> bool {
+    cfg!(unix) && !config.follow_symlinks && !config.dereference_top_level && path.is_symlink()
+}
+
+/// Whether an I/O error may go away if the operation is retried
+///
+/// Timeouts and interrupted calls qualify everywhere; on Unix so do `EIO` and (on Linux)
+/// `ESTALE`, which network filesystems report for dropped connections. Errors like a
+/// missing file or denied permission are permanent.
+pub(crate) fn is_transient_io_error(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    if matches!(
+        error.kind(),
+        ErrorKind::TimedOut | ErrorKind::Interrupted | ErrorKind::WouldBlock
+    ) {
+        return true;
+    }
+    match error.raw_os_error() {
+        // EIO has the same number on every Unix
+        #[cfg(unix)]
+        Some(5) => true,
+        #[cfg(target_os = "linux")]
+        Some(libc::ESTALE) => true,
+        _ => false,
+    }
+}
+
+/// Whether `path` is on a network filesystem, where every directory listing is a round trip
+///
+/// Detects NFS, SMB/CIFS, 9P, Ceph, AFS and Coda on Linux; always false elsewhere or if
+/// the filesystem can't be queried.
+pub fn is_remote_filesystem(path: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        const REMOTE_MAGICS: &[u32] = &[
+            0x6969,      // NFS
+            0x517B,      // SMB
+            0xFF53_4D42, // CIFS
+            0xFE53_4D42, // SMB2
+            0x0102_1997, // 9P
+            0x00C3_6400, // Ceph
+            0x5346_414F, // AFS
+            0x7375_7245, // Coda
+        ];
+
+        let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return false;
+        }
+        REMOTE_MAGICS.contains(&(stat.f_type as u32))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
 /// Check if a path is hidden (starts with .)
 pub fn is_hidden(path: &Path) -> bool {
     path.file_name()
@@ -291,32 +872,190 @@ pub fn is_hidden(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
+/// Include/exclude patterns compiled once for a directory walk
+///
+/// Patterns are matched against the entry's file name. Exclude patterns take
+/// precedence over include patterns. Include patterns only restrict files:
+/// directories are always traversed so matching files further down are found.
+///
+/// Exclude patterns follow gitignore rules: the last matching pattern decides, a leading
+/// `!` re-includes what earlier patterns excluded, and a trailing `/` matches only
+/// directories.
+#[derive(Debug, Default)]
+pub struct Filters {
+    include: Vec<glob::Pattern>,
+    exclude: Vec<ExcludeRule>,
+}
+
+impl Filters {
+    /// Compile the configured patterns, failing on the first invalid one
+    pub fn new(config: &Config) -> Result<Self> {
+        let include = config
+            .include_patterns
+            .iter()
+            .map(|pattern| compile_pattern(pattern, pattern))
+            .collect::<Result<_>>()?;
+        let exclude = config
+            .exclude_patterns
+            .iter()
+            .map(|pattern| ExcludeRule::parse(pattern))
+            .collect::<Result<_>>()?;
+        Ok(Self { include, exclude })
+    }
+
+    /// Check whether a directory entry passes the include/exclude patterns
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => return true,
+        };
+
+        let mut excluded = false;
+        for rule in &self.exclude {
+            if (is_dir || !rule.dir_only) && rule.glob.matches(name) {
+                excluded = !rule.negated;
+            }
+        }
+        if excluded {
+            return false;
+        }
+
+        if is_dir || self.include.is_empty() {
+            return true;
+        }
+
+        self.include.iter().any(|pattern| pattern.matches(name))
+    }
+}
+
+/// An exclude pattern split into its gitignore-style parts
+#[derive(Debug)]
+struct ExcludeRule {
+    glob: glob::Pattern,
+    /// `!pattern`: re-include entries excluded by earlier patterns
+    negated: bool,
+    /// `pattern/`: only match directories
+    dir_only: bool,
+}
+
+impl ExcludeRule {
+    fn parse(pattern: &str) -> Result<Self> {
+        let (negated, glob) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            // `\!` and `\#` escape a literal leading character
+            None => (false, pattern.strip_prefix('\\').unwrap_or(pattern)),
+        };
+        let (dir_only, glob) = match glob.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, glob),
+        };
+        // Patterns match names, so a leading `/` anchor has no further effect
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+        Ok(Self {
+            glob: compile_pattern(glob, pattern)?,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+/// Compile `glob`, reporting errors against the `pattern` it was written as
+fn compile_pattern(glob: &str, pattern: &str) -> Result<glob::Pattern> {
+    glob::Pattern::new(glob)
+        .map_err(|e| QbakError::validation(format!("Invalid pattern '{pattern}': {e}")))
+}
+
+/// Validate that all glob patterns can be compiled
+pub fn validate_patterns(patterns: &[String]) -> Result<()> {
+    for pattern in patterns {
+        ExcludeRule::parse(pattern)?;
+    }
+    Ok(())
+}
+
+/// Read exclude patterns from a gitignore-style file
+///
+/// Blank lines and lines starting with `#` are ignored; trailing whitespace is trimmed.
+pub fn read_exclude_file(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        QbakError::validation(format!(
+            "Could not read exclude file {}: {e}",
+            path.display()
+        ))
+    })?;
+    Ok(parse_exclude_patterns(&content))
+}
+
+fn parse_exclude_patterns(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::default_config;
     use std::fs::File;
     use tempfile::tempdir;
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_validate_source_rejects_fifo() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        let fifo = dir.path().join("pipe");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+
+        let config = default_config();
+        match validate_source(&fifo, &config) {
+            Err(QbakError::UnsupportedFileType { kind, .. }) => assert_eq!(kind, "FIFO"),
+            other => panic!("Expected UnsupportedFileType, got {other:?}"),
+        }
+        assert_eq!(
+            special_file_kind(&fs::symlink_metadata(&fifo).unwrap().file_type()),
+            Some("FIFO")
+        );
+        assert_eq!(
+            special_file_kind(&fs::metadata(dir.path()).unwrap().file_type()),
+            None
+        );
+    }
+
     #[test]
     fn test_validate_source() {
+        let config = default_config();
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("test.txt");
         File::create(&file_path).unwrap();
 
         // Valid file
-        assert!(validate_source(&file_path).is_ok());
+        assert!(validate_source(&file_path, &config).is_ok());
 
         // Non-existent file
         let missing_path = dir.path().join("missing.txt");
-        assert!(validate_source(&missing_path).is_err());
+        assert!(validate_source(&missing_path, &config).is_err());
 
         // Path traversal attempt
+        let restricted = Config {
+            allow_outside_cwd: false,
+            ..default_config()
+        };
         let traversal_path = Path::new("../../../etc/passwd");
-        assert!(validate_source(traversal_path).is_err());
+        assert!(validate_source(traversal_path, &restricted).is_err());
     }
 
     #[test]
     fn test_validate_source_path_traversal() {
+        let config = Config {
+            allow_outside_cwd: false,
+            ..default_config()
+        };
         let paths_with_traversal = vec![
             "../etc/passwd",
             "../../root/.ssh",
@@ -327,7 +1066,7 @@ mod tests {
 
         for path_str in paths_with_traversal {
             let path = Path::new(path_str);
-            let result = validate_source(path);
+            let result = validate_source(path, &config);
             assert!(result.is_err(), "Path {path_str} should be rejected");
 
             // The error could be PathTraversal or SourceNotFound since the path doesn't exist
@@ -340,6 +1079,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate_source_dotted_names() {
+        let config = default_config();
+        let dir = tempdir().unwrap();
+
+        // Two dots inside a name are not a parent-directory component
+        let dotted_dir = dir.path().join("release..old");
+        fs::create_dir(&dotted_dir).unwrap();
+        for name in ["foo..bar.txt", "..hidden", "trailing.."] {
+            let file = dotted_dir.join(name);
+            File::create(&file).unwrap();
+            assert!(validate_source(&file, &config).is_ok(), "{name} rejected");
+        }
+
+        assert!(has_parent_component(Path::new("a/../b")));
+        assert!(has_parent_component(Path::new("../b")));
+        assert!(!has_parent_component(Path::new("./foo..bar")));
+        assert!(!has_parent_component(Path::new(
+            "/tmp/release..old/..hidden"
+        )));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_validate_source_outside_cwd() {
+        let allowed = default_config();
+        let restricted = Config {
+            allow_outside_cwd: false,
+            ..default_config()
+        };
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+
+        // Absolute paths elsewhere are fine unless restricted
+        let hosts = Path::new("/etc/hosts");
+        if hosts.exists() && !hosts.canonicalize().unwrap().starts_with(&cwd) {
+            assert!(validate_source(hosts, &allowed).is_ok());
+            assert!(matches!(
+                validate_source(hosts, &restricted),
+                Err(QbakError::PathTraversal { .. })
+            ));
+        }
+
+        // A relative ../ path to a sibling tree
+        let sibling = tempdir().unwrap();
+        let file = sibling.path().canonicalize().unwrap().join("file.txt");
+        File::create(&file).unwrap();
+        let up = "../".repeat(cwd.components().count() - 1);
+        let relative = PathBuf::from(up).join(file.strip_prefix("/").unwrap());
+        assert!(validate_source(&relative, &allowed).is_ok());
+        assert!(matches!(
+            validate_source(&relative, &restricted),
+            Err(QbakError::PathTraversal { .. })
+        ));
+
+        // A symlink inside the cwd whose target escapes it
+        let inside = tempfile::Builder::new().tempdir_in(&cwd).unwrap();
+        let inside_file = inside.path().join("inside.txt");
+        File::create(&inside_file).unwrap();
+        let link = inside.path().join("escape");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        assert!(validate_source(&inside_file, &restricted).is_ok());
+        assert!(validate_source(&link, &allowed).is_ok());
+        assert!(matches!(
+            validate_source(&link, &restricted),
+            Err(QbakError::PathTraversal { .. })
+        ));
+    }
+
     #[test]
     fn test_calculate_size() {
         let dir = tempdir().unwrap();
@@ -382,6 +1189,38 @@ mod tests {
         assert_eq!(size, 0);
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_calculate_size_counts_hardlinks_once() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("tree");
+        std::fs::create_dir_all(source.join("sub")).unwrap();
+        std::fs::write(source.join("data.bin"), vec![0u8; 1000]).unwrap();
+        std::fs::hard_link(source.join("data.bin"), source.join("sub/link.bin")).unwrap();
+        std::fs::write(source.join("other.txt"), "12345").unwrap();
+
+        assert_eq!(calculate_size(&source).unwrap(), 1005);
+    }
+
+    #[test]
+    fn test_is_transient_io_error() {
+        use std::io::{Error, ErrorKind};
+
+        assert!(is_transient_io_error(&Error::from(ErrorKind::TimedOut)));
+        assert!(is_transient_io_error(&Error::from(ErrorKind::Interrupted)));
+        assert!(!is_transient_io_error(&Error::from(ErrorKind::NotFound)));
+        assert!(!is_transient_io_error(&Error::from(
+            ErrorKind::PermissionDenied
+        )));
+        #[cfg(unix)]
+        {
+            assert!(is_transient_io_error(&Error::from_raw_os_error(5)));
+            // ENOENT and EACCES
+            assert!(!is_transient_io_error(&Error::from_raw_os_error(2)));
+            assert!(!is_transient_io_error(&Error::from_raw_os_error(13)));
+        }
+    }
+
     #[test]
     fn test_format_size() {
         assert_eq!(format_size(0), "0 B");
@@ -484,7 +1323,52 @@ mod tests {
         std::fs::write(&file_path, "test content").unwrap();
 
         // Should pass since we mock infinite space
-        assert!(check_available_space(&file_path, dir.path()).is_ok());
+        assert!(check_available_space(&file_path, dir.path(), &default_config()).is_ok());
+    }
+
+    #[test]
+    fn test_check_available_space_for() {
+        let dir = tempdir().unwrap();
+
+        let config = default_config();
+
+        assert!(check_available_space_for(0, dir.path(), &config).is_ok());
+        assert!(matches!(
+            check_available_space_for(u64::MAX / 2, dir.path(), &config),
+            Err(QbakError::InsufficientSpace { .. })
+        ));
+
+        // The free-space floor applies even to an empty backup
+        let config = Config {
+            min_free_space: u64::MAX / 2,
+            ..default_config()
+        };
+        assert!(matches!(
+            check_available_space_for(0, dir.path(), &config),
+            Err(QbakError::InsufficientSpace { needed, buffer, .. })
+                if needed == u64::MAX / 2 && buffer == u64::MAX / 2
+        ));
+    }
+
+    #[test]
+    fn test_space_buffer() {
+        let mut config = default_config();
+        assert_eq!(space_buffer(1000, &config), 100);
+
+        config.space_buffer_percent = 0;
+        assert_eq!(space_buffer(1000, &config), 0);
+
+        // The absolute minimum wins for small backups
+        config.space_buffer_percent = 10;
+        config.space_buffer_min = 4096;
+        assert_eq!(space_buffer(1000, &config), 4096);
+        assert_eq!(space_buffer(1_000_000, &config), 100_000);
+
+        // Out-of-range percentages are clamped
+        config.space_buffer_percent = 500;
+        config.space_buffer_min = 0;
+        assert_eq!(space_buffer(1000, &config), 1000);
+        assert_eq!(space_buffer(u64::MAX, &config), u64::MAX);
     }
 
     #[test]
@@ -535,7 +1419,32 @@ mod tests {
         std::fs::create_dir_all(&subdir).unwrap();
 
         // Should validate successfully for directories
-        assert!(validate_source(&subdir).is_ok());
+        let config = default_config();
+        assert!(validate_source(&subdir, &config).is_ok());
+    }
+
+    #[test]
+    fn test_format_size_in_units() {
+        assert_eq!(format_size_in(999, SizeUnits::Si), "999 B");
+        assert_eq!(format_size_in(1000, SizeUnits::Si), "1.0 kB");
+        assert_eq!(format_size_in(1_500_000, SizeUnits::Si), "1.5 MB");
+        assert_eq!(format_size_in(1_000_000_000, SizeUnits::Si), "1.0 GB");
+        assert_eq!(format_size_in(1024, SizeUnits::Iec), "1.0 KiB");
+        assert_eq!(
+            format_size_in(1024 * 1024 * 3 / 2, SizeUnits::Iec),
+            "1.5 MiB"
+        );
+        assert_eq!(format_size_in(1024, SizeUnits::Binary), format_size(1024));
+    }
+
+    #[test]
+    fn test_format_size_for() {
+        let mut config = default_config();
+        assert_eq!(format_size_for(1572864, &config), "1.5 MB");
+
+        config.show_bytes = true;
+        assert_eq!(format_size_for(1572864, &config), "1.5 MB / 1572864 bytes");
+        assert_eq!(format_size_for(512, &config), "512 B");
     }
 
     #[test]
@@ -581,6 +1490,43 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_validate_backup_location() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("src");
+        fs::create_dir_all(source.join("lib")).unwrap();
+        let file = source.join("main.rs");
+        File::create(&file).unwrap();
+
+        // Next to the source, or in a separate directory that doesn't exist yet
+        assert!(validate_backup_location(&source, &dir.path().join("src-backup")).is_ok());
+        assert!(validate_backup_location(&source, &dir.path().join("out/src-backup")).is_ok());
+        assert!(validate_backup_location(&file, &source.join("main-backup.rs")).is_ok());
+
+        // Inside the tree being backed up, even through `..` or a missing directory
+        for inside in [
+            source.join("backups/src-backup"),
+            source.join("lib/../src-backup"),
+            source.clone(),
+        ] {
+            assert!(matches!(
+                validate_backup_location(&source, &inside),
+                Err(QbakError::Validation { .. })
+            ));
+        }
+        assert!(validate_backup_location(&file, &file).is_err());
+
+        // Unresolvable sources are reported elsewhere
+        assert!(validate_backup_location(&dir.path().join("missing"), &source).is_ok());
+    }
+
+    #[test]
+    fn test_validate_backup_filename_bare_name() {
+        // A name without a directory component refers to the current directory
+        let name = format!("qbak-test-{}-backup.txt", std::process::id());
+        assert!(validate_backup_filename(Path::new(&name)).is_ok());
+    }
+
     #[test]
     fn test_validate_backup_filename_parent_not_writable() {
         // Test case where parent directory might not be writable
@@ -605,4 +1551,221 @@ mod tests {
         let size = calculate_size(&test_dir).unwrap();
         assert_eq!(size, 0);
     }
+
+    #[test]
+    fn test_filters_match() {
+        let mut config = default_config();
+
+        // No patterns - everything passes
+        let filters = Filters::new(&config).unwrap();
+        assert!(filters.matches(Path::new("main.rs"), false));
+
+        config.include_patterns = vec!["*.rs".to_string(), "*.toml".to_string()];
+        let filters = Filters::new(&config).unwrap();
+        assert!(filters.matches(Path::new("src/main.rs"), false));
+        assert!(filters.matches(Path::new("Cargo.toml"), false));
+        assert!(!filters.matches(Path::new("README.md"), false));
+
+        // Directories are always traversed when only includes are set
+        assert!(filters.matches(Path::new("src"), true));
+
+        // Exclude takes precedence over include
+        config.exclude_patterns = vec!["build.rs".to_string(), "target".to_string()];
+        let filters = Filters::new(&config).unwrap();
+        assert!(!filters.matches(Path::new("build.rs"), false));
+        assert!(!filters.matches(Path::new("target"), true));
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let dir = tempdir().unwrap();
+        let file_a = dir.path().join("a.txt");
+        let file_b = dir.path().join("b.txt");
+        let file_c = dir.path().join("c.txt");
+        std::fs::write(&file_a, "same content").unwrap();
+        std::fs::write(&file_b, "same content").unwrap();
+        std::fs::write(&file_c, "other content").unwrap();
+
+        let hash_a = sha256_file(&file_a).unwrap();
+        assert_eq!(hash_a, sha256_file(&file_b).unwrap());
+        assert_ne!(hash_a, sha256_file(&file_c).unwrap());
+
+        // SHA-256 of the empty string
+        let empty = dir.path().join("empty.txt");
+        File::create(&empty).unwrap();
+        assert_eq!(sha256_file(&empty).unwrap()[..4], [0xe3, 0xb0, 0xc4, 0x42]);
+    }
+
+    #[test]
+    fn test_exclude_patterns_gitignore_rules() {
+        let content = "# build output\n\ntarget/\n*.log\n!keep.log\n\\#notes.txt  \n";
+        let patterns = parse_exclude_patterns(content);
+        assert_eq!(patterns, ["target/", "*.log", "!keep.log", "\\#notes.txt"]);
+        assert!(validate_patterns(&patterns).is_ok());
+
+        let config = Config {
+            exclude_patterns: patterns,
+            ..default_config()
+        };
+        let filters = Filters::new(&config).unwrap();
+        assert!(!filters.matches(Path::new("target"), true));
+        // `target/` only matches directories
+        assert!(filters.matches(Path::new("target"), false));
+        assert!(!filters.matches(Path::new("debug.log"), false));
+        assert!(filters.matches(Path::new("keep.log"), false));
+        assert!(!filters.matches(Path::new("#notes.txt"), false));
+        assert!(filters.matches(Path::new("main.rs"), false));
+    }
+
+    #[test]
+    fn test_validate_patterns() {
+        assert!(validate_patterns(&["*.rs".to_string(), "data-?.csv".to_string()]).is_ok());
+        assert!(validate_patterns(&["[unclosed".to_string()]).is_err());
+
+        // An invalid pattern fails to compile instead of never matching
+        for config in [
+            Config {
+                include_patterns: vec!["[unclosed".to_string()],
+                ..default_config()
+            },
+            Config {
+                exclude_patterns: vec!["!/[unclosed/".to_string()],
+                ..default_config()
+            },
+        ] {
+            assert!(Filters::new(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_mtime_nanos() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "content").unwrap();
+
+        filetime::set_file_mtime(&file, FileTime::from_unix_time(1_700_000_000, 250)).unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+        assert_eq!(mtime_nanos(&metadata), 1_700_000_000_000_000_250);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("100M").unwrap(), 100_000_000);
+        assert_eq!(parse_size("2G").unwrap(), 2_000_000_000);
+        assert_eq!(parse_size("2g").unwrap(), 2_000_000_000);
+        assert_eq!(parse_size("10KB").unwrap(), 10_000);
+        assert_eq!(parse_size("1Ki").unwrap(), 1024);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 << 29);
+        assert_eq!(parse_size(" 4 T ").unwrap(), 4_000_000_000_000);
+        assert_eq!(parse_size("0.5K").unwrap(), 500);
+    }
+
+    #[test]
+    fn test_parse_size_edge_cases() {
+        // Zero with any unit, and fractional values rounded to whole bytes
+        assert_eq!(parse_size("0G").unwrap(), 0);
+        assert_eq!(parse_size("0.0TiB").unwrap(), 0);
+        assert_eq!(parse_size(".5M").unwrap(), 500_000);
+        assert_eq!(parse_size("10.").unwrap(), 10);
+        assert_eq!(parse_size("1.5").unwrap(), 2);
+        assert_eq!(parse_size("0.4").unwrap(), 0);
+        assert_eq!(parse_size("1.25Mi").unwrap(), 1_310_720);
+
+        // Missing unit means bytes; a bare `B` or space before the unit is fine
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("7B").unwrap(), 7);
+        assert_eq!(parse_size("10 MiB").unwrap(), 10 << 20);
+        assert_eq!(parse_size("3kib").unwrap(), 3072);
+
+        // Largest values still fit; anything beyond u64 is rejected
+        assert_eq!(parse_size("16000000T").unwrap(), 16_000_000_000_000_000_000);
+        assert!(parse_size("20000000T").is_err());
+
+        for invalid in [
+            "", " ", ".", "M", "-1", "+1", "1.2.3", "10X", "1e3", "inf", "NaN", "1 2M", "5MM",
+            "1Bi", "2iB",
+        ] {
+            assert!(
+                matches!(parse_size(invalid), Err(QbakError::Validation { .. })),
+                "{invalid:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("1.5d").unwrap(), Duration::from_secs(129600));
+        assert_eq!(parse_duration("2H").unwrap(), Duration::from_secs(7200));
+    }
+
+    #[test]
+    fn test_parse_duration_edge_cases() {
+        // Zero with any unit, and fractional values down to sub-second precision
+        assert_eq!(parse_duration("0d").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("0.0h").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("0.5m").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration(".25h").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2.").unwrap(), Duration::from_secs(2));
+
+        // Missing unit means seconds; whitespace around the unit is ignored
+        assert_eq!(parse_duration("3600").unwrap(), Duration::from_secs(3600));
+        assert_eq!(
+            parse_duration(" 7 d ").unwrap(),
+            Duration::from_secs(604_800)
+        );
+        assert_eq!(parse_duration("10S").unwrap(), Duration::from_secs(10));
+
+        // Durations too long to represent are rejected rather than wrapping
+        assert!(parse_duration("1000000000000000000000d").is_err());
+
+        for invalid in [
+            "",
+            " ",
+            ".",
+            "h",
+            "-1h",
+            "+1h",
+            "1w",
+            "1ms",
+            "1.2.3s",
+            "1 2s",
+            "1hh",
+            "inf",
+            "NaN",
+            "2025-06-01",
+        ] {
+            assert!(
+                matches!(parse_duration(invalid), Err(QbakError::Validation { .. })),
+                "{invalid:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_cutoff_time() {
+        let date = parse_cutoff_time("2025-06-01").unwrap();
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_748_736_000);
+        assert_eq!(date, expected);
+        assert_eq!(parse_cutoff_time("2025-06-01T00:00:00").unwrap(), expected);
+        assert_eq!(
+            parse_cutoff_time("2025-06-01T02:00:00+02:00").unwrap(),
+            expected
+        );
+
+        // Relative durations count back from now
+        let cutoff = parse_cutoff_time("24h").unwrap();
+        let age = SystemTime::now().duration_since(cutoff).unwrap();
+        assert!(age >= Duration::from_secs(86400) && age < Duration::from_secs(86460));
+
+        assert!(parse_cutoff_time("yesterday").is_err());
+        assert!(parse_cutoff_time("2025-13-01").is_err());
+    }
 }